@@ -304,7 +304,10 @@ macro_rules! build_ecs {
 
         pub use self::_ecs_inner::ComponentNum;
 
+        // Components missing from serialized data, eg. ones added after the data was saved, are
+        // left empty.
         #[derive(Serialize, Deserialize)]
+        #[serde(default)]
         pub struct _ComponentStore {
             $(pub $compname: $crate::ComponentData<$comptype>),+
         }
@@ -11,9 +11,10 @@ pub trait GridNode: PartialEq + Eq + Clone + Hash + PartialOrd + Ord {
 /// A pathfinding map structure.
 ///
 /// A Dijkstra map lets you run pathfinding from any graph node it covers
-/// towards or away from the target nodes of the map. Currently the structure
-/// only supports underlying graphs with a fixed grid graph where the
-/// neighbors of each node must be the adjacent grid cells of that node.
+/// towards or away from the target nodes of the map. Maps over fixed grid
+/// graphs, where the neighbors of each node are the adjacent grid cells of
+/// that node, are built with `new`. Graphs with irregular connections, like
+/// portals, can be covered with `from_fn`.
 pub struct Dijkstra<N> {
     pub weights: HashMap<N, u32>,
 }
@@ -22,6 +23,31 @@ impl<N: GridNode> Dijkstra<N> {
     /// Create a new Dijkstra map up to limit distance from goals, omitting
     /// nodes for which the is_valid predicate returns false.
     pub fn new<F: Fn(&N) -> bool>(goals: Vec<N>, is_valid: F, limit: u32) -> Dijkstra<N> {
+        Dijkstra::from_fn(
+            goals,
+            |n| n.neighbors().into_iter().filter(|m| is_valid(m)).collect(),
+            limit,
+        )
+    }
+
+    /// Return the neighbors of a cell (if any), sorted from downhill to
+    /// uphill.
+    pub fn sorted_neighbors(&self, node: &N) -> Vec<N> {
+        let mut ret = Vec::new();
+        for n in &node.neighbors() {
+            if let Some(w) = self.weights.get(n) {
+                ret.push((w, n.clone()));
+            }
+        }
+        ret.sort_by(|&(w1, _), &(w2, _)| w1.cmp(w2));
+        ret.into_iter().map(|(_, n)| n).collect()
+    }
+}
+
+impl<N: Eq + Hash + Clone> Dijkstra<N> {
+    /// Create a new Dijkstra map up to limit distance from goals using a
+    /// function that lists the valid neighbors of a node.
+    pub fn from_fn<F: Fn(&N) -> Vec<N>>(goals: Vec<N>, neighbors: F, limit: u32) -> Dijkstra<N> {
         assert!(!goals.is_empty());
 
         let mut weights = HashMap::new();
@@ -38,8 +64,8 @@ impl<N: GridNode> Dijkstra<N> {
 
             let mut new_edge = HashSet::new();
             for n in &edge {
-                for m in n.neighbors() {
-                    if is_valid(&m) && !weights.contains_key(&m) {
+                for m in neighbors(n) {
+                    if !weights.contains_key(&m) {
                         new_edge.insert(m);
                     }
                 }
@@ -54,19 +80,6 @@ impl<N: GridNode> Dijkstra<N> {
 
         Dijkstra { weights }
     }
-
-    /// Return the neighbors of a cell (if any), sorted from downhill to
-    /// uphill.
-    pub fn sorted_neighbors(&self, node: &N) -> Vec<N> {
-        let mut ret = Vec::new();
-        for n in &node.neighbors() {
-            if let Some(w) = self.weights.get(n) {
-                ret.push((w, n.clone()));
-            }
-        }
-        ret.sort_by(|&(w1, _), &(w2, _)| w1.cmp(w2));
-        ret.into_iter().map(|(_, n)| n).collect()
-    }
 }
 
 /// Find A* path in freeform graph.
@@ -180,4 +193,24 @@ mod test {
             astar_path(8, &12, |x| neighbors(8, x))
         );
     }
+
+    #[test]
+    fn test_dijkstra_from_fn() {
+        // Line graph where 0 also connects directly to 10.
+        let neighbors = |&x: &i32| {
+            let mut ret = vec![x - 1, x + 1];
+            if x == 0 {
+                ret.push(10);
+            }
+            ret
+        };
+
+        let map = Dijkstra::from_fn(vec![0], neighbors, 3);
+        assert_eq!(map.weights.get(&0), Some(&0));
+        assert_eq!(map.weights.get(&-2), Some(&2));
+        assert_eq!(map.weights.get(&-3), None);
+        assert_eq!(map.weights.get(&10), Some(&1));
+        assert_eq!(map.weights.get(&11), Some(&2));
+        assert_eq!(map.weights.get(&12), None);
+    }
 }
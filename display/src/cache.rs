@@ -11,6 +11,7 @@ lazy_static! {
     static ref TERRAIN_BRUSHES: VecMap<Arc<Brush>> = init::terrain_brushes();
     static ref ENTITY_BRUSHES: VecMap<Arc<Brush>> = init::entity_brushes();
     static ref MISC_BRUSHES: VecMap<Arc<Brush>> = init::misc_brushes();
+    static ref ABILITY_BRUSHES: VecMap<Arc<Brush>> = init::ability_brushes();
     static ref FONT: Arc<FontData> = Arc::new(vitral::add_tilesheet_font(
         "font",
         PngBytes(include_bytes!("../assets/font.png")),
@@ -39,6 +40,14 @@ pub fn entity(e: world::Icon) -> Arc<Brush> {
     )
 }
 
+pub fn ability(a: world::Ability) -> Arc<Brush> {
+    Arc::clone(
+        ABILITY_BRUSHES
+            .get(a as usize)
+            .unwrap_or_else(|| panic!("No brush for ability {:?}", a)),
+    )
+}
+
 pub fn misc(e: Icon) -> Arc<Brush> {
     Arc::clone(
         MISC_BRUSHES
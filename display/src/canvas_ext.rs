@@ -4,7 +4,7 @@ use euclid::default::Point2D;
 use euclid::vec2;
 use vitral::Canvas;
 use vitral::{color, Align};
-use world::Icon;
+use world::{Ability, Icon};
 
 /// Magog-specific extensions to Canvas API.
 pub trait CanvasExt {
@@ -13,6 +13,9 @@ pub trait CanvasExt {
     fn draw_item_icon(&mut self, pos: Point2D<i32>, icon: Icon, count: u32) {
         self.draw_entity(pos + vec2(0, -2), icon, count); // TODO
     }
+
+    /// Draw the spellbook icon of an innate ability.
+    fn draw_ability_icon(&mut self, pos: Point2D<i32>, ability: Ability);
 }
 
 impl CanvasExt for Canvas<'_> {
@@ -38,4 +41,15 @@ impl CanvasExt for Canvas<'_> {
             );
         }
     }
+
+    fn draw_ability_icon(&mut self, pos: Point2D<i32>, ability: Ability) {
+        for splat in &cache::ability(ability)[0] {
+            self.draw_image_2color(
+                &splat.image,
+                pos + vec2(0, -2) - splat.offset.to_untyped(),
+                splat.color,
+                splat.back_color,
+            );
+        }
+    }
 }
@@ -164,14 +164,49 @@ pub fn entity_brushes() -> VecMap<Arc<Brush>> {
 
     ret.insert(PlaceholderMob as usize, Builder::new("assets/mobs.png").color(LIGHTGRAY).mob(0*32, 3*32).finish());
     ret.insert(InvisibleMob as usize, Builder::new("assets/mobs.png").color(LIGHTGRAY).mob(1*32, 3*32).finish());
+    ret.insert(Corpse as usize, Builder::new("assets/fx.png").color(DARKRED).tile(4*32, 0).finish());
 
     ret.insert(Sword as usize, Builder::new("assets/items.png").color(LIGHTGRAY).item(0*16, 0*16).finish());
+    ret.insert(Bow as usize, Builder::new("assets/items.png").color(BURLYWOOD).item(3*16, 0*16).finish());
+    ret.insert(Arrow as usize, Builder::new("assets/items.png").color(LIGHTGRAY).item(3*16, 0*16).finish());
     ret.insert(Helmet as usize, Builder::new("assets/items.png").color(LIGHTGRAY).item(1*16, 0*16).finish());
+    ret.insert(Shield as usize, Builder::new("assets/items.png").color(BURLYWOOD).item(4*16, 0*16).finish());
     ret.insert(Armor as usize, Builder::new("assets/items.png").color(LIGHTGRAY).item(6*16, 0*16).finish());
 
     ret.insert(Scroll1 as usize, Builder::new("assets/items.png").color(LIGHTYELLOW).item(0*16, 1*16).finish());
+    ret.insert(Scroll2 as usize, Builder::new("assets/items.png").color(LIGHTGREEN).item(0*16, 1*16).finish());
+    ret.insert(Scroll3 as usize, Builder::new("assets/items.png").color(LIGHTBLUE).item(0*16, 1*16).finish());
+    ret.insert(Scroll4 as usize, Builder::new("assets/items.png").color(LIGHTPINK).item(0*16, 1*16).finish());
+    ret.insert(Scroll5 as usize, Builder::new("assets/items.png").color(WHEAT).item(0*16, 1*16).finish());
     ret.insert(Wand1 as usize, Builder::new("assets/items.png").color(RED).item(3*16, 0*16).finish());
     ret.insert(Wand2 as usize, Builder::new("assets/items.png").color(CYAN).item(3*16, 0*16).finish());
+    ret.insert(Wand3 as usize, Builder::new("assets/items.png").color(HOTPINK).item(3*16, 0*16).finish());
+    ret.insert(Wand4 as usize, Builder::new("assets/items.png").color(BURLYWOOD).item(3*16, 0*16).finish());
+    ret.insert(Wand5 as usize, Builder::new("assets/items.png").color(IVORY).item(3*16, 0*16).finish());
+    ret.insert(Amulet1 as usize, Builder::new("assets/items.png").color(GOLD).item(2*16, 0*16).finish());
+    ret.insert(Amulet2 as usize, Builder::new("assets/items.png").color(MEDIUMSEAGREEN).item(2*16, 0*16).finish());
+    ret.insert(Amulet3 as usize, Builder::new("assets/items.png").color(SILVER).item(2*16, 0*16).finish());
+    ret.insert(Torch as usize, Builder::new("assets/items.png").color(ORANGE).item(3*16, 0*16).finish());
+    ret.insert(Ration as usize, Builder::new("assets/items.png").color(BURLYWOOD).item(7*16, 0*16).finish());
+    ret
+}
+
+#[rustfmt::skip]
+pub fn ability_brushes() -> VecMap<Arc<Brush>> {
+    use world::Ability::*;
+    let mut ret = VecMap::new();
+
+    ret.insert(LightningBolt as usize, Builder::new("assets/abilities.png").color(YELLOW).item(64, 0).finish());
+    ret.insert(Regenerate as usize, Builder::new("assets/abilities.png").color(LIGHTGREEN).item(64, 0).finish());
+    ret.insert(Invisibility as usize, Builder::new("assets/abilities.png").color(LIGHTBLUE).item(64, 0).finish());
+    ret.insert(Identify as usize, Builder::new("assets/abilities.png").color(WHEAT).item(64, 0).finish());
+    ret.insert(Heal as usize, Builder::new("assets/abilities.png").color(LIMEGREEN).item(32, 0).finish());
+    ret.insert(Fireball as usize, Builder::new("assets/abilities.png").color(ORANGE).item(16, 0).finish());
+    ret.insert(Confuse as usize, Builder::new("assets/abilities.png").color(MAGENTA).item(0, 0).finish());
+    ret.insert(Charm as usize, Builder::new("assets/abilities.png").color(HOTPINK).item(0, 0).finish());
+    ret.insert(PoisonSpit as usize, Builder::new("assets/abilities.png").color(GREEN).item(48, 0).finish());
+    ret.insert(Smite as usize, Builder::new("assets/abilities.png").color(GOLD).item(64, 0).finish());
+    ret.insert(FrostBolt as usize, Builder::new("assets/abilities.png").color(LIGHTCYAN).item(16, 0).finish());
     ret
 }
 
@@ -226,5 +261,25 @@ pub fn misc_brushes() -> VecMap<Arc<Brush>> {
         .tile(6*32, 1*32).merge()
         .finish());
 
+    ret.insert(Missile as usize, Builder::new("assets/items.png")
+        .color(LIGHTGRAY)
+        .item(3*16, 0*16)
+        .finish());
+
     ret
 }
+
+#[cfg(test)]
+mod test {
+    use super::{ability_brushes, load_graphics};
+    use world::Ability;
+
+    #[test]
+    fn test_ability_brushes() {
+        load_graphics();
+        let brushes = ability_brushes();
+        for &a in Ability::iter() {
+            assert!(brushes.contains_key(a as usize), "No brush for ability {:?}", a);
+        }
+    }
+}
@@ -16,7 +16,8 @@ use std::iter::FromIterator;
 use std::sync::Arc;
 use vitral::{color, Canvas};
 use world::{
-    AnimState, FovStatus, LerpLocation, Location, PhysicsSpace, PhysicsVector, Sector, World,
+    AnimState, FovStatus, LerpLocation, Location, PhysicsSpace, PhysicsVector, Sector, Status,
+    World,
 };
 
 /// Useful general constant for cell dimension ops.
@@ -89,16 +90,6 @@ impl WorldView {
 
             let mut loc = origins[0] + chart_pos;
 
-            // Relative player pos to currently drawn thing.
-            let mut player_pos = None;
-            if let Some(player) = world.player() {
-                if let Some(player_loc) = world.location(player) {
-                    if let Some(vec) = player_loc.v2_at(loc) {
-                        player_pos = Some(vec);
-                    }
-                }
-            }
-
             let in_map_memory;
 
             // If the chart position is in live FOV, we want to show the deepest stack coordinate.
@@ -141,22 +132,17 @@ impl WorldView {
                         1.0
                     } else {
                         let normal = angle.normal();
-                        // When underground, use the player position as light position instead of
-                        // constant-dir sunlight.
-                        let light_dir = if world.is_underground(loc) && player_pos.is_some() {
-                            // XXX: Things get screwy if location is the same as player location
-                            // (eg. when player stands in a doorway), hack around that by
-                            // displacing the zero position.
-                            let center_pos = if player_pos == Some(vec2(0, 0)) {
-                                vec2(1, 1)
-                            } else {
-                                player_pos.unwrap()
-                            };
-                            center_pos.project::<PhysicsSpace>().to_3d().normalize()
-                        } else {
-                            vec3(-(2.0f32.sqrt()) / 2.0, 2.0f32.sqrt() / 2.0, 0.0)
+                        // Light from a light source shines outwards from the source, otherwise
+                        // use constant-dir sunlight.
+                        let light_dir = match world.light_direction(loc) {
+                            // A light source in the cell itself lights up every side.
+                            Some(offset) if offset == vec2(0, 0) => None,
+                            Some(offset) => {
+                                Some(offset.project::<PhysicsSpace>().to_3d().normalize())
+                            }
+                            None => Some(vec3(-(2.0f32.sqrt()) / 2.0, 2.0f32.sqrt() / 2.0, 0.0)),
                         };
-                        (0.1..=1.0).clamp(-light_dir.dot(normal))
+                        light_dir.map_or(1.0, |dir| (0.1..=1.0).clamp(-dir.dot(normal)))
                     };
 
                     Coloring::Shaded { ambient, diffuse }
@@ -188,7 +174,7 @@ impl WorldView {
             // FIXME: This should not use live entity data for the remembered objects, since it
             // will then show the object moving around without the player observing it.
             for &i in &items {
-                if let Some(desc) = world.ecs().desc.get(i) {
+                if let Some(icon) = world.entity_icon(i) {
                     let screen_pos = screen_pos + lerp_offset(world, i);
                     let color = if in_map_memory {
                         Coloring::MapMemory
@@ -199,7 +185,7 @@ impl WorldView {
                         }
                     };
                     entity_sprite_buffer.push(
-                        Sprite::new(Layer::Object, screen_pos, cache::entity(desc.icon))
+                        Sprite::new(Layer::Object, screen_pos, cache::entity(icon))
                             .color(color),
                     );
                 }
@@ -213,6 +199,16 @@ impl WorldView {
                     let screen_pos = screen_pos + lerp_offset(world, i);
 
                     if let Some(desc) = world.ecs().desc.get(i) {
+                        // Invisible mobs are not drawn, except for the player who gets shown as a
+                        // shimmer.
+                        let icon = if !world.has_status(i, Status::Invisible) {
+                            desc.icon
+                        } else if world.is_player(i) {
+                            world::Icon::InvisibleMob
+                        } else {
+                            continue;
+                        };
+
                         let frame_idx = if world.is_bobbing(i) {
                             ((world.get_anim_tick() / 10) % 2) as usize
                         } else {
@@ -248,7 +244,7 @@ impl WorldView {
                         };
 
                         entity_sprite_buffer.push(
-                            Sprite::new(Layer::Object, screen_pos, cache::entity(desc.icon))
+                            Sprite::new(Layer::Object, screen_pos, cache::entity(icon))
                                 .idx(frame_idx)
                                 .color(coloring),
                         );
@@ -360,6 +356,18 @@ impl WorldView {
                                 );
                             }
                         }
+
+                        if anim.state == AnimState::Missile {
+                            let t = world.get_anim_tick() - anim.anim_start;
+
+                            if t <= anim.tween_duration as u64 {
+                                entity_sprite_buffer.push(Sprite::new(
+                                    Layer::Effect,
+                                    screen_pos,
+                                    cache::misc(Icon::Missile),
+                                ));
+                            }
+                        }
                     }
                 }
             }
@@ -1,6 +1,6 @@
 use rand;
 use std::io::Write;
-use world::{attack_damage, roll};
+use world::{attack_damage, roll, Damage};
 
 fn ev<F>(n: usize, f: F) -> f32
 where
@@ -20,7 +20,7 @@ fn expected_dmg(advantage: i32) -> f32 {
 
     ev(REPEAT_ROLLS, |rng| {
         let roll = roll(rng);
-        let dmg = attack_damage(roll, advantage, 100);
+        let dmg = attack_damage(roll, advantage, 100, 0, Damage::Physical);
         dmg as f32 / 100.0
     })
 }
@@ -43,7 +43,7 @@ fn main() {
     }
     let e = ev(1_000_000, |rng| {
         let roll = roll(rng);
-        let dmg = attack_damage(roll, 0, 100);
+        let dmg = attack_damage(roll, 0, 100, 0, Damage::Physical);
         dmg as f32 / 100.0
     });
     println!("Hello, world!");
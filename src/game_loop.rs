@@ -1,18 +1,25 @@
 use crate::msg;
-use calx::{stego, CellVector, Dir6, IncrementalState};
+use crate::save::{self, SaveError};
+use calx::{CellVector, Dir6, IncrementalState};
 use calx_ecs::Entity;
 use display::{self, CanvasExt, ScreenVector};
 use euclid::default::{Point2D, Rect};
 use euclid::{point2, size2, vec2};
-use image;
+use log::warn;
+use std::collections::VecDeque;
+use std::fs::File;
 use std::io::prelude::*;
-use std::io::Cursor;
+use std::path::PathBuf;
 use vitral::{
     self, color, Align, ButtonAction, Canvas, InputEvent, Keycode, RectUtil, Rgba, Scene,
     SceneSwitch,
 };
-use world::{Ability, ActionOutcome, Command, LerpLocation, Slot, World, WorldSeed};
+use world::{
+    Ability, ActionOutcome, Command, CommandError, Hunger, LerpLocation, ModifierOp,
+    ModifierSource, Replay, Sector, Slot, Stat, StatBreakdown, World, WorldSeed,
+};
 
+#[derive(Copy, Clone, Eq, PartialEq)]
 pub struct HotbarAction {
     ability: Ability,
     // Is the ability activated via an item instead of being innate.
@@ -24,7 +31,16 @@ pub(crate) struct GameRuntime {
     command: Option<Command>,
     cursor_item: Option<Entity>,
     hotbar: [Option<HotbarAction>; 10],
-    hotbar_focus: Option<usize>,
+    /// Selected targeted action from the hotbar or the spellbook.
+    hotbar_focus: Option<HotbarAction>,
+    /// Events from a replay that are being played back instead of reading player input.
+    playback: VecDeque<Command>,
+    /// File to save a replay of the game in.
+    recording: Option<PathBuf>,
+    /// History length when the replay was last saved.
+    recorded_len: usize,
+    /// Sector the player was in when last checked for autosave.
+    autosave_sector: Option<Sector>,
 }
 
 impl GameRuntime {
@@ -35,6 +51,81 @@ impl GameRuntime {
             cursor_item: None,
             hotbar: Default::default(),
             hotbar_focus: None,
+            playback: Default::default(),
+            recording: None,
+            recorded_len: 0,
+            autosave_sector: None,
+        }
+    }
+
+    /// Play back the events of an existing history before accepting player input.
+    pub fn with_playback(mut self, history: calx::History<WorldSeed, Command>) -> GameRuntime {
+        self.playback = history.events.into();
+        self
+    }
+
+    /// Save a replay file of the game as it is played.
+    pub fn with_recording(mut self, path: PathBuf) -> GameRuntime {
+        self.recording = Some(path);
+        self
+    }
+
+    /// Save the game when the player has moved into a new sector.
+    fn autosave(&mut self) -> Result<(), SaveError> {
+        if !self.playback.is_empty() || !self.world.player_can_act() {
+            return Ok(());
+        }
+        let sector = match self.world.player().and_then(|p| self.world.location(p)) {
+            Some(loc) => Sector::from(loc),
+            None => return Ok(()),
+        };
+        if self.autosave_sector == Some(sector) {
+            return Ok(());
+        }
+
+        // Don't save when starting out, only after an actual transition.
+        let is_transition = self.autosave_sector.is_some();
+        self.autosave_sector = Some(sector);
+        if is_transition {
+            save::save(&self.world, save::AUTOSAVE_SLOT, None)?;
+        }
+        Ok(())
+    }
+
+    /// Run the next events from the playback queue.
+    fn run_playback(&mut self) {
+        // Move at the same moderately snappy pace as regular waiting, but only run one player
+        // command per frame so that it can be seen.
+        for _ in 0..3 {
+            match self.playback.pop_front() {
+                Some(Command::Wait) => self.world.update(Command::Wait),
+                Some(cmd) => {
+                    self.world.update(cmd);
+                    break;
+                }
+                None => break,
+            }
+        }
+    }
+
+    /// Write the replay file if recording and the history has advanced to a new player turn.
+    fn save_recording(&mut self) {
+        let path = match self.recording {
+            Some(ref path) => path,
+            None => return,
+        };
+        let len = self.world.history().events.len();
+        if len == self.recorded_len || !self.world.player_can_act() {
+            return;
+        }
+        self.recorded_len = len;
+
+        let replay = Replay::new(self.world.history()).with_state(&self.world);
+        if let Err(e) = File::create(path)
+            .map_err(Into::into)
+            .and_then(|f| replay.save(f))
+        {
+            warn!("Failed to save replay {}: {}", path.display(), e);
         }
     }
 
@@ -56,31 +147,89 @@ impl GameRuntime {
         true
     }
 
-    fn is_bindable_hotbar_action(&self, slot: usize) -> bool {
-        match self.hotbar[slot] {
+    fn is_bindable_hotbar_action(action: Option<HotbarAction>) -> bool {
+        match action {
             Some(HotbarAction { ability, .. }) => ability.is_targeted(),
             _ => false,
         }
     }
 
-    fn is_untargeted_hotbar_action(&self, slot: usize) -> bool {
-        match self.hotbar[slot] {
+    fn is_untargeted_hotbar_action(action: Option<HotbarAction>) -> bool {
+        match action {
             Some(HotbarAction { ability, .. }) => !ability.is_targeted(),
             _ => false,
         }
     }
 
+    /// Draw a hotbar button and return its bounds.
+    fn draw_hotbar_slot(
+        &self,
+        canvas: &mut Canvas,
+        pos: Point2D<i32>,
+        action: Option<HotbarAction>,
+    ) -> Rect<i32> {
+        let bounds = Rect::new(pos, size2(16, 16));
+        let color = if action.is_some() && action == self.hotbar_focus {
+            color::ORANGE
+        } else {
+            color::RED
+        };
+        canvas.fill_rect(&bounds.inflate(1, 1), color);
+        canvas.fill_rect(&bounds, color::BLACK);
+        bounds
+    }
+
+    /// Handle clicks that select or fire an action.
+    fn click_hotbar_action(&mut self, action: Option<HotbarAction>, click: ButtonAction) {
+        match click {
+            ButtonAction::LeftClicked | ButtonAction::RightClicked
+                if Self::is_bindable_hotbar_action(action) =>
+            {
+                self.hotbar_focus = action;
+            }
+
+            // Right-click to immediately fire an untargeted action
+            ButtonAction::RightClicked if Self::is_untargeted_hotbar_action(action) => {
+                if let Some(HotbarAction { ability, item }) = action {
+                    self.force_command(Command::UntargetedAbility { ability, item });
+                }
+            }
+            _ => {}
+        }
+    }
+
+    /// Draw the innate spells of the player left of the hotbar.
+    pub fn draw_spellbook(&mut self, canvas: &mut Canvas) {
+        let player = match self.world.player() {
+            Some(player) => player,
+            None => return,
+        };
+
+        for (x, ability) in self.world.list_abilities(player).into_iter().enumerate() {
+            let pos = point2(4 + x as i32 * 24, 344);
+            let action = Some(HotbarAction {
+                ability,
+                item: None,
+            });
+            let bounds = self.draw_hotbar_slot(canvas, pos, action);
+            canvas.draw_ability_icon(pos + vec2(8, 8), ability);
+
+            // Gray out spells the player can't afford right now.
+            if self.world.mana(player) < ability.data().cost.mana {
+                canvas.fill_rect(&bounds, Rgba::from(0x00_00_00_aa));
+            }
+
+            if self.cursor_item.is_none() {
+                let click = canvas.click_state(&bounds);
+                self.click_hotbar_action(action, click);
+            }
+        }
+    }
+
     pub fn draw_hotbar(&mut self, canvas: &mut Canvas) {
         for x in 0..10 {
             let pos = point2(204 + x as i32 * 24, 344);
-            let bounds = Rect::new(pos, size2(16, 16));
-            let color = if Some(x) == self.hotbar_focus {
-                color::ORANGE
-            } else {
-                color::RED
-            };
-            canvas.fill_rect(&bounds.inflate(1, 1), color);
-            canvas.fill_rect(&bounds, color::BLACK);
+            let bounds = self.draw_hotbar_slot(canvas, pos, self.hotbar[x]);
 
             match self.hotbar[x] {
                 None => {}
@@ -93,10 +242,8 @@ impl GameRuntime {
                         self.world.count(item),
                     );
                 }
-                Some(HotbarAction {
-                    ability: _ability, ..
-                }) => {
-                    // TODO: Icons for raw abilities
+                Some(HotbarAction { ability, .. }) => {
+                    canvas.draw_ability_icon(pos + vec2(8, 8), ability);
                 }
             }
 
@@ -117,24 +264,10 @@ impl GameRuntime {
                 }
             } else {
                 match canvas.click_state(&bounds) {
-                    ButtonAction::LeftClicked if self.is_bindable_hotbar_action(x) => {
-                        self.hotbar_focus = Some(x);
-                    }
-                    ButtonAction::RightClicked if self.is_bindable_hotbar_action(x) => {
-                        self.hotbar_focus = Some(x);
-                    }
-
-                    // Right-click to immediately fire an untargeted action
-                    ButtonAction::RightClicked if self.is_untargeted_hotbar_action(x) => {
-                        if let Some(HotbarAction { ability, item }) = self.hotbar[x] {
-                            self.force_command(Command::UntargetedAbility { ability, item });
-                        }
-                    }
-
                     ButtonAction::MiddleClicked => {
                         self.hotbar[x] = None;
                     }
-                    _ => {}
+                    click => self.click_hotbar_action(self.hotbar[x], click),
                 }
             }
         }
@@ -152,6 +285,17 @@ impl GameRuntime {
                 }
             }
         }
+
+        if let Some(HotbarAction { ability, item }) = self.hotbar_focus {
+            let is_valid = match (item, self.world.player()) {
+                (Some(item), _) => self.world.is_alive(item),
+                (None, Some(player)) => self.world.has_ability(player, ability),
+                (None, None) => false,
+            };
+            if !is_valid {
+                self.hotbar_focus = None;
+            }
+        }
     }
 }
 
@@ -171,10 +315,13 @@ impl Scene<GameRuntime> for GameLoop {
         ctx.update_hotbar();
         self.process_events(ctx);
 
-        if ctx.world.player_can_act() {
-            if let Some(cmd) = ctx.command {
+        if !ctx.playback.is_empty() {
+            // Ignore player input until playback is done.
+            ctx.command = None;
+            ctx.run_playback();
+        } else if ctx.world.player_can_act() {
+            if let Some(cmd) = ctx.command.take() {
                 ctx.world.update(cmd);
-                ctx.command = None;
             } else {
                 ctx.world.tick_anims();
             }
@@ -201,6 +348,11 @@ impl Scene<GameRuntime> for GameLoop {
             }
         }
 
+        ctx.save_recording();
+        if let Err(e) = ctx.autosave() {
+            let _ = writeln!(&mut self.console, "Autosave failed: {}", e);
+        }
+
         None
     }
 
@@ -228,6 +380,7 @@ impl Scene<GameRuntime> for GameLoop {
 
         canvas.set_clip(view_area);
         view.draw(&*ctx.world, canvas);
+        self.night_tint(ctx, canvas, &view_area);
         canvas.clear_clip();
 
         canvas.set_clip(status_area);
@@ -238,6 +391,7 @@ impl Scene<GameRuntime> for GameLoop {
         console_area.size.height = 32;
         self.console.draw_small(canvas, &console_area);
 
+        let mut switch = None;
         if view_area.contains(canvas.mouse_pos()) {
             let mouse_loc =
                 view.screen_to_cell(ScreenVector::from_untyped(canvas.mouse_pos().to_vector()));
@@ -248,20 +402,36 @@ impl Scene<GameRuntime> for GameLoop {
 
                 if click_state == ButtonAction::LeftClicked {
                     if relative_vec == CellVector::zero() {
-                        ctx.command = Some(Command::Take);
+                        switch = self.take(ctx);
                     } else {
                         let dir = Dir6::from_v2(relative_vec);
                         self.smart_step(ctx, dir);
                     }
                 }
 
-                // Use targeted ability with RMB
-                if click_state == ButtonAction::RightClicked {
-                    if let Some(i) = ctx.hotbar_focus {
-                        if let Some(HotbarAction { ability, item }) = ctx.hotbar[i] {
-                            if relative_vec != CellVector::zero() {
-                                let dir = Dir6::from_v2(relative_vec);
-                                ctx.command = Some(Command::TargetedAbility { ability, dir, item });
+                // Open and close doors or dig through walls next to the player with MMB.
+                if click_state == ButtonAction::MiddleClicked
+                    && ctx.world.location(player)?.distance_from(mouse_loc) == Some(1)
+                {
+                    let dir = Dir6::from_v2(relative_vec);
+                    for cmd in &[Command::ToggleDoor(dir), Command::Dig(dir)] {
+                        if ctx.world.can_command(cmd) {
+                            ctx.command = Some(cmd.clone());
+                            break;
+                        }
+                    }
+                }
+
+                // Use targeted ability with RMB, or shoot if no ability is selected.
+                if click_state == ButtonAction::RightClicked && relative_vec != CellVector::zero() {
+                    let dir = Dir6::from_v2(relative_vec);
+                    match ctx.hotbar_focus {
+                        Some(HotbarAction { ability, item }) => {
+                            ctx.command = Some(Command::TargetedAbility { ability, dir, item });
+                        }
+                        None => {
+                            if ctx.world.can_command(&Command::Fire(dir)) {
+                                ctx.command = Some(Command::Fire(dir));
                             }
                         }
                     }
@@ -270,7 +440,7 @@ impl Scene<GameRuntime> for GameLoop {
             })();
         }
 
-        None
+        switch
     }
 
     fn input(
@@ -325,7 +495,7 @@ impl Scene<GameRuntime> for GameLoop {
                             .iter()
                             .enumerate()
                             .rev()
-                            .find(|(_, &c)| c != Command::Wait)
+                            .find(|(_, c)| **c != Command::Wait)
                         {
                             println!("DEBUG Undoing last turn");
                             history.events.truncate(idx);
@@ -334,7 +504,7 @@ impl Scene<GameRuntime> for GameLoop {
                 }
 
                 G => {
-                    ctx.command = Some(Command::Take);
+                    return self.take(ctx);
                 }
 
                 Escape => {
@@ -342,29 +512,27 @@ impl Scene<GameRuntime> for GameLoop {
                 }
                 F5 => {
                     // Quick save.
-
-                    let enc = ron::ser::to_string_pretty(&ctx.world, Default::default()).unwrap();
-                    let cover = canvas.screenshot();
-                    let save = stego::embed_gzipped(&cover, enc.as_bytes());
-                    let _ = image::save_buffer(
-                        "save.png",
-                        &save,
-                        save.width(),
-                        save.height(),
-                        image::ColorType::RGB(8),
-                    );
+                    match save::save(&ctx.world, save::QUICKSAVE_SLOT, Some(&canvas.screenshot())) {
+                        Ok(path) => {
+                            let _ = writeln!(&mut self.console, "Saved to {}", path.display());
+                        }
+                        Err(e) => {
+                            let _ = writeln!(&mut self.console, "Save failed: {}", e);
+                        }
+                    }
                 }
                 F9 => {
-                    // Quick load
-
-                    // TODO: Error handling when file is missing or not an image.
-                    let save = image::open("save.png").unwrap().to_rgb();
-                    // TODO: Error handling when stego data can't be retrieved
-                    let save = stego::extract(&save).unwrap();
-                    // TODO: Error handling when stego data can't be deserialized into world
-                    let new_world: IncrementalState<World> =
-                        ron::de::from_reader(&mut Cursor::new(&save)).unwrap();
-                    ctx.world = new_world;
+                    // Quick load.
+                    match save::load(save::QUICKSAVE_SLOT) {
+                        Ok(world) => {
+                            ctx.world = world;
+                            ctx.autosave_sector = None;
+                            let _ = writeln!(&mut self.console, "Game loaded");
+                        }
+                        Err(e) => {
+                            let _ = writeln!(&mut self.console, "Load failed: {}", e);
+                        }
+                    }
                 }
                 F12 => {
                     // Capture screenshot.
@@ -418,6 +586,17 @@ impl GameLoop {
         Some(true)
     }
 
+    /// Pick up the item under the player, or let the player choose from a pile.
+    fn take(&self, ctx: &mut GameRuntime) -> Option<SceneSwitch<GameRuntime>> {
+        let loc = ctx.world.location(ctx.world.player()?)?;
+        if ctx.world.items_at(loc).len() > 1 {
+            Some(SceneSwitch::Push(Box::new(FloorScreen)))
+        } else {
+            ctx.command = Some(Command::Take);
+            None
+        }
+    }
+
     fn side_step(&self, ctx: &mut GameRuntime, side: Side) -> ActionOutcome {
         let player = ctx.world.player()?;
         let loc = ctx.world.location(player)?;
@@ -443,16 +622,59 @@ impl GameLoop {
         self.smart_step(ctx, actual_dir)
     }
 
+    /// Darken the overworld view with a blue tint when the sun is down.
+    fn night_tint(&self, ctx: &GameRuntime, canvas: &mut Canvas, area: &Rect<i32>) {
+        const MAX_TINT: f32 = 0.4;
+
+        let on_surface = ctx
+            .world
+            .player()
+            .and_then(|p| ctx.world.location(p))
+            .is_some_and(|loc| !ctx.world.is_underground(loc));
+        let darkness = 1.0 - ctx.world.daylight();
+        if on_surface && darkness > 0.0 {
+            canvas.fill_rect(area, Rgba::new(0.0, 0.02, 0.2, darkness * MAX_TINT));
+        }
+    }
+
     fn status_draw(&self, ctx: &mut GameRuntime, canvas: &mut Canvas, area: &Rect<i32>) {
         canvas.fill_rect(area, Rgba::from(0x33_11_11_ff));
+
+        // List the time of day and the player's mana, hunger and active statuses with their
+        // remaining durations.
+        let statuses = match ctx.world.player() {
+            Some(player) => {
+                let mut parts = vec![ctx.world.day_phase().name().to_string()];
+                match ctx.world.hunger(player) {
+                    None | Some(Hunger::NotHungry) => {}
+                    Some(hunger) => parts.push(hunger.name().to_string()),
+                }
+                if ctx.world.max_mana(player) > 0 {
+                    parts.push(format!(
+                        "mana {}/{}",
+                        ctx.world.mana(player),
+                        ctx.world.max_mana(player)
+                    ));
+                }
+                parts.extend(
+                    ctx.world
+                        .active_statuses(player)
+                        .into_iter()
+                        .map(|(status, duration)| format!("{} {}", status.name(), duration)),
+                );
+                parts.join("  ")
+            }
+            None => String::new(),
+        };
         canvas.draw_text(
             &*display::font(),
             area.origin,
             Align::Left,
             color::RED,
-            "Welcome to status bar",
+            &statuses,
         );
 
+        ctx.draw_spellbook(canvas);
         ctx.draw_hotbar(canvas);
     }
 
@@ -470,6 +692,8 @@ enum PickAction {
     Place(Entity),
     Swap(Entity, Entity),
     Drop(Entity),
+    Name(Entity),
+    Eat(Entity),
 }
 
 impl Scene<GameRuntime> for InventoryScreen {
@@ -480,10 +704,19 @@ impl Scene<GameRuntime> for InventoryScreen {
     ) -> Option<SceneSwitch<GameRuntime>> {
         use PickAction::*;
 
-        fn handle_action(ctx: &mut GameRuntime, slot: Slot, action: Option<PickAction>) {
+        fn handle_action(
+            ctx: &mut GameRuntime,
+            slot: Slot,
+            action: Option<PickAction>,
+        ) -> Option<SceneSwitch<GameRuntime>> {
             match action {
                 Some(Pick(e)) => {
-                    ctx.cursor_item = Some(e);
+                    // Cursed equipment stays where it is.
+                    if let Some(player) = ctx.world.player() {
+                        if ctx.world.check_unequip(player, e).is_ok() {
+                            ctx.cursor_item = Some(e);
+                        }
+                    }
                 }
                 Some(Place(e)) => {
                     // Putting it back where you took it, no-op but change UI.
@@ -509,10 +742,22 @@ impl Scene<GameRuntime> for InventoryScreen {
                 Some(Drop(_e)) => {
                     ctx.force_command(Command::Drop(slot));
                 }
+                Some(Name(e)) => {
+                    return Some(SceneSwitch::Push(Box::new(NameItemScreen::new(e))));
+                }
+                Some(Eat(_e)) => {
+                    ctx.force_command(Command::Eat(slot));
+                }
                 _ => {}
             }
+            None
         }
 
+        let mut switch = None;
+        // Explanation for why the action under the mouse cursor won't work.
+        let mut hover_error = None;
+        let mut hover_item = None;
+
         // Inventory items
         for y in 0..5 {
             for x in 0..10 {
@@ -524,7 +769,9 @@ impl Scene<GameRuntime> for InventoryScreen {
                 let slot = Slot::Bag((x + y * 10) as u32);
 
                 let action = self.item_button(ctx, canvas, pos, slot);
-                handle_action(ctx, slot, action);
+                hover_error = self.slot_error(ctx, canvas, pos, slot).or(hover_error);
+                hover_item = self.hovered_item(ctx, canvas, pos, slot).or(hover_item);
+                switch = handle_action(ctx, slot, action).or(switch);
             }
         }
 
@@ -550,7 +797,42 @@ impl Scene<GameRuntime> for InventoryScreen {
             canvas.fill_rect(&bounds, color::BLACK);
 
             let action = self.item_button(ctx, canvas, pos, slot);
-            handle_action(ctx, slot, action);
+            hover_error = self.slot_error(ctx, canvas, pos, slot).or(hover_error);
+            hover_item = self.hovered_item(ctx, canvas, pos, slot).or(hover_item);
+            switch = handle_action(ctx, slot, action).or(switch);
+        }
+
+        if let Some(err) = hover_error {
+            canvas.draw_text(
+                &display::font(),
+                point2(8, 122),
+                Align::Left,
+                color::ORANGE,
+                &err.to_string(),
+            );
+        } else if let Some(item) = hover_item {
+            canvas.draw_text(
+                &display::font(),
+                point2(8, 122),
+                Align::Left,
+                color::SILVER,
+                &ctx.world.entity_name(item),
+            );
+        }
+
+        // Player stats and where they come from
+        if let Some(player) = ctx.world.player() {
+            let font = display::font();
+            for (i, &stat) in Stat::iter().enumerate() {
+                let breakdown = ctx.world.stat_breakdown(player, stat);
+                canvas.draw_text(
+                    &font,
+                    point2(8, 136 + i as i32 * font.height),
+                    Align::Left,
+                    color::SILVER,
+                    &breakdown_text(&ctx.world, &breakdown),
+                );
+            }
         }
 
         ctx.draw_hotbar(canvas);
@@ -564,7 +846,7 @@ impl Scene<GameRuntime> for InventoryScreen {
                 ctx.world.count(item),
             );
         }
-        None
+        switch
     }
 
     fn input(
@@ -592,6 +874,33 @@ impl Scene<GameRuntime> for InventoryScreen {
     }
 }
 
+/// Describe the value of a stat and the modifiers that went into it.
+fn breakdown_text(world: &World, breakdown: &StatBreakdown) -> String {
+    let mut ret = format!(
+        "{}: {} (base {}",
+        breakdown.stat.name(),
+        breakdown.total,
+        breakdown.base
+    );
+    for &(source, op) in &breakdown.modifiers {
+        let source = match source {
+            ModifierSource::Level => "level".to_string(),
+            ModifierSource::Intrinsic(i) => format!("{:?}", i).to_lowercase(),
+            ModifierSource::Item(e) => world.entity_name(e),
+            ModifierSource::Status(s) => s.name().to_string(),
+            ModifierSource::Hunger(h) => h.name().to_string(),
+        };
+        let op = match op {
+            ModifierOp::Add(x) => format!("{:+}", x),
+            ModifierOp::Percent(x) => format!("x{}%", x),
+            ModifierOp::Max(x) => format!("at least {}", x),
+        };
+        ret.push_str(&format!(", {} {}", source, op));
+    }
+    ret.push(')');
+    ret
+}
+
 impl InventoryScreen {
     /// Return entity if item was clicked and grabbed.
     fn item_button(
@@ -622,6 +931,16 @@ impl InventoryScreen {
                         return Some(PickAction::Drop(e));
                     }
                 }
+                // Food is eaten and unidentified items can be given names.
+                if canvas.click_state(&bounds) == ButtonAction::RightClicked
+                    && ctx.cursor_item.is_none()
+                {
+                    if ctx.world.food_value(e).is_some() {
+                        return Some(PickAction::Eat(e));
+                    } else if !ctx.world.is_identified(e) {
+                        return Some(PickAction::Name(e));
+                    }
+                }
             }
         }
 
@@ -633,4 +952,311 @@ impl InventoryScreen {
 
         None
     }
+
+    /// Return the item in a slot if the mouse is over it.
+    fn hovered_item(
+        &self,
+        ctx: &GameRuntime,
+        canvas: &Canvas,
+        pos: Point2D<i32>,
+        slot: Slot,
+    ) -> Option<Entity> {
+        if ctx.cursor_item.is_some() || !Rect::new(pos, size2(16, 16)).contains(canvas.mouse_pos())
+        {
+            return None;
+        }
+        ctx.world.entity_equipped(ctx.world.player()?, slot)
+    }
+
+    /// Grey out a slot if clicking on it won't work.
+    ///
+    /// Return the reason if the mouse is over the slot.
+    fn slot_error(
+        &self,
+        ctx: &GameRuntime,
+        canvas: &mut Canvas,
+        pos: Point2D<i32>,
+        slot: Slot,
+    ) -> Option<CommandError> {
+        let player = ctx.world.player()?;
+        let cmd = match (ctx.cursor_item, ctx.world.entity_equipped(player, slot)) {
+            (Some(c), Some(e)) if c == e => return None,
+            (Some(c), Some(_)) => Command::InventorySwap(ctx.world.entity_slot(c)?, slot),
+            (Some(c), None) => Command::InventoryPlace(c, slot),
+            // Cursed items can't be picked up from an equipment slot.
+            (None, Some(_)) => Command::Drop(slot),
+            (None, None) => return None,
+        };
+        let err = ctx.world.check_command(&cmd).err()?;
+
+        let bounds = Rect::new(pos, size2(16, 16));
+        canvas.fill_rect(&bounds, Rgba::from(0x00_00_00_aa));
+        if bounds.contains(canvas.mouse_pos()) {
+            Some(err)
+        } else {
+            None
+        }
+    }
+}
+
+/// List of the items in the pile the player is standing on.
+struct FloorScreen;
+
+impl Scene<GameRuntime> for FloorScreen {
+    fn render(
+        &mut self,
+        ctx: &mut GameRuntime,
+        canvas: &mut Canvas,
+    ) -> Option<SceneSwitch<GameRuntime>> {
+        let items = match ctx.world.player().and_then(|p| ctx.world.location(p)) {
+            Some(loc) => ctx.world.items_at(loc),
+            None => Vec::new(),
+        };
+        if items.is_empty() {
+            return Some(SceneSwitch::Pop);
+        }
+
+        let font = display::font();
+        canvas.draw_text(
+            &font,
+            point2(8, 8),
+            Align::Left,
+            color::SILVER,
+            "LMB: take, RMB: take one, MMB: take some, A: take all",
+        );
+        for (i, &item) in items.iter().enumerate() {
+            let pos = point2(8, 24 + i as i32 * 20);
+            let bounds = Rect::new(pos, size2(16, 16));
+            canvas.fill_rect(&bounds.inflate(1, 1), color::GREEN);
+            canvas.fill_rect(&bounds, color::BLACK);
+            canvas.draw_item_icon(
+                pos + vec2(8, 8),
+                ctx.world.entity_icon(item).expect("Item icon missing"),
+                ctx.world.count(item),
+            );
+
+            let row = Rect::new(pos, size2(320, 16));
+            let text_color = if row.contains(canvas.mouse_pos()) {
+                color::WHITE
+            } else {
+                color::SILVER
+            };
+            canvas.draw_text(
+                &font,
+                pos + vec2(24, 4),
+                Align::Left,
+                text_color,
+                &ctx.world.entity_name(item),
+            );
+
+            match canvas.click_state(&row) {
+                ButtonAction::LeftClicked => {
+                    let count = ctx.world.count(item);
+                    ctx.force_command(Command::TakeItem(item, count));
+                }
+                ButtonAction::RightClicked => {
+                    ctx.force_command(Command::TakeItem(item, 1));
+                }
+                ButtonAction::MiddleClicked if ctx.world.count(item) > 1 => {
+                    return Some(SceneSwitch::Push(Box::new(TakeCountScreen::new(item))));
+                }
+                _ => {}
+            }
+        }
+        None
+    }
+
+    fn input(
+        &mut self,
+        ctx: &mut GameRuntime,
+        event: &InputEvent,
+        _canvas: &mut Canvas,
+    ) -> Option<SceneSwitch<GameRuntime>> {
+        if let InputEvent::KeyEvent {
+            is_down: true,
+            hardware_key: Some(scancode),
+            ..
+        } = event
+        {
+            use Keycode::*;
+            match scancode {
+                A => {
+                    ctx.force_command(Command::TakeAll);
+                }
+                Escape => return Some(SceneSwitch::Pop),
+                _ => {}
+            }
+        }
+        None
+    }
+}
+
+/// Number entry for picking up part of a stack from the floor.
+struct TakeCountScreen {
+    item: Entity,
+    count: String,
+}
+
+impl TakeCountScreen {
+    const MAX_LEN: usize = 5;
+
+    fn new(item: Entity) -> TakeCountScreen {
+        TakeCountScreen {
+            item,
+            count: String::new(),
+        }
+    }
+}
+
+impl Scene<GameRuntime> for TakeCountScreen {
+    fn render(
+        &mut self,
+        ctx: &mut GameRuntime,
+        canvas: &mut Canvas,
+    ) -> Option<SceneSwitch<GameRuntime>> {
+        let font = display::font();
+        canvas.draw_item_icon(
+            point2(16, 16),
+            ctx.world.entity_icon(self.item).expect("Item icon missing"),
+            ctx.world.count(self.item),
+        );
+        canvas.draw_text(
+            &font,
+            point2(32, 12),
+            Align::Left,
+            color::SILVER,
+            &format!(
+                "Take how many of {}? (1-{})",
+                ctx.world.entity_name(self.item),
+                ctx.world.count(self.item)
+            ),
+        );
+        canvas.draw_text(
+            &font,
+            point2(32, 12 + font.height),
+            Align::Left,
+            color::WHITE,
+            &format!("{}_", self.count),
+        );
+        None
+    }
+
+    fn input(
+        &mut self,
+        ctx: &mut GameRuntime,
+        event: &InputEvent,
+        _canvas: &mut Canvas,
+    ) -> Option<SceneSwitch<GameRuntime>> {
+        match event {
+            InputEvent::Typed(c) if c.is_ascii_digit() && self.count.len() < Self::MAX_LEN => {
+                self.count.push(*c);
+            }
+            InputEvent::KeyEvent {
+                is_down: true,
+                hardware_key: Some(scancode),
+                ..
+            } => {
+                use Keycode::*;
+                match scancode {
+                    Backspace => {
+                        self.count.pop();
+                    }
+                    Enter | PadEnter => {
+                        // Asking for more than there is takes the whole stack.
+                        let count = self.count.parse().unwrap_or(0).min(ctx.world.count(self.item));
+                        if count > 0 {
+                            ctx.force_command(Command::TakeItem(self.item, count));
+                        }
+                        return Some(SceneSwitch::Pop);
+                    }
+                    Escape => return Some(SceneSwitch::Pop),
+                    _ => {}
+                }
+            }
+            _ => {}
+        }
+        None
+    }
+}
+
+/// Text entry for giving a custom name to an unidentified item.
+struct NameItemScreen {
+    item: Entity,
+    name: String,
+}
+
+impl NameItemScreen {
+    const MAX_LEN: usize = 24;
+
+    fn new(item: Entity) -> NameItemScreen {
+        NameItemScreen {
+            item,
+            name: String::new(),
+        }
+    }
+}
+
+impl Scene<GameRuntime> for NameItemScreen {
+    fn render(
+        &mut self,
+        ctx: &mut GameRuntime,
+        canvas: &mut Canvas,
+    ) -> Option<SceneSwitch<GameRuntime>> {
+        let font = display::font();
+        canvas.draw_item_icon(
+            point2(16, 16),
+            ctx.world.entity_icon(self.item).expect("Item icon missing"),
+            ctx.world.count(self.item),
+        );
+        canvas.draw_text(
+            &font,
+            point2(32, 12),
+            Align::Left,
+            color::SILVER,
+            &format!("Call {}:", ctx.world.entity_name(self.item)),
+        );
+        canvas.draw_text(
+            &font,
+            point2(32, 12 + font.height),
+            Align::Left,
+            color::WHITE,
+            &format!("{}_", self.name),
+        );
+        None
+    }
+
+    fn input(
+        &mut self,
+        ctx: &mut GameRuntime,
+        event: &InputEvent,
+        _canvas: &mut Canvas,
+    ) -> Option<SceneSwitch<GameRuntime>> {
+        match event {
+            InputEvent::Typed(c)
+                if !c.is_control() && self.name.chars().count() < Self::MAX_LEN =>
+            {
+                self.name.push(*c);
+            }
+            InputEvent::KeyEvent {
+                is_down: true,
+                hardware_key: Some(scancode),
+                ..
+            } => {
+                use Keycode::*;
+                match scancode {
+                    Backspace => {
+                        self.name.pop();
+                    }
+                    Enter | PadEnter => {
+                        ctx.force_command(Command::CallItem(self.item, self.name.clone()));
+                        return Some(SceneSwitch::Pop);
+                    }
+                    Escape => return Some(SceneSwitch::Pop),
+                    _ => {}
+                }
+            }
+            _ => {}
+        }
+        None
+    }
 }
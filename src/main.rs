@@ -4,21 +4,78 @@
 use crate::game_loop::GameLoop;
 use display;
 use env_logger;
-use log::info;
+use log::{info, warn};
 use rand;
 use rand::Rng;
+use std::fs::File;
+use std::path::{Path, PathBuf};
+use std::process;
 use structopt;
 use structopt::StructOpt;
 use vitral::{self, AppConfig, Flick};
-use world::{ExternalEntity, WorldSeed, WorldSkeleton};
+use world::{ExternalEntity, Replay, Simulation, World, WorldSeed, WorldSkeleton};
 
 pub mod game_loop;
 mod msg;
+mod save;
 
 #[derive(Debug, StructOpt)]
 struct Opt {
     #[structopt(long = "seed")]
     seed: Option<u32>,
+    /// Load additional entity specs from a RON file or a directory of RON files.
+    #[structopt(long = "specs", parse(from_os_str))]
+    specs: Option<PathBuf>,
+    /// Start a new game where the player doesn't need to eat.
+    #[structopt(long = "no-hunger")]
+    no_hunger: bool,
+    #[structopt(subcommand)]
+    cmd: Option<Cmd>,
+}
+
+#[derive(Debug, StructOpt)]
+enum Cmd {
+    /// Play a new game and record it into a replay file.
+    Record {
+        #[structopt(parse(from_os_str))]
+        path: PathBuf,
+    },
+    /// Play back a recorded game.
+    Replay {
+        #[structopt(parse(from_os_str))]
+        path: PathBuf,
+        /// Run the replay without graphics and print out the end state.
+        #[structopt(long = "headless")]
+        headless: bool,
+    },
+    /// Check that a replay reproduces the recorded end state.
+    Verify {
+        #[structopt(parse(from_os_str))]
+        path: PathBuf,
+    },
+    /// Run a replay twice side by side and report the first tick where the runs diverge.
+    Desync {
+        #[structopt(parse(from_os_str))]
+        path: PathBuf,
+    },
+}
+
+fn load_replay(path: &Path) -> Replay {
+    let replay = File::open(path)
+        .map_err(|e| e.into())
+        .and_then(Replay::load)
+        .unwrap_or_else(|e| {
+            eprintln!("Failed to load replay {}: {}", path.display(), e);
+            process::exit(1);
+        });
+    if replay.game_version != world::GAME_VERSION {
+        warn!(
+            "Replay was recorded with game version {}, running {}",
+            replay.game_version,
+            world::GAME_VERSION
+        );
+    }
+    replay
 }
 
 pub fn main() {
@@ -27,24 +84,90 @@ pub fn main() {
     const FPS: f64 = 30.0;
     env_logger::init();
 
-    display::load_graphics();
+    if let Some(ref path) = opt.specs {
+        match world::load_specs(path) {
+            Ok(n) => info!("Loaded {} specs from {}", n, path.display()),
+            Err(e) => {
+                eprintln!("Failed to load specs: {}", e);
+                process::exit(1);
+            }
+        }
+    }
 
-    msg::register();
+    let mut runtime = match opt.cmd {
+        Some(Cmd::Replay {
+            ref path,
+            headless: true,
+        }) => {
+            let mut sim = Simulation::from_history(load_replay(path).history());
+            for line in sim.take_messages() {
+                println!("{}", line);
+            }
+            println!("{:#?}", sim.report());
+            return;
+        }
+        Some(Cmd::Verify { ref path }) => {
+            let replay = load_replay(path);
+            let expected = replay.state_hash.unwrap_or_else(|| {
+                eprintln!("Replay {} has no recorded state hash", path.display());
+                process::exit(1);
+            });
+            let world: calx::IncrementalState<World> = replay.history().into();
+            if world.state_hash() != expected {
+                println!(
+                    "FAIL: expected state hash {:016x}, got {:016x}",
+                    expected,
+                    world.state_hash()
+                );
+                process::exit(1);
+            }
+            println!("OK: {} ticks, state hash {:016x}", replay.end_tick, expected);
+            return;
+        }
+        Some(Cmd::Desync { ref path }) => {
+            let replay = load_replay(path);
+            if let Some(desync) = world::find_desync(&replay.history()) {
+                println!(
+                    "FAIL: desync at tick {} in {}",
+                    desync.tick,
+                    desync.parts.join(", ")
+                );
+                process::exit(1);
+            }
+            println!("OK: no desync in {} ticks", replay.end_tick);
+            return;
+        }
+        Some(Cmd::Replay { ref path, .. }) => {
+            let replay = load_replay(path);
+            game_loop::GameRuntime::new(replay.seed()).with_playback(replay.history())
+        }
+        _ => {
+            let rng_seed = opt.seed.unwrap_or_else(|| rand::thread_rng().gen());
+            // Print out the seed in case worldgen has a bug and we want to debug stuff with the
+            // same seed.
+            info!("World seed: {}", rng_seed);
 
-    let rng_seed = opt.seed.unwrap_or_else(|| rand::thread_rng().gen());
-    // Print out the seed in case worldgen has a bug and we want to debug stuff with the same seed.
-    info!("World seed: {}", rng_seed);
-
-    let world_seed = WorldSeed {
-        rng_seed,
-        world_skeleton: WorldSkeleton::overworld_sprawl(),
-        player_character: ExternalEntity::from_name("player").unwrap(),
+            game_loop::GameRuntime::new(WorldSeed {
+                rng_seed,
+                world_skeleton: WorldSkeleton::overworld_sprawl(),
+                player_character: ExternalEntity::from_name("player").unwrap(),
+                hunger: !opt.no_hunger,
+            })
+        }
     };
 
+    if let Some(Cmd::Record { path }) = opt.cmd {
+        runtime = runtime.with_recording(path);
+    }
+
+    display::load_graphics();
+
+    msg::register();
+
     vitral::App::new(
         AppConfig::new(format!("Magog v{}", env!("CARGO_PKG_VERSION")))
             .frame_duration(Flick::from_seconds(1.0 / FPS)),
-        game_loop::GameRuntime::new(world_seed),
+        runtime,
         vec![Box::new(GameLoop::default())],
     )
     .run()
@@ -0,0 +1,765 @@
+//! Saving and loading games.
+
+use calx::{stego, IncrementalState};
+use image::RgbImage;
+use std::error::Error;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+use world::{ExternalEntity, Identification, ItemType, World, GAME_VERSION};
+
+/// Save slot used by the quick save and quick load keys.
+pub const QUICKSAVE_SLOT: &str = "quicksave";
+
+/// Save slot that is written automatically during play.
+pub const AUTOSAVE_SLOT: &str = "autosave";
+
+/// Function that rewrites serialized save data from an older game version.
+type Migration = fn(&str) -> Result<String, SaveError>;
+
+/// Save file migrations, indexed by the game version they upgrade from.
+///
+/// Each migration rewrites the serialized save into the format of some later game version. Add an
+/// entry here whenever a change to the game version breaks existing saves.
+const MIGRATIONS: &[(&str, Migration)] = &[
+    ("0.1.0", drop_delayed_status),
+    ("0.1.1", status_instance_lists),
+    ("0.1.2", player_light_and_spells),
+    ("0.1.3", item_identification),
+];
+
+#[derive(Debug)]
+pub enum SaveError {
+    Io(io::Error),
+    Image(image::ImageError),
+    /// Save data could not be extracted from the stego image.
+    Stego,
+    Serialize(ron::ser::Error),
+    Deserialize(ron::de::Error),
+    /// Save is from a game version that can't be migrated to the current one.
+    Version(String),
+    /// There is no save in the slot.
+    NoSave(String),
+}
+
+impl fmt::Display for SaveError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        use SaveError::*;
+        match self {
+            Io(e) => write!(f, "I/O error: {}", e),
+            Image(e) => write!(f, "Image error: {}", e),
+            Stego => write!(f, "No valid save data found in image"),
+            Serialize(e) => write!(f, "Serialization error: {}", e),
+            Deserialize(e) => write!(f, "Corrupt save data: {}", e),
+            Version(v) => write!(
+                f,
+                "Save is from incompatible game version {}, current version is {}",
+                v, GAME_VERSION
+            ),
+            NoSave(slot) => write!(f, "No save in slot '{}'", slot),
+        }
+    }
+}
+
+impl Error for SaveError {}
+
+impl From<io::Error> for SaveError {
+    fn from(e: io::Error) -> SaveError { SaveError::Io(e) }
+}
+
+impl From<image::ImageError> for SaveError {
+    fn from(e: image::ImageError) -> SaveError { SaveError::Image(e) }
+}
+
+impl From<ron::ser::Error> for SaveError {
+    fn from(e: ron::ser::Error) -> SaveError { SaveError::Serialize(e) }
+}
+
+impl From<ron::de::Error> for SaveError {
+    fn from(e: ron::de::Error) -> SaveError { SaveError::Deserialize(e) }
+}
+
+/// Container format of a save file.
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+pub enum Format {
+    /// Plain RON text.
+    Ron,
+    /// Compressed RON embedded in a PNG screenshot.
+    Png,
+}
+
+impl Format {
+    fn extension(self) -> &'static str {
+        match self {
+            Format::Ron => "ron",
+            Format::Png => "png",
+        }
+    }
+}
+
+/// Directory where the save slots are stored.
+pub fn save_dir() -> PathBuf { calx::app_data_path("magog").join("saves") }
+
+pub fn slot_path(slot: &str, format: Format) -> PathBuf {
+    save_dir().join(format!("{}.{}", slot, format.extension()))
+}
+
+/// Save the game in a slot.
+///
+/// If a cover image is given, the save is embedded in it as a PNG file, otherwise it is written as
+/// plain RON.
+pub fn save(
+    world: &IncrementalState<World>,
+    slot: &str,
+    cover: Option<&RgbImage>,
+) -> Result<PathBuf, SaveError> {
+    fs::create_dir_all(save_dir())?;
+    let path = match cover {
+        Some(_) => slot_path(slot, Format::Png),
+        None => slot_path(slot, Format::Ron),
+    };
+    write_save(world, &path, cover)?;
+    Ok(path)
+}
+
+/// Write the game into a save file, embedded in the cover image if one is given.
+fn write_save(
+    world: &IncrementalState<World>,
+    path: &Path,
+    cover: Option<&RgbImage>,
+) -> Result<(), SaveError> {
+    let text = ron::ser::to_string_pretty(world, Default::default())?;
+
+    // Write into a temporary file first so that a failed save won't clobber an existing one.
+    let extension = path.extension().and_then(|e| e.to_str()).unwrap_or_default();
+    let tmp = path.with_extension(format!("tmp.{}", extension));
+    match cover {
+        Some(cover) => {
+            let image = stego::embed_gzipped(cover, text.as_bytes());
+            image::save_buffer(
+                &tmp,
+                &image,
+                image.width(),
+                image.height(),
+                image::ColorType::RGB(8),
+            )?;
+        }
+        None => fs::write(&tmp, text)?,
+    }
+    fs::rename(&tmp, path)?;
+    Ok(())
+}
+
+/// Load the game from a slot.
+///
+/// If the slot has saves in several formats, the most recent one is used.
+pub fn load(slot: &str) -> Result<IncrementalState<World>, SaveError> {
+    let format = [Format::Ron, Format::Png]
+        .iter()
+        .filter_map(|&f| {
+            let modified = fs::metadata(slot_path(slot, f)).and_then(|m| m.modified());
+            modified.ok().map(|t| (t, f))
+        })
+        .max_by_key(|&(t, _)| t)
+        .map(|(_, f)| f)
+        .ok_or_else(|| SaveError::NoSave(slot.to_string()))?;
+
+    read_save(&slot_path(slot, format), format)
+}
+
+/// Read the game from a save file, migrating it from an older game version if needed.
+fn read_save(path: &Path, format: Format) -> Result<IncrementalState<World>, SaveError> {
+    let text = match format {
+        Format::Ron => fs::read_to_string(path)?,
+        Format::Png => {
+            let image = image::open(path)?.to_rgb();
+            let data = stego::extract(&image).map_err(|_| SaveError::Stego)?;
+            String::from_utf8(data).map_err(|_| SaveError::Stego)?
+        }
+    };
+
+    parse_save(text)
+}
+
+/// Deserialize a save, migrating it from an older game version if needed.
+fn parse_save(text: String) -> Result<IncrementalState<World>, SaveError> {
+    let text = migrate(text, MIGRATIONS)?;
+    let mut world: IncrementalState<World> = ron::de::from_str(&text)?;
+    world.rebuild_all_stats();
+    Ok(world)
+}
+
+/// Run migrations on serialized save data until it matches the current game version.
+fn migrate(mut text: String, migrations: &[(&str, Migration)]) -> Result<String, SaveError> {
+    // Bound the number of passes in case a migration doesn't update the version.
+    for _ in 0..=migrations.len() {
+        let version = save_version(&text)?;
+        if version == GAME_VERSION {
+            return Ok(text);
+        }
+        match migrations.iter().find(|(v, _)| *v == version) {
+            Some((_, f)) => text = f(&text)?,
+            None => return Err(SaveError::Version(version)),
+        }
+    }
+    Err(SaveError::Version(save_version(&text)?))
+}
+
+/// Path to the game version in serialized save data.
+const VERSION_PATH: &[&str] = &["state", "version"];
+
+/// Path to the status component data in serialized save data.
+const STATUS_PATH: &[&str] = &["state", "ecs", "store", "status", "data"];
+
+/// Path to the component stores in serialized save data.
+const STORE_PATH: &[&str] = &["state", "ecs", "store"];
+
+/// Path to the player entity in serialized save data.
+const PLAYER_PATH: &[&str] = &["state", "flags", "player"];
+
+/// Path to the world state in serialized save data.
+const STATE_PATH: &[&str] = &["state"];
+
+/// Path to the world random seed in serialized save data.
+const SEED_PATH: &[&str] = &["history", "seed", "rng_seed"];
+
+/// Path to the starting components of the player in serialized save data.
+const LOADOUT_PATH: &[&str] = &["history", "seed", "player_character", "loadout"];
+
+fn corrupt(msg: &str) -> SaveError { SaveError::Deserialize(ron::de::Error::Message(msg.into())) }
+
+/// Read the game version from serialized save data without deserializing the whole world.
+fn save_version(text: &str) -> Result<String, SaveError> {
+    // Look the version up directly instead of parsing the whole save, which might not be possible
+    // for older versions.
+    let span = find_path(text, VERSION_PATH).ok_or_else(|| corrupt("No save version"))?;
+    Ok(ron::de::from_str(&text[span])?)
+}
+
+/// Set the game version in serialized save data.
+fn set_version(text: &str, version: &str) -> Result<String, SaveError> {
+    let span = find_path(text, VERSION_PATH).ok_or_else(|| corrupt("No save version"))?;
+    Ok(splice(text, vec![(span, format!("{:?}", version))]))
+}
+
+/// Rewrite the entries of every status map in serialized save data.
+///
+/// The rewrite function gets the status name and its serialized value and returns the new value,
+/// or `None` to remove the status.
+fn rewrite_statuses(text: &str, f: impl Fn(&str, &str) -> Option<String>) -> String {
+    let data = match find_path(text, STATUS_PATH) {
+        Some(span) => span,
+        None => return text.to_string(),
+    };
+    let edits = items(text, data)
+        .into_iter()
+        .map(|map| {
+            let entries: Vec<String> = entries(text, map.clone())
+                .into_iter()
+                .filter_map(|(k, v)| {
+                    let key = &text[k];
+                    Some(format!("{}: {}", key, f(key, &text[v])?))
+                })
+                .collect();
+            (map, format!("{{{}}}", entries.join(", ")))
+        })
+        .collect();
+    splice(text, edits)
+}
+
+/// Migrate from 0.1.0, where action timing used a `Delayed` status instead of mob energy.
+fn drop_delayed_status(text: &str) -> Result<String, SaveError> {
+    let text = rewrite_statuses(text, |status, value| match status {
+        "Delayed" => None,
+        _ => Some(value.to_string()),
+    });
+    set_version(&text, "0.1.1")
+}
+
+/// Migrate from 0.1.1, where each status had a single duration instead of a list of instances.
+fn status_instance_lists(text: &str) -> Result<String, SaveError> {
+    let text = rewrite_statuses(text, |_, duration| Some(format!("[{}]", duration)));
+    set_version(&text, "0.1.2")
+}
+
+/// Migrate from 0.1.2, where the player had no light, mana or spells and torches were shields.
+///
+/// The player gets the light, mana and abilities of the current player spec and items that the
+/// spec makes lights become lights.
+fn player_light_and_spells(text: &str) -> Result<String, SaveError> {
+    let spec = ExternalEntity::from_name("player")
+        .map_err(|_| corrupt("No player spec"))?
+        .loadout;
+    let (mana, light) = spec.stats.map_or((0, 0), |s| (s.base.mana, s.base.light));
+    let abilities = ron::ser::to_string(&spec.abilities.unwrap_or_default())?;
+
+    let store = find_path(text, STORE_PATH).ok_or_else(|| corrupt("No component store"))?;
+    let store = |name| field(text, store.clone(), name);
+    let mut edits = Vec::new();
+
+    // The player is gone if the game was saved after dying.
+    if let Some(player) = find_path(text, PLAYER_PATH).and_then(|span| items(text, span).pop()) {
+        if let Some(stats) = store("stats").and_then(|s| component(text, s, player.clone())) {
+            edits.extend(player_stats(text, stats, mana, light));
+        }
+        if let Some(abilities_store) = store("abilities") {
+            if component(text, abilities_store.clone(), player.clone()).is_none() {
+                if let (Some(data), Some(entities)) = (
+                    field(text, abilities_store.clone(), "data"),
+                    field(text, abilities_store, "entities"),
+                ) {
+                    edits.push(append(text, data, &abilities));
+                    edits.push(append(text, entities, &text[player]));
+                }
+            }
+        }
+    }
+
+    // Also update the world seed so that replaying the game gives the same player.
+    if let Some(loadout) = find_path(text, LOADOUT_PATH) {
+        if let Some(span) = field(text, loadout.clone(), "abilities") {
+            if &text[span.clone()] == "None" {
+                edits.push((span, format!("Some({})", abilities)));
+            }
+        }
+        if let Some(stats) = field(text, loadout, "stats").and_then(|s| items(text, s).pop()) {
+            edits.extend(player_stats(text, stats, mana, light));
+        }
+    }
+
+    if let (Some(desc), Some(item)) = (store("desc"), store("item")) {
+        let shield = ron::ser::to_string(&ItemType::Shield)?;
+        let light = ron::ser::to_string(&ItemType::Light)?;
+        for (e, desc) in components(text, desc) {
+            let is_light = field(text, desc, "singular_name")
+                .and_then(|name| ron::de::from_str::<String>(&text[name]).ok())
+                .and_then(|name| ExternalEntity::from_name(&name).ok())
+                .and_then(|spec| spec.loadout.item)
+                .map(|item| item.item_type)
+                == Some(ItemType::Light);
+            if !is_light {
+                continue;
+            }
+            let item_type = component(text, item.clone(), e)
+                .and_then(|item| field(text, item, "item_type"))
+                .filter(|span| text[span.clone()] == shield);
+            if let Some(item_type) = item_type {
+                edits.push((item_type, light.clone()));
+            }
+        }
+    }
+
+    set_version(&splice(text, edits), "0.1.3")
+}
+
+/// Return edits that give serialized player stats the light and mana of the player spec.
+fn player_stats(text: &str, stats: Span, mana: i32, light: i32) -> Vec<(Span, String)> {
+    let base = match field(text, stats, "base") {
+        Some(span) => span,
+        None => return Vec::new(),
+    };
+    let mut edits = Vec::new();
+    let old_mana = field(text, base.clone(), "mana");
+    if let Some(old_mana) = old_mana.filter(|span| &text[span.clone()] == "0") {
+        edits.push((old_mana, mana.to_string()));
+    }
+    if field(text, base.clone(), "light").is_none() {
+        edits.push(append(text, base, &format!("light: {}", light)));
+    }
+    edits
+}
+
+/// Migrate from 0.1.3, saves from before item identification have no identification table.
+///
+/// The table is built from the world seed the same way a new world builds it.
+fn item_identification(text: &str) -> Result<String, SaveError> {
+    let state = find_path(text, STATE_PATH).ok_or_else(|| corrupt("No world state"))?;
+    if field(text, state.clone(), "identification").is_some() {
+        return set_version(text, "0.1.4");
+    }
+    let seed = find_path(text, SEED_PATH).ok_or_else(|| corrupt("No world seed"))?;
+    let identification = Identification::new(ron::de::from_str(&text[seed])?);
+    let identification = format!("identification: {}", ron::ser::to_string(&identification)?);
+    let text = splice(text, vec![append(text, state, &identification)]);
+    set_version(&text, "0.1.4")
+}
+
+/// Byte range of a value in RON text.
+type Span = Range<usize>;
+
+/// Return the positions of the commas and colons at the top level of the span.
+///
+/// Separators inside nested values and string or character literals are skipped.
+fn separators(text: &str, span: Span) -> Vec<(usize, u8)> {
+    let bytes = text.as_bytes();
+    let mut ret = Vec::new();
+    let mut depth = 0;
+    let mut i = span.start;
+    while i < span.end {
+        match bytes[i] {
+            quote @ b'"' | quote @ b'\'' => {
+                i += 1;
+                while i < span.end && bytes[i] != quote {
+                    if bytes[i] == b'\\' {
+                        i += 1;
+                    }
+                    i += 1;
+                }
+            }
+            b'(' | b'[' | b'{' => depth += 1,
+            b')' | b']' | b'}' => depth -= 1,
+            c @ b',' | c @ b':' if depth == 0 => ret.push((i, c)),
+            _ => {}
+        }
+        i += 1;
+    }
+    ret
+}
+
+/// Shrink the span to leave out surrounding whitespace.
+fn trim(text: &str, span: Span) -> Span {
+    let s = &text[span.clone()];
+    let start = span.start + (s.len() - s.trim_start().len());
+    let end = span.end - (s.len() - s.trim_end().len());
+    start..end.max(start)
+}
+
+/// Return the spans of the items of a RON struct, tuple, list or map.
+fn items(text: &str, span: Span) -> Vec<Span> {
+    let span = trim(text, span);
+    let open = match text[span.clone()].find(|c| "([{".contains(c)) {
+        Some(i) => span.start + i,
+        None => return Vec::new(),
+    };
+    if !text[span.clone()].ends_with(|c| ")]}".contains(c)) {
+        return Vec::new();
+    }
+    let inner = open + 1..span.end - 1;
+
+    let mut ret = Vec::new();
+    let mut start = inner.start;
+    let commas = separators(text, inner.clone())
+        .into_iter()
+        .filter(|&(_, c)| c == b',')
+        .map(|(i, _)| i);
+    for end in commas.chain(Some(inner.end)) {
+        let item = trim(text, start..end);
+        if !item.is_empty() {
+            ret.push(item);
+        }
+        start = end + 1;
+    }
+    ret
+}
+
+/// Return the key and value spans of the entries of a RON struct or map.
+fn entries(text: &str, span: Span) -> Vec<(Span, Span)> {
+    items(text, span)
+        .into_iter()
+        .filter_map(|item| {
+            let (colon, _) = separators(text, item.clone())
+                .into_iter()
+                .find(|&(_, c)| c == b':')?;
+            Some((trim(text, item.start..colon), trim(text, colon + 1..item.end)))
+        })
+        .collect()
+}
+
+/// Find the value of a field of a RON struct.
+fn field(text: &str, span: Span, name: &str) -> Option<Span> {
+    entries(text, span)
+        .into_iter()
+        .find(|(k, _)| &text[k.clone()] == name)
+        .map(|(_, v)| v)
+}
+
+/// Find the value at a path of nested struct fields in RON text.
+fn find_path(text: &str, path: &[&str]) -> Option<Span> {
+    path.iter().try_fold(0..text.len(), |span, name| field(text, span, name))
+}
+
+/// Return the entity and value spans of the components in a serialized component store.
+fn components(text: &str, store: Span) -> Vec<(Span, Span)> {
+    match (field(text, store.clone(), "entities"), field(text, store, "data")) {
+        (Some(entities), Some(data)) => {
+            items(text, entities).into_iter().zip(items(text, data)).collect()
+        }
+        _ => Vec::new(),
+    }
+}
+
+/// Find the component of an entity in a serialized component store.
+fn component(text: &str, store: Span, entity: Span) -> Option<Span> {
+    let id = |span: Span| (field(text, span.clone(), "uid"), field(text, span, "idx"));
+    let id = |span: Span| match id(span) {
+        (Some(uid), Some(idx)) => Some((&text[uid], &text[idx])),
+        _ => None,
+    };
+    let entity = id(entity)?;
+    components(text, store)
+        .into_iter()
+        .find(|(e, _)| id(e.clone()) == Some(entity))
+        .map(|(_, c)| c)
+}
+
+/// Return an edit that adds an item to the end of a RON struct, tuple, list or map.
+fn append(text: &str, span: Span, item: &str) -> (Span, String) {
+    match items(text, span.clone()).last() {
+        Some(last) => (last.end..last.end, format!(", {}", item)),
+        None => {
+            let end = trim(text, span).end - 1;
+            (end..end, item.to_string())
+        }
+    }
+}
+
+/// Replace non-overlapping spans of the text.
+fn splice(text: &str, mut edits: Vec<(Span, String)>) -> String {
+    edits.sort_by_key(|(span, _)| span.start);
+    let mut ret = String::with_capacity(text.len());
+    let mut pos = 0;
+    for (span, replacement) in edits {
+        ret.push_str(&text[pos..span.start]);
+        ret.push_str(&replacement);
+        pos = span.end;
+    }
+    ret.push_str(&text[pos..]);
+    ret
+}
+
+#[cfg(test)]
+mod test {
+    use super::{
+        drop_delayed_status, find_path, item_identification, migrate, parse_save,
+        player_light_and_spells, read_save, save_dir, save_version, slot_path,
+        status_instance_lists, write_save, Format, Migration, SaveError, MIGRATIONS,
+    };
+    use calx::{Dir6, IncrementalState};
+    use std::fs;
+    use world::{
+        Ability, Command, Identification, Simulation, Status, World, WorldSeed, GAME_VERSION,
+    };
+
+    fn world() -> IncrementalState<World> {
+        let mut sim = Simulation::new(WorldSeed {
+            hunger: true,
+            ..WorldSeed::test(1)
+        });
+        sim.run(vec![Command::Step(Dir6::South), Command::Pass]);
+        sim.history().clone().into()
+    }
+
+    #[test]
+    fn test_slot_path() {
+        let path = slot_path("quicksave", Format::Png);
+        assert_eq!(path.parent(), Some(save_dir().as_path()));
+        assert_eq!(path.file_name().unwrap(), "quicksave.png");
+        assert_eq!(slot_path("autosave", Format::Ron).file_name().unwrap(), "autosave.ron");
+    }
+
+    #[test]
+    fn test_save_version() {
+        let version = |text: &str| save_version(text).ok();
+        assert_eq!(version("(state: (version: \"0.1.0\", ecs: ()))"), Some("0.1.0".into()));
+        assert_eq!(version("(\n  state: (\n    version : \"1.2\",\n  ),\n)"), Some("1.2".into()));
+        // Only the version field of the world counts.
+        assert_eq!(version("(state: (name: \"0\", version: \"1\"))"), Some("1".into()));
+        assert_eq!(version("(state: (ecs: (version: \"0.1.0\")))"), None);
+        assert_eq!(version("(state: (ecs: ()))"), None);
+        assert_eq!(version("(state: (version: \"0.1.0"), None);
+    }
+
+    #[test]
+    fn test_find_path() {
+        let text = r#"(a: "(:,\"", b: 'x', c: {(x: 1): [2, 3], Some((y: 1)): (d: (e: ')'))})"#;
+        let find = |path: &[&str]| find_path(text, path).map(|span| &text[span]);
+        assert_eq!(find(&["a"]), Some(r#""(:,\"""#));
+        assert_eq!(find(&["b"]), Some("'x'"));
+        assert_eq!(find(&["c", "(x: 1)"]), Some("[2, 3]"));
+        assert_eq!(find(&["c", "Some((y: 1))", "d", "e"]), Some("')'"));
+        assert_eq!(find(&["x"]), None);
+        assert_eq!(find(&["a", "b"]), None);
+    }
+
+    #[test]
+    fn test_migrate_0_1_0() {
+        // Save from 0.1.0 where the player is confused and slowed and every mob that has acted is
+        // delayed. Rename the player to make sure only the status maps get rewritten.
+        let text = include_str!("fixtures/save-0.1.0.ron").replacen(
+            "singular_name: \"player\"",
+            "singular_name: \"Delayed: 12,\"",
+            1,
+        );
+        assert_eq!(save_version(&text).unwrap(), "0.1.0");
+
+        let migrated = migrate(text.clone(), MIGRATIONS).unwrap();
+        assert_eq!(save_version(&migrated).unwrap(), GAME_VERSION);
+        assert_eq!(migrated.matches("Delayed").count(), 1);
+
+        let world = parse_save(text).unwrap();
+        let player = world.player().unwrap();
+        assert_eq!(world.entity_name(player), "Delayed: 12,");
+        let statuses: Vec<_> = world.active_statuses(player).into_iter().map(|(s, _)| s).collect();
+        assert_eq!(statuses, vec![Status::Confused, Status::Slowed]);
+    }
+
+    #[test]
+    fn test_migrate_0_1_1() {
+        let status = |data: &str| {
+            format!("(state: (version: \"0.1.1\", ecs: (store: (status: (data: [{}])))))", data)
+        };
+        assert_eq!(
+            status_instance_lists(&status("{Confused: 20, Slowed: 30}, {}")).unwrap(),
+            status("{Confused: [20], Slowed: [30]}, {}").replace("0.1.1", "0.1.2")
+        );
+
+        // Use a 0.1.1 save where the player has been renamed to look like a status entry.
+        let text = include_str!("fixtures/save-0.1.0.ron").replacen(
+            "singular_name: \"player\"",
+            "singular_name: \"Slowed: 3\"",
+            1,
+        );
+        let text = drop_delayed_status(&text).unwrap();
+        assert_eq!(save_version(&text).unwrap(), "0.1.1");
+
+        let world = parse_save(text).unwrap();
+        let player = world.player().unwrap();
+        assert_eq!(world.entity_name(player), "Slowed: 3");
+        let statuses = world.active_statuses(player);
+        assert_eq!(statuses, vec![(Status::Confused, 20), (Status::Slowed, 30)]);
+
+        // The migrated world survives a save and load at the current version.
+        let text = ron::ser::to_string_pretty(&world, Default::default()).unwrap();
+        let loaded = parse_save(text).unwrap();
+        assert_eq!(loaded.active_statuses(player), statuses);
+        assert_eq!(loaded.state_hash(), world.state_hash());
+    }
+
+    #[test]
+    fn test_migrate_0_1_2() {
+        let save = |version, abilities, item_type, base, seed_abilities| {
+            format!(
+                "(state: (version: {version:?}, ecs: (store: (\
+                 abilities: {abilities}, \
+                 desc: (data: [(singular_name: \"player\"), (singular_name: \"torch\")], \
+                 entities: [(uid: 1, idx: 0), (uid: 2, idx: 1)]), \
+                 item: (data: [(item_type: {item_type})], entities: [(uid: 2, idx: 1)]), \
+                 stats: (data: [(base: {base}, actual: (mana: 0))], \
+                 entities: [(uid: 1, idx: 0)]))), \
+                 flags: (player: Some((uid: 1, idx: 0)))), \
+                 history: (seed: (player_character: (loadout: (\
+                 abilities: {seed_abilities}, stats: Some((base: {base})))))))",
+                version = version,
+                abilities = abilities,
+                item_type = item_type,
+                base = base,
+                seed_abilities = seed_abilities,
+            )
+        };
+        assert_eq!(
+            player_light_and_spells(&save(
+                "0.1.2",
+                "(data: [], entities: [])",
+                "Shield",
+                "(mana: 0,)",
+                "None"
+            ))
+            .unwrap(),
+            save(
+                "0.1.3",
+                "(data: [[Heal,]], entities: [(uid: 1, idx: 0)])",
+                "Light",
+                "(mana: 8, light: 3,)",
+                "Some([Heal,])"
+            )
+        );
+
+        // Use a 0.1.2 save from before the player had light, mana or spells.
+        let text = include_str!("fixtures/save-0.1.0.ron");
+        let text = status_instance_lists(&drop_delayed_status(text).unwrap()).unwrap();
+        assert_eq!(save_version(&text).unwrap(), "0.1.2");
+
+        let world = parse_save(text).unwrap();
+        let player = world.player().unwrap();
+        assert_eq!(world.stats(player).light, 3);
+        assert_eq!(world.stats(player).mana, 8);
+        assert_eq!(world.ecs().abilities.get(player), Some(&vec![Ability::Heal]));
+
+        // The migrated player matches the player of a new game.
+        let new_game = self::world();
+        let new_player = new_game.player().unwrap();
+        assert_eq!(world.base_stats(player), new_game.base_stats(new_player));
+        assert_eq!(world.ecs().abilities.get(player), new_game.ecs().abilities.get(new_player));
+
+        // The world seed spawns the same player.
+        let fresh: IncrementalState<World> = world.history().clone().into();
+        assert_eq!(fresh.base_stats(player), world.base_stats(player));
+        assert_eq!(fresh.ecs().abilities.get(player), world.ecs().abilities.get(player));
+    }
+
+    #[test]
+    fn test_migrate_0_1_3() {
+        // Use a 0.1.3 save from before item identification.
+        let text = include_str!("fixtures/save-0.1.0.ron");
+        let text = status_instance_lists(&drop_delayed_status(text).unwrap()).unwrap();
+        let text = player_light_and_spells(&text).unwrap();
+        assert_eq!(save_version(&text).unwrap(), "0.1.3");
+        assert!(find_path(&text, &["state", "identification"]).is_none());
+
+        let migrated = item_identification(&text).unwrap();
+        assert_eq!(save_version(&migrated).unwrap(), "0.1.4");
+        // Saves that already have the table keep it.
+        assert_eq!(item_identification(&migrated).unwrap(), migrated);
+
+        // The table is the same one that replaying the game from the world seed builds.
+        let identification = |text: &str| -> Identification {
+            let span = find_path(text, &["state", "identification"]).unwrap();
+            ron::de::from_str(&text[span]).unwrap()
+        };
+        let world = parse_save(text).unwrap();
+        let fresh: IncrementalState<World> = world.history().clone().into();
+        let fresh = ron::ser::to_string(&fresh).unwrap();
+        assert_eq!(identification(&migrated), identification(&fresh));
+        assert_ne!(identification(&migrated), Identification::default());
+    }
+
+    #[test]
+    fn test_save_roundtrip() {
+        let dir = std::env::temp_dir().join(format!("magog-save-test-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("slot.ron");
+
+        let world = world();
+        write_save(&world, &path, None).unwrap();
+        let loaded = read_save(&path, Format::Ron);
+        fs::remove_dir_all(&dir).unwrap();
+
+        let loaded = loaded.unwrap();
+        assert_eq!(loaded.state_hash(), world.state_hash());
+        assert_eq!(loaded.history().events, world.history().events);
+    }
+
+    #[test]
+    fn test_migration_chain() {
+        fn save(version: &str) -> String { format!("(state: (version: {:?}))", version) }
+        fn a_to_b(text: &str) -> Result<String, SaveError> { Ok(text.replace("\"a\"", "\"b\"")) }
+        fn b_to_current(_: &str) -> Result<String, SaveError> { Ok(save(GAME_VERSION)) }
+        fn stuck(text: &str) -> Result<String, SaveError> { Ok(text.to_string()) }
+        let migrations: &[(&str, Migration)] = &[("b", b_to_current), ("a", a_to_b), ("c", stuck)];
+
+        // Migrations are chained until the save is at the current version.
+        assert_eq!(migrate(save("a"), migrations).unwrap(), save(GAME_VERSION));
+        assert_eq!(migrate(save("b"), migrations).unwrap(), save(GAME_VERSION));
+        assert_eq!(migrate(save(GAME_VERSION), &[]).unwrap(), save(GAME_VERSION));
+
+        // Versions without a migration path are errors.
+        let version = |r: Result<String, SaveError>| match r {
+            Err(SaveError::Version(v)) => Some(v),
+            _ => None,
+        };
+        assert_eq!(version(migrate(save("d"), migrations)), Some("d".into()));
+        assert_eq!(version(migrate(save("c"), migrations)), Some("c".into()));
+    }
+}
@@ -1,16 +1,18 @@
 //! Creature AI and activity loop logic
 
 use crate::{
-    msg,
-    stats::{Intrinsic, Status},
-    Location, World,
+    effect::{Effect, Targeting},
+    msg, noise, spec,
+    stats::Intrinsic,
+    status::Status,
+    World,
 };
 use calx::{Dir6, RngExt};
 use calx_ecs::Entity;
 use serde_derive::{Deserialize, Serialize};
 
 /// Used to determine who tries to fight whom.
-#[derive(Copy, Clone, Eq, PartialEq, Debug, Serialize, Deserialize)]
+#[derive(Copy, Clone, Eq, PartialEq, Ord, PartialOrd, Hash, Debug, Serialize, Deserialize)]
 pub enum Alignment {
     /// Standard dungeon enemies, work in concert against the player.
     Enemy,
@@ -18,13 +20,43 @@ pub enum Alignment {
     Player,
     /// Indifferent or hungry, potential threat to Player and Enemy alike
     Animal,
+    /// Townsfolk who mind their own business unless provoked.
+    Neutral,
 }
 
+impl Alignment {
+    /// Return the default attitude of the faction towards another faction.
+    ///
+    /// The attitudes come from the faction specs. The player's reputation with a faction can
+    /// override this, see `World::relation`.
+    pub fn relation(self, other: Alignment) -> Relation { spec::faction_relation(self, other) }
+}
+
+/// Attitude of one faction towards another.
+#[derive(Copy, Clone, Eq, PartialEq, Debug, Deserialize)]
+pub enum Relation {
+    Hostile,
+    Indifferent,
+    Friendly,
+}
+
+/// Player reputation with a faction at or below which the faction turns hostile.
+const HOSTILE_REPUTATION: i32 = -20;
+
+/// Reputation lost for attacking a creature that wasn't hostile.
+const ATTACK_REPUTATION_LOSS: i32 = 10;
+
 #[derive(Copy, Clone, Eq, PartialEq, Debug, Serialize, Deserialize)]
 pub struct Brain {
     pub state: BrainState,
     pub alignment: Alignment,
+    /// Faction the mob currently sides with instead of its own, eg. when charmed.
+    #[serde(default)]
+    pub allegiance: Option<Alignment>,
     pub shout: ShoutType,
+    /// Action energy, the mob can act when this is not negative.
+    #[serde(default)]
+    pub energy: i32,
 }
 
 impl Brain {
@@ -48,6 +80,11 @@ impl Brain {
         self.shout = shout;
         self
     }
+
+    pub fn alignment(mut self, alignment: Alignment) -> Brain {
+        self.alignment = alignment;
+        self
+    }
 }
 
 impl Default for Brain {
@@ -55,7 +92,43 @@ impl Default for Brain {
         Brain {
             state: BrainState::Asleep,
             alignment: Alignment::Enemy,
+            allegiance: None,
             shout: ShoutType::Silent,
+            energy: 0,
+        }
+    }
+}
+
+/// Energy cost of a standard action.
+///
+/// A creature of normal speed gains enough energy for a standard action in 12 frames.
+const ACTION_ENERGY: i32 = 36;
+
+/// Kinds of actions that take different amounts of time to perform.
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+pub enum Action {
+    Move,
+    Attack,
+    Shoot,
+    Cast,
+    Take,
+    Eat,
+    Operate,
+    Dig,
+    Wait,
+}
+
+impl Action {
+    /// Return how much action energy performing the action costs.
+    pub fn energy_cost(self) -> i32 {
+        use Action::*;
+        match self {
+            Move | Attack | Eat | Operate | Wait => ACTION_ENERGY,
+            // Digging through rock is slow work.
+            Dig => ACTION_ENERGY * 3,
+            // Aiming takes a bit longer.
+            Shoot | Cast => ACTION_ENERGY * 4 / 3,
+            Take => ACTION_ENERGY / 2,
         }
     }
 }
@@ -102,7 +175,7 @@ impl World {
             if !self.is_npc(npc) {
                 continue;
             }
-            if self.ticks_this_frame(npc) {
+            if self.ticks_this_frame(npc) && !self.has_status(npc, Status::Stunned) {
                 self.run_ai_for(npc)
             }
         }
@@ -123,14 +196,12 @@ impl World {
 
         let brain_state = self.brain_state(npc).expect("Running AI for non-mob");
         match brain_state {
-            Asleep => {
-                // TODO: Noise waves that wake up sleeping mobs when they hit them, stop making
-                // each sleeping mob do active scanning every tic.
-                if let Some(e) = self.find_enemy(npc) {
-                    self.designate_enemy(npc, e);
-                }
-            }
+            // Sleeping mobs don't do anything until a noise wakes them up.
+            Asleep => {}
             Hunting(target) => {
+                if self.is_fleeing(npc) && self.ai_flee(npc, target) {
+                    return;
+                }
                 if self.rng().one_chance_in(12) {
                     self.ai_drift(npc);
                 } else {
@@ -151,10 +222,14 @@ impl World {
 
     /// Approach and attack target entity.
     fn ai_hunt(&mut self, npc: Entity, target: Entity) {
+        if self.ai_use_ability(npc, target) || self.ai_shoot(npc, target) {
+            return;
+        }
+
         if let (Some(my_loc), Some(target_loc)) = (self.location(npc), self.location(target)) {
             if my_loc.metric_distance(target_loc) == 1 {
                 let _ = self.entity_melee(npc, my_loc.dir6_towards(target_loc).unwrap());
-            } else if let Some(move_dir) = self.pathing_dir_towards(npc, target_loc) {
+            } else if let Some(move_dir) = self.pathing_dir_towards(npc, target) {
                 let _ = self.entity_step(npc, move_dir);
             } else {
                 self.ai_drift(npc);
@@ -162,6 +237,106 @@ impl World {
         }
     }
 
+    /// Try to move away from a target.
+    ///
+    /// Return false if there's nowhere to run.
+    fn ai_flee(&mut self, npc: Entity, target: Entity) -> bool {
+        // Roll down the target's flee field, which leads away from it without cornering the mob.
+        match self.flow_dir(npc, target, false) {
+            Some(dir) => self.entity_step(npc, dir).is_some(),
+            None => false,
+        }
+    }
+
+    /// Return whether a mob is too badly hurt to keep fighting.
+    fn is_fleeing(&self, npc: Entity) -> bool {
+        const FLEE_HEALTH_DIVISOR: i32 = 4;
+        self.hp(npc) * FLEE_HEALTH_DIVISOR <= self.max_hp(npc)
+    }
+
+    /// Try to shoot a target that is in the line of fire.
+    ///
+    /// Return whether a shot was fired.
+    fn ai_shoot(&mut self, npc: Entity, target: Entity) -> bool {
+        let (my_loc, target_loc) = match (self.location(npc), self.location(target)) {
+            (Some(a), Some(b)) => (a, b),
+            _ => return false,
+        };
+        // Adjacent enemies get meleed instead.
+        if !self.can_fire(npc) || my_loc.metric_distance(target_loc) <= 1 {
+            return false;
+        }
+        let dir = match my_loc.dir6_towards(target_loc) {
+            Some(dir) => dir,
+            None => return false,
+        };
+
+        let range = self.ranged_range(npc);
+        if self.trace_projectile(my_loc, dir, range).1 != Some(target) {
+            return false;
+        }
+        self.entity_fire(npc, dir).is_some()
+    }
+
+    /// Try to use an innate ability against a target.
+    ///
+    /// Return whether an ability was used.
+    fn ai_use_ability(&mut self, npc: Entity, target: Entity) -> bool {
+        let (my_loc, target_loc) = match (self.location(npc), self.location(target)) {
+            (Some(a), Some(b)) => (a, b),
+            _ => return false,
+        };
+        let abilities = match self.ecs().abilities.get(npc) {
+            Some(a) => a.clone(),
+            None => return false,
+        };
+
+        for ability in abilities {
+            let data = ability.data();
+            if self.mana(npc) < data.cost.mana {
+                continue;
+            }
+            if data.targeting != Targeting::User
+                && my_loc.metric_distance(target_loc) > data.range as i32
+            {
+                continue;
+            }
+
+            let dir = match data.targeting {
+                Targeting::Projectile => match my_loc.dir6_towards(target_loc) {
+                    // Only shoot when the target is in the line of fire.
+                    Some(dir)
+                        if self.projected_explosion_center(my_loc, dir, data.range)
+                            == target_loc =>
+                    {
+                        Some(dir)
+                    }
+                    _ => continue,
+                },
+                Targeting::RandomMob => None,
+                // Patch yourself up when badly hurt, other abilities that hit the user aren't
+                // used in a fight.
+                Targeting::User
+                    if self.hp(npc) * 2 < self.max_hp(npc)
+                        && data.effects.iter().any(|e| matches!(e, Effect::Heal(_))) =>
+                {
+                    None
+                }
+                Targeting::User => continue,
+            };
+
+            // Don't spam the ability every turn.
+            if !self.rng().one_chance_in(3) {
+                continue;
+            }
+
+            if self.use_ability(npc, ability, dir, None).is_some() {
+                return true;
+            }
+        }
+        false
+    }
+
     /// Wander around aimlessly
     fn ai_drift(&mut self, npc: Entity) {
         let dirs = Dir6::permuted_dirs(self.rng());
@@ -181,6 +356,7 @@ impl World {
         if let BrainState::Hunting(x) = brain_state {
             // Is the existing target still valid?
             if self.is_alive(x)
+                && self.can_perceive(npc, x)
                 && self
                     .distance_between(npc, x)
                     .map_or(false, |d| d <= FLEE_THRESHOLD)
@@ -189,15 +365,16 @@ impl World {
             }
         }
 
-        // TODO: Possibility to pick other targets than player
-        // TODO: Creatures that are friendly or neutral to player
+        let loc = self.location(npc)?;
 
-        if let (Some(loc), Some(player), Some(player_loc)) = (
-            self.location(npc),
+        if let (Some(player), Some(player_loc)) = (
             self.player(),
             self.player().map(|p| self.location(p)).unwrap_or(None),
         ) {
-            if self.player_sees(loc) {
+            if self.is_hostile_to(npc, player)
+                && self.player_sees(loc)
+                && self.can_perceive(npc, player)
+            {
                 // Okay, tricky spot. Player might be seeing mob across a portal, in
                 // which case we can't do naive distance check.
                 // This could have a helper method that finds chart distance to self in
@@ -211,18 +388,51 @@ impl World {
                 }
             }
         }
-        None
+
+        // Go after the closest hostile creature nearby.
+        self.active_mobs()
+            .into_iter()
+            .filter(|&e| e != npc && !self.is_player(e) && self.is_hostile_to(npc, e))
+            .filter(|&e| self.can_perceive(npc, e))
+            .filter_map(|e| Some((self.location(e)?.distance_from(loc)?, e)))
+            .filter(|&(d, _)| d <= WAKEUP_DISTANCE)
+            .min_by_key(|&(d, _)| d)
+            .map(|(_, e)| e)
+    }
+
+    /// Return whether a mob can notice the other entity.
+    ///
+    /// Invisible entities can only be noticed from right next to them.
+    fn can_perceive(&self, npc: Entity, other: Entity) -> bool {
+        !self.has_status(other, Status::Invisible)
+            || self.distance_between(npc, other).map(|d| d <= 1) == Some(true)
     }
 
     /// End move for entity.
     ///
-    /// Applies delay.
-    pub(crate) fn end_turn(&mut self, e: Entity) {
-        let delay = self.action_delay(e);
-        self.gain_status(e, Status::Delayed, delay);
+    /// Spends the energy the action took.
+    pub(crate) fn end_turn(&mut self, e: Entity, action: Action) {
+        if let Some(brain) = self.ecs_mut().brain.get_mut(e) {
+            brain.energy -= action.energy_cost();
+        }
+    }
+
+    /// Regain energy spent on actions.
+    pub(crate) fn recover_energy(&mut self, e: Entity) {
+        let speed = self.speed(e);
+        if let Some(brain) = self.ecs_mut().brain.get_mut(e) {
+            // Energy doesn't accumulate past what is needed to act.
+            if brain.energy < 0 {
+                brain.energy += speed;
+            }
+        }
     }
 
     pub(crate) fn notify_attacked_by(&mut self, victim: Entity, attacker: Entity) {
+        if self.is_player(attacker) && !self.is_hostile_to(victim, attacker) {
+            self.lose_reputation(victim);
+        }
+
         // TODO: Check if victim is already in close combat and don't disengage against new target
         // if it is.
         // TODO: The idea is to do Doom-style thing where friendly fire will occasionally cause
@@ -233,19 +443,57 @@ impl World {
     fn designate_enemy(&mut self, e: Entity, target: Entity) {
         // TODO: Probably want this logic to be more complex eventually.
         if self.is_npc(e) {
-            if self.brain_state(e) == Some(BrainState::Asleep) {
-                self.shout(e);
-            }
+            let was_asleep = self.brain_state(e) == Some(BrainState::Asleep);
             if let Some(brain) = self.ecs_mut().brain.get_mut(e) {
                 brain.state = BrainState::Hunting(target);
             }
+            if was_asleep {
+                self.shout(e);
+            }
+        }
+    }
+
+    /// Lower the player's reputation with the faction of a creature the player attacked.
+    fn lose_reputation(&mut self, victim: Entity) {
+        let faction = match self.ecs().brain.get(victim) {
+            // Use the native faction, charmed creatures still belong to it.
+            Some(brain) if brain.alignment != Alignment::Player => brain.alignment,
+            _ => return,
+        };
+        let reputation = self.flags.reputation.entry(faction).or_insert(0);
+        let was_tolerated = *reputation > HOSTILE_REPUTATION;
+        *reputation -= ATTACK_REPUTATION_LOSS;
+        if was_tolerated && *reputation <= HOSTILE_REPUTATION {
+            msg!("You have made enemies.");
+        }
+    }
+
+    /// Wake up a sleeping mob that heard a noise.
+    ///
+    /// If the mob is hostile to the source of the noise, it goes after the source, otherwise it
+    /// starts looking around.
+    pub(crate) fn hear_noise(&mut self, npc: Entity, source: Option<Entity>) {
+        if self.brain_state(npc) != Some(BrainState::Asleep) {
+            return;
+        }
+        match source {
+            Some(source) if self.is_hostile_to(npc, source) => self.designate_enemy(npc, source),
+            _ => {
+                if let Some(brain) = self.ecs_mut().brain.get_mut(npc) {
+                    brain.state = BrainState::Roaming;
+                }
+            }
         }
     }
 
     /// Make a mob shout according to its type.
+    ///
+    /// Shouting makes noise that can wake up other monsters.
     pub(crate) fn shout(&mut self, e: Entity) {
-        // TODO: Create noise, wake up other nearby monsters.
         if let Some(shout) = self.ecs().brain.get(e).map(|b| b.shout) {
+            if shout != ShoutType::Silent {
+                self.mob_noise(e, noise::SHOUT_NOISE);
+            }
             match shout {
                 ShoutType::Shout => {
                     msg!("[One] shout[s] angrily."; self.subject(e));
@@ -280,17 +528,52 @@ impl World {
     pub fn is_mob(&self, e: Entity) -> bool { self.ecs().brain.contains(e) }
 
     /// Return the AI state of an entity.
-    fn brain_state(&self, e: Entity) -> Option<BrainState> {
+    pub(crate) fn brain_state(&self, e: Entity) -> Option<BrainState> {
         self.ecs().brain.get(e).map(|brain| brain.state)
     }
 
     /// Return the value for how a mob will react to other mobs.
+    ///
+    /// Allegiance overrides the mob's native alignment.
     pub fn alignment(&self, e: Entity) -> Option<Alignment> {
-        self.ecs().brain.get(e).map(|b| b.alignment)
+        self.ecs()
+            .brain
+            .get(e)
+            .map(|b| b.allegiance.unwrap_or(b.alignment))
+    }
+
+    /// Make a mob side with a different faction.
+    ///
+    /// The mob forgets its current target.
+    pub(crate) fn set_allegiance(&mut self, e: Entity, alignment: Alignment) {
+        if let Some(brain) = self.ecs_mut().brain.get_mut(e) {
+            brain.allegiance = Some(alignment);
+            if let BrainState::Hunting(_) = brain.state {
+                brain.state = BrainState::Roaming;
+            }
+        }
+    }
+
+    /// Return the player's standing with a faction.
+    pub fn reputation(&self, faction: Alignment) -> i32 {
+        self.flags.reputation.get(&faction).cloned().unwrap_or(0)
+    }
+
+    /// Return the attitude of one faction towards another.
+    pub fn relation(&self, a: Alignment, b: Alignment) -> Relation {
+        let other = match (a, b) {
+            (Alignment::Player, other) | (other, Alignment::Player) => other,
+            _ => return a.relation(b),
+        };
+        if other != Alignment::Player && self.reputation(other) <= HOSTILE_REPUTATION {
+            Relation::Hostile
+        } else {
+            a.relation(b)
+        }
     }
 
-    /// Return how many frames the entity will delay after an action.
-    pub(crate) fn action_delay(&self, e: Entity) -> u32 {
+    /// Return how much action energy the entity gains every frame.
+    pub fn speed(&self, e: Entity) -> i32 {
         // Granular speed system:
         // | slow and slowed  | 1 |
         // | slow or slowed   | 2 |
@@ -298,43 +581,26 @@ impl World {
         // | quick or hasted  | 4 |
         // | quick and hasted | 5 |
 
-        let mut speed = 3;
-        if self.has_intrinsic(e, Intrinsic::Slow) {
-            speed -= 1;
-        }
-        if self.has_status(e, Status::Slowed) {
-            speed -= 1;
-        }
-        if self.has_intrinsic(e, Intrinsic::Quick) {
-            speed += 1;
-        }
-        if self.has_status(e, Status::Hasted) {
-            speed += 1;
-        }
+        // The intrinsics and statuses apply as modifiers to the speed stat.
+        (3 + self.stats(e).speed).max(1)
+    }
 
-        match speed {
-            1 => 36,
-            2 => 18,
-            3 => 12,
-            4 => 9,
-            5 => 7,
-            _ => panic!("Invalid speed value {}", speed),
-        }
+    /// Return how many frames it takes for the entity to recover from an action.
+    pub(crate) fn action_delay(&self, e: Entity, action: Action) -> u32 {
+        let speed = self.speed(e);
+        debug_assert!(speed > 0);
+        ((action.energy_cost() + speed - 1) / speed) as u32
     }
 
     /// Return if the entity is a mob that should get an update this frame
-    /// based on its speed properties. Does not check for status effects like
+    /// based on its action energy. Does not check for status effects like
     /// sleep that might prevent actual action.
     pub fn ticks_this_frame(&self, e: Entity) -> bool {
-        if !self.is_mob(e) || !self.is_alive(e) {
-            return false;
-        }
-
-        if self.has_status(e, Status::Delayed) {
+        if !self.is_alive(e) {
             return false;
         }
 
-        true
+        matches!(self.ecs().brain.get(e), Some(brain) if brain.energy >= 0)
     }
 
     /// Return whether the entity is dead and should be removed from the world.
@@ -360,7 +626,7 @@ impl World {
 
     /// Return whether the entity is a mob that will act this frame.
     pub fn acts_this_frame(&self, e: Entity) -> bool {
-        if !self.is_active(e) {
+        if !self.is_active(e) || self.has_status(e, Status::Stunned) {
             return false;
         }
         self.ticks_this_frame(e)
@@ -382,19 +648,17 @@ impl World {
         }
 
         if let Some(BrainState::Hunting(target)) = self.brain_state(npc) {
-            if npc == target {
+            if other == target {
                 // Already beating him up, obviously he must've done something bad to make you
                 // fight him.
                 return true;
             }
         }
 
-        let (a, b) = (self.alignment(npc), self.alignment(other));
-        if a.is_none() || b.is_none() {
-            return false;
+        match (self.alignment(npc), self.alignment(other)) {
+            (Some(a), Some(b)) => self.relation(a, b) == Relation::Hostile,
+            _ => false,
         }
-
-        a != b
     }
 
     /// Look for targets to shoot in a direction.
@@ -415,12 +679,17 @@ impl World {
         None
     }
 
-    /// Try to get the next step on the path from origin towards destination.
+    /// Try to get the next step on the path from the entity towards the target.
     ///
-    /// Tries to be fast, not necessarily doing proper pathfinding.
-    pub(crate) fn pathing_dir_towards(&self, e: Entity, destination: Location) -> Option<Dir6> {
-        // Could do all sorts of cool things here eventually like a Dijkstra map cache, but for now
-        // just doing very simple stuff.
+    /// Follows the target's flow field when the target is close enough to have one, otherwise
+    /// just heads in the general direction of the target.
+    pub(crate) fn pathing_dir_towards(&self, e: Entity, target: Entity) -> Option<Dir6> {
+        if let Some(dir) = self.flow_dir(e, target, true) {
+            return Some(dir);
+        }
+
+        // Out of flow field range or other mobs are blocking the path, just try to get closer.
+        let destination = self.location(target)?;
         if let Some(origin) = self.location(e) {
             if let Some(dir) = origin.dir6_towards(destination) {
                 // Try direct approach, the the other directions.
@@ -450,3 +719,123 @@ impl World {
             .collect()
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::{Action, Alignment, Relation, ATTACK_REPUTATION_LOSS};
+    use crate::{effect::Effect, status::Status, ExternalEntity, World, WorldSeed};
+    use calx_ecs::Entity;
+
+    fn spawn(world: &mut World, name: &str) -> Entity {
+        let loc = world.location(world.player().unwrap()).unwrap();
+        world.spawn(&ExternalEntity::from_name(name).unwrap(), loc)
+    }
+
+    #[test]
+    fn test_relations() {
+        use Alignment::*;
+        let factions = [Enemy, Player, Animal, Neutral];
+        for &a in &factions {
+            assert_eq!(a.relation(a), Relation::Friendly);
+            for &b in &factions {
+                assert_eq!(a.relation(b), b.relation(a));
+            }
+        }
+        // Animals go after everyone else.
+        assert_eq!(Animal.relation(Enemy), Relation::Hostile);
+        assert_eq!(Animal.relation(Player), Relation::Hostile);
+        assert_eq!(Neutral.relation(Player), Relation::Indifferent);
+    }
+
+    #[test]
+    fn test_reputation_loss() {
+        let mut world = World::new(&WorldSeed::test(1));
+        let player = world.player().unwrap();
+        let dog = spawn(&mut world, "dog");
+        let cat = spawn(&mut world, "cat");
+        let dreg = spawn(&mut world, "dreg");
+        assert!(!world.is_hostile_to(cat, player));
+
+        // Hitting enemies that are already hostile costs nothing.
+        world.notify_attacked_by(dreg, player);
+        assert_eq!(world.reputation(Alignment::Enemy), 0);
+
+        // Townsfolk put up with one attack.
+        world.notify_attacked_by(dog, player);
+        assert_eq!(world.reputation(Alignment::Neutral), -ATTACK_REPUTATION_LOSS);
+        assert_eq!(world.relation(Alignment::Neutral, Alignment::Player), Relation::Indifferent);
+        assert!(!world.is_hostile_to(cat, player));
+
+        // The dog is now hostile, so attacking it again doesn't count.
+        world.notify_attacked_by(dog, player);
+        assert_eq!(world.reputation(Alignment::Neutral), -ATTACK_REPUTATION_LOSS);
+
+        // A second victim turns the whole faction against the player.
+        world.notify_attacked_by(cat, player);
+        assert_eq!(world.reputation(Alignment::Neutral), -2 * ATTACK_REPUTATION_LOSS);
+        assert_eq!(world.relation(Alignment::Player, Alignment::Neutral), Relation::Hostile);
+        let other_cat = spawn(&mut world, "cat");
+        assert!(world.is_hostile_to(other_cat, player));
+        assert!(world.is_hostile_to(player, other_cat));
+
+        // Other factions don't care.
+        assert_eq!(world.relation(Alignment::Neutral, Alignment::Enemy), Relation::Hostile);
+        assert_eq!(world.reputation(Alignment::Animal), 0);
+    }
+
+    #[test]
+    fn test_charm() {
+        let mut world = World::new(&WorldSeed::test(1));
+        let player = world.player().unwrap();
+        let dreg = spawn(&mut world, "dreg");
+        let other_dreg = spawn(&mut world, "dreg");
+        assert!(world.is_hostile_to(dreg, player));
+
+        world.apply_effect_to_entity(&Effect::Charm, dreg, Some(player));
+        assert_eq!(world.alignment(dreg), Some(Alignment::Player));
+        assert!(!world.is_hostile_to(dreg, player));
+        assert!(world.is_hostile_to(dreg, other_dreg));
+        assert!(world.is_hostile_to(other_dreg, dreg));
+
+        // The charmed mob still counts as a member of its own faction.
+        assert_eq!(world.ecs().brain[dreg].alignment, Alignment::Enemy);
+        world.notify_attacked_by(dreg, player);
+        assert_eq!(world.reputation(Alignment::Enemy), -ATTACK_REPUTATION_LOSS);
+
+        // Charm only works on other factions' mobs.
+        world.apply_effect_to_entity(&Effect::Charm, player, Some(other_dreg));
+        assert_eq!(world.alignment(player), Some(Alignment::Player));
+        world.apply_effect_to_entity(&Effect::Charm, other_dreg, None);
+        assert_eq!(world.alignment(other_dreg), Some(Alignment::Enemy));
+    }
+
+    #[test]
+    fn test_energy_scheduler() {
+        let mut world = World::new(&WorldSeed::test(1));
+        let loc = world.location(world.player().unwrap()).unwrap();
+        let dreg = ExternalEntity::from_name("dreg").unwrap();
+        let slow = world.spawn(&dreg, loc);
+        let fast = world.spawn(&dreg, loc);
+        world.gain_status(slow, Status::Slowed, 1000);
+        world.gain_status(fast, Status::Hasted, 1000);
+        assert_eq!((world.speed(slow), world.speed(fast)), (2, 4));
+
+        // Run the scheduler with both mobs moving whenever they have the energy for it.
+        let mut moves = [0, 0];
+        for _ in 0..120 {
+            for (i, &e) in [slow, fast].iter().enumerate() {
+                world.recover_energy(e);
+                if world.ticks_this_frame(e) {
+                    world.end_turn(e, Action::Move);
+                    moves[i] += 1;
+                }
+            }
+        }
+        // A move costs 36 energy, so it takes the slow mob 18 ticks to recover and the fast one 9.
+        assert_eq!(moves, [7, 14]);
+
+        // Slower actions take longer to recover from.
+        assert_eq!(world.action_delay(fast, Action::Move), 9);
+        assert_eq!(world.action_delay(fast, Action::Dig), 27);
+    }
+}
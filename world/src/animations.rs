@@ -88,7 +88,7 @@ pub struct Anim {
     pub state: AnimState,
 }
 
-#[derive(Eq, PartialEq, Copy, Clone, Debug, Serialize, Deserialize)]
+#[derive(Eq, PartialEq, Copy, Clone, Hash, Debug, Serialize, Deserialize)]
 pub enum AnimState {
     /// Mob decorator, doing nothing in particular
     Mob,
@@ -104,6 +104,8 @@ pub enum AnimState {
     Explosion,
     /// Pre-exploded fireball
     Firespell,
+    /// Arrow or other projectile in flight
+    Missile,
 }
 
 impl AnimState {
@@ -113,7 +115,7 @@ impl AnimState {
         use AnimState::*;
         match self {
             Mob | MobHurt | MobBlocks => false,
-            Gib | Smoke | Explosion | Firespell => true,
+            Gib | Smoke | Explosion | Firespell | Missile => true,
         }
     }
 }
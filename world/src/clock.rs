@@ -0,0 +1,210 @@
+//! Time of day on the surface
+
+use crate::{sector::SECTOR_WIDTH, spec, Location, Sector, World};
+use calx::{seeded_rng, RngExt, WeightedChoice};
+use euclid::vec2;
+use rand::Rng;
+
+/// How many ticks a full day and night takes.
+pub const DAY_LENGTH: u64 = 14400;
+
+/// Time of day when the world starts, in the morning.
+const START_TIME: u64 = 1200;
+
+/// How many ticks dawn and dusk take.
+const TWILIGHT_LENGTH: u64 = 1200;
+
+/// Time of day when dusk starts.
+const DUSK: u64 = 7200;
+
+/// Time of day when night starts.
+const NIGHT: u64 = DUSK + TWILIGHT_LENGTH;
+
+/// Ambient light on the surface at night.
+const NIGHT_LIGHT: f32 = 0.3;
+
+/// Sight range on the surface at night.
+const NIGHT_SIGHT_RANGE: i32 = 8;
+
+/// How often nocturnal creatures may come out near the player at night.
+const NIGHT_SPAWN_INTERVAL: u64 = 600;
+
+/// Parts of the day on the surface.
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+pub enum DayPhase {
+    Dawn,
+    Day,
+    Dusk,
+    Night,
+}
+
+impl DayPhase {
+    pub fn name(self) -> &'static str {
+        match self {
+            DayPhase::Dawn => "dawn",
+            DayPhase::Day => "day",
+            DayPhase::Dusk => "dusk",
+            DayPhase::Night => "night",
+        }
+    }
+}
+
+impl World {
+    /// Return the time of day in ticks since dawn.
+    fn time_of_day(&self) -> u64 { (self.get_tick() + START_TIME) % DAY_LENGTH }
+
+    /// Return which part of the day it is.
+    pub fn day_phase(&self) -> DayPhase {
+        match self.time_of_day() {
+            t if t < TWILIGHT_LENGTH => DayPhase::Dawn,
+            t if t < DUSK => DayPhase::Day,
+            t if t < NIGHT => DayPhase::Dusk,
+            _ => DayPhase::Night,
+        }
+    }
+
+    /// Return how bright the sky is, from the darkness of night to full daylight.
+    pub fn daylight(&self) -> f32 {
+        let t = self.time_of_day();
+        let twilight = |t: u64| t as f32 / TWILIGHT_LENGTH as f32;
+        match self.day_phase() {
+            DayPhase::Dawn => NIGHT_LIGHT + (1.0 - NIGHT_LIGHT) * twilight(t),
+            DayPhase::Day => 1.0,
+            DayPhase::Dusk => 1.0 - (1.0 - NIGHT_LIGHT) * twilight(t - DUSK),
+            DayPhase::Night => NIGHT_LIGHT,
+        }
+    }
+
+    /// Return how far you can see on the surface at this time of day.
+    pub(crate) fn surface_sight_range(&self) -> i32 {
+        let t = (self.daylight() - NIGHT_LIGHT) / (1.0 - NIGHT_LIGHT);
+        NIGHT_SIGHT_RANGE + ((SECTOR_WIDTH - NIGHT_SIGHT_RANGE) as f32 * t).round() as i32
+    }
+
+    /// Advance the time of day.
+    pub(crate) fn tick_clock(&mut self) {
+        // Sight range changes with daylight.
+        if self.get_tick().is_multiple_of(TWILIGHT_LENGTH / 10) {
+            if let Some(player) = self.player() {
+                self.do_fov(player);
+            }
+        }
+
+        if self.day_phase() == DayPhase::Night
+            && self.get_tick().is_multiple_of(NIGHT_SPAWN_INTERVAL)
+        {
+            self.spawn_night_creature();
+        }
+    }
+
+    /// Maybe spawn a nocturnal creature out of sight of the player on the surface.
+    fn spawn_night_creature(&mut self) {
+        let origin = match self.player().and_then(|p| self.location(p)) {
+            Some(loc) if !self.is_underground(loc) => loc,
+            _ => return,
+        };
+        let biome = match self.world_cache.sector_biome(Sector::from(origin)) {
+            Some(biome) => biome,
+            None => return,
+        };
+        let depth = self.depth(origin);
+
+        // Fresh generator from the time so the spawns don't disturb the world RNG.
+        let mut rng = seeded_rng(&(self.rng_seed(), self.get_tick(), "night"));
+        if !rng.one_chance_in(2) {
+            return;
+        }
+        let spec = match spec::iter_specs().weighted_choice(&mut rng, |spec| {
+            if spec.rarity() == 0.0 || !spec.is_nocturnal() || !spec.can_spawn_in(biome, depth) {
+                0.0
+            } else {
+                1.0 / spec.rarity()
+            }
+        }) {
+            Some(spec) => spec,
+            None => return,
+        };
+
+        for _ in 0..10 {
+            let range = NIGHT_SIGHT_RANGE + 2;
+            let loc: Location =
+                origin + vec2(rng.gen_range(-range, range + 1), rng.gen_range(-range, range + 1));
+            let dist = origin.distance_from(loc).unwrap_or(0);
+            if dist > NIGHT_SIGHT_RANGE / 2 && !self.blocks_walk(loc) && !self.player_sees(loc) {
+                self.spawn(&spec.sample(&mut rng), loc);
+                return;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{DayPhase, DAY_LENGTH, NIGHT_SIGHT_RANGE, NIGHT_SPAWN_INTERVAL};
+    use crate::{sector::SECTOR_WIDTH, spec, World, WorldSeed};
+
+    #[test]
+    fn test_day_and_night() {
+        let mut world = World::new(&WorldSeed::test(1));
+        assert_eq!(world.day_phase(), DayPhase::Day);
+        assert_eq!(world.daylight(), 1.0);
+        assert_eq!(world.surface_sight_range(), SECTOR_WIDTH);
+
+        let mut phases = Vec::new();
+        for tick in 0..DAY_LENGTH {
+            world.flags.tick = tick;
+            if phases.last() != Some(&world.day_phase()) {
+                phases.push(world.day_phase());
+            }
+            assert!(world.daylight() > 0.0 && world.daylight() <= 1.0);
+        }
+        use DayPhase::*;
+        assert_eq!(phases, vec![Day, Dusk, Night, Dawn]);
+
+        world.flags.tick = DAY_LENGTH / 2;
+        assert_eq!(world.day_phase(), Night);
+        assert_eq!(world.surface_sight_range(), NIGHT_SIGHT_RANGE);
+        let player = world.player().unwrap();
+        let loc = world.location(player).unwrap();
+        assert!(world.light_level(loc) > 0.9);
+        // The sky doesn't light up the night but the player's own light does.
+        assert!(world.light_level(loc + euclid::vec2(5, 0)) < 0.5);
+    }
+
+    #[test]
+    fn test_nocturnal_specs() {
+        let bat = spec::iter_specs().find(|s| s.name() == "bat").unwrap();
+        assert!(bat.is_nocturnal());
+        let sword = spec::iter_specs().find(|s| s.name() == "sword").unwrap();
+        assert!(!sword.is_nocturnal());
+    }
+
+    #[test]
+    fn test_night_spawns() {
+        let run = || {
+            let mut world = World::new(&WorldSeed::test(1));
+            let mobs = |world: &World| world.ecs.brain.ent_iter().cloned().collect::<Vec<_>>();
+            let before = mobs(&world);
+
+            // Nothing comes out during the day.
+            world.spawn_night_creature();
+            assert_eq!(mobs(&world), before);
+
+            world.flags.tick = DAY_LENGTH / 2;
+            for _ in 0..20 {
+                world.spawn_night_creature();
+                world.flags.tick += NIGHT_SPAWN_INTERVAL;
+            }
+            let spawned: Vec<_> =
+                mobs(&world).into_iter().filter(|e| !before.contains(e)).collect();
+            assert!(!spawned.is_empty());
+            for &e in &spawned {
+                let name = &world.ecs.desc[e].singular_name;
+                assert!(spec::iter_specs().any(|s| s.name() == name && s.is_nocturnal()));
+            }
+            world.state_hash()
+        };
+        // Night spawns are the same every time.
+        assert_eq!(run(), run());
+    }
+}
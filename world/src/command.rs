@@ -1,8 +1,10 @@
-use crate::{Ability, Slot, World, WorldSeed};
+use crate::{Ability, Slot, Stat, World, WorldSeed};
 use calx::Dir6;
 use calx::Incremental;
 use calx_ecs::Entity;
 use serde_derive::{Deserialize, Serialize};
+use std::error::Error;
+use std::fmt;
 
 /// Return type for actions that might fail.
 ///
@@ -12,7 +14,7 @@ use serde_derive::{Deserialize, Serialize};
 pub type ActionOutcome = Option<bool>;
 
 /// Player command events that the world is updated with.
-#[derive(Copy, Clone, Eq, PartialEq, Debug, Serialize, Deserialize)]
+#[derive(Clone, Eq, PartialEq, Debug, Serialize, Deserialize)]
 pub enum Command {
     /// Called to update the state on frames where the player can't act.
     Wait,
@@ -22,9 +24,9 @@ pub enum Command {
     Step(Dir6),
     /// Melee attack in direction.
     Melee(Dir6),
+    /// Shoot a ranged attack in direction.
+    Fire(Dir6),
     /// Pick up the topmost item from the floor where you're standing on.
-    ///
-    /// TODO: Item selection support.
     Take,
     /// Drop an item from inventory slot.
     Drop(Slot),
@@ -48,8 +50,58 @@ pub enum Command {
         dir: Dir6,
         item: Option<Entity>,
     },
+    /// Pick up a number of items from a stack on the floor where you're standing on.
+    TakeItem(Entity, u32),
+    /// Pick up everything from the floor where you're standing on.
+    TakeAll,
+    /// Eat a food item from inventory slot.
+    Eat(Slot),
+    /// Dig through the wall in direction.
+    Dig(Dir6),
+    /// Open or close the door in direction.
+    ToggleDoor(Dir6),
+    /// Give a custom name to the kind of an unidentified item.
+    ///
+    /// An empty name removes the custom name.
+    CallItem(Entity, String),
+}
+
+/// Reason why a command can't be carried out.
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+pub enum CommandError {
+    /// The command makes no sense in the current state of the world.
+    Invalid,
+    /// There's no free slot to put the item in.
+    NoRoom,
+    /// The item doesn't go in the slot.
+    WrongSlot,
+    /// The item is cursed and can't be taken off.
+    Cursed(Entity),
+    /// Character level is below the requirement of the item.
+    LevelTooLow(i32),
+    /// Stat is below the requirement of the item.
+    StatTooLow(Stat, i32),
+    /// A two-handed weapon and something in the other hand.
+    HandsFull,
+}
+
+impl fmt::Display for CommandError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        use CommandError::*;
+        match self {
+            Invalid => write!(f, "Can't do that."),
+            NoRoom => write!(f, "No room for that."),
+            WrongSlot => write!(f, "That doesn't go there."),
+            Cursed(_) => write!(f, "It's cursed and won't come off."),
+            LevelTooLow(level) => write!(f, "Needs character level {}.", level),
+            StatTooLow(stat, value) => write!(f, "Needs {} {}.", stat.name(), value),
+            HandsFull => write!(f, "Needs both hands."),
+        }
+    }
 }
 
+impl Error for CommandError {}
+
 impl Incremental for World {
     type Seed = WorldSeed;
     type Event = Command;
@@ -72,50 +124,116 @@ impl World {
     /// Return whether a command will work in the current world state.
     ///
     /// Mostly for cases where the feedback is important for the UI (eg. inventory logic).
-    pub fn can_command(&self, cmd: &Command) -> bool {
+    pub fn can_command(&self, cmd: &Command) -> bool { self.check_command(cmd).is_ok() }
+
+    /// Check whether a command will work in the current world state, and explain why not if it
+    /// won't.
+    pub fn check_command(&self, cmd: &Command) -> Result<(), CommandError> {
         use Command::*;
+        use CommandError::*;
 
-        if self.player().is_none() {
-            return *cmd == Command::Wait;
-        }
-        let player = self.player().unwrap();
+        let player = match self.player() {
+            Some(player) => player,
+            None if *cmd == Command::Wait => return Ok(()),
+            None => return Err(Invalid),
+        };
 
         match cmd {
-            Wait => !self.player_can_act(),
+            Wait if self.player_can_act() => Err(Invalid),
+
+            Drop(slot) => {
+                let item = self.entity_equipped(player, *slot).ok_or(Invalid)?;
+                self.check_unequip(player, item)
+            }
+
+            Equip(slot) => {
+                let item = self.entity_equipped(player, *slot).ok_or(Invalid)?;
+                if slot.is_equipment_slot() {
+                    self.check_unequip(player, item)?;
+                    self.free_bag_slot(player).ok_or(NoRoom)?;
+                    Ok(())
+                } else {
+                    let slot = self.free_equip_slot(player, item).ok_or(NoRoom)?;
+                    self.check_equip(player, item, slot)
+                }
+            }
 
             InventoryPlace(item, slot) => {
                 if !self.entity_contains(player, *item) {
-                    return false;
+                    return Err(Invalid);
                 }
                 if self.entity_equipped(player, *slot).is_some() {
-                    return false;
+                    return Err(NoRoom);
                 }
-                if !slot.accepts(self.equip_type(*item)) {
-                    return false;
-                }
-                true
+                self.check_unequip(player, *item)?;
+                self.check_equip(player, *item, *slot)
             }
 
             InventorySwap(slot1, slot2) => {
                 if slot1 == slot2 {
-                    return false;
+                    return Err(Invalid);
                 }
-                if let Some(e) = self.entity_equipped(player, *slot1) {
-                    if !slot2.accepts(self.equip_type(e)) {
-                        return false;
+                let e1 = self.entity_equipped(player, *slot1);
+                let e2 = self.entity_equipped(player, *slot2);
+                for &(e, to) in &[(e1, *slot2), (e2, *slot1)] {
+                    if let Some(e) = e {
+                        self.check_unequip(player, e)?;
+                        self.check_equip(player, e, to)?;
                     }
                 }
+                Ok(())
+            }
 
-                if let Some(e) = self.entity_equipped(player, *slot2) {
-                    if !slot1.accepts(self.equip_type(e)) {
-                        return false;
-                    }
+            Fire(_) if !self.can_fire(player) => Err(Invalid),
+            Dig(dir) if !self.can_dig(player, *dir) => Err(Invalid),
+            ToggleDoor(dir) if !self.can_toggle_door(player, *dir) => Err(Invalid),
+
+            Eat(slot) => {
+                let item = self.entity_equipped(player, *slot).ok_or(Invalid)?;
+                if self.hunger(player).is_none() || self.food_value(item).is_none() {
+                    return Err(Invalid);
+                }
+                self.check_unequip(player, item)
+            }
+
+            Take => {
+                let item = self.item_at(self.location(player).ok_or(Invalid)?).ok_or(Invalid)?;
+                if self.has_room_for(player, item) {
+                    Ok(())
+                } else {
+                    Err(NoRoom)
+                }
+            }
+
+            TakeItem(item, count) => {
+                let loc = self.location(player).ok_or(Invalid)?;
+                if !self.items_at(loc).contains(item) || *count == 0 || *count > self.count(*item)
+                {
+                    return Err(Invalid);
+                }
+                if self.has_room_for(player, *item) {
+                    Ok(())
+                } else {
+                    Err(NoRoom)
                 }
-                true
             }
 
+            TakeAll => {
+                let loc = self.location(player).ok_or(Invalid)?;
+                let items = self.items_at(loc);
+                if items.is_empty() {
+                    Err(Invalid)
+                } else if items.iter().any(|&item| self.has_room_for(player, item)) {
+                    Ok(())
+                } else {
+                    Err(NoRoom)
+                }
+            }
+
+            CallItem(item, _) if self.is_identified(*item) => Err(Invalid),
+
             // TODO: Add failure checks for the rest as needed.
-            _ => true,
+            _ => Ok(()),
         }
     }
 
@@ -135,28 +253,58 @@ impl World {
                 let player = self.player()?;
                 self.entity_melee(player, *dir)
             }
+            Fire(dir) => {
+                let player = self.player()?;
+                self.entity_fire(player, *dir)
+            }
             Take => {
                 let player = self.player()?;
                 let item = self.item_at(self.location(player)?)?;
                 self.entity_take(player, item)
             }
+            TakeItem(item, count) => {
+                let player = self.player()?;
+                self.entity_take_count(player, *item, *count)
+            }
+            TakeAll => {
+                let player = self.player()?;
+                self.entity_take_all(player)
+            }
+            Eat(slot) => {
+                let player = self.player()?;
+                self.check_command(cmd).ok()?;
+                self.eat(player, self.entity_equipped(player, *slot)?)
+            }
+            Dig(dir) => {
+                let player = self.player()?;
+                self.dig(player, *dir)
+            }
+            ToggleDoor(dir) => {
+                let player = self.player()?;
+                self.toggle_door(player, *dir)
+            }
+            CallItem(item, name) => {
+                self.call_item(*item, name);
+                // Naming things is bookkeeping, it doesn't take time.
+                Some(false)
+            }
             Drop(slot) => {
                 let player = self.player()?;
-                self.place_entity(self.entity_equipped(player, *slot)?, self.location(player)?);
+                self.check_command(cmd).ok()?;
+                self.drop_item(self.entity_equipped(player, *slot)?, self.location(player)?);
                 // Dropping items does not cost a turn since you'll be doing it from the inventory
                 // screen.
                 Some(false)
             }
             Equip(slot) => {
                 let player = self.player()?;
+                self.check_command(cmd).ok()?;
                 let item = self.entity_equipped(player, *slot)?;
                 let swap_slot = if slot.is_equipment_slot() {
                     // Remove equipped.
-                    // TODO: Items that can't be removed because of curses etc. trip here.
                     self.free_bag_slot(player)?
                 } else {
                     // Equip from bag.
-                    // TODO: Inability to equip item because stats limits etc. trips here.
                     self.free_equip_slot(player, item)?
                 };
 
@@ -164,7 +312,6 @@ impl World {
                 Some(false)
             }
             InventoryPlace(item, slot) => {
-                // TODO: Check curses, limits etc, as in Equip
                 let player = self.player()?;
 
                 // Checks implemented in can_command, piggyback on those.
@@ -176,7 +323,6 @@ impl World {
                 Some(false)
             }
             InventorySwap(slot1, slot2) => {
-                // TODO: Check curses, limits etc, as in Equip
                 let player = self.player()?;
 
                 // Checks implemented in can_command, piggyback on those.
@@ -206,21 +352,13 @@ impl World {
                 debug_assert!(!ability.is_targeted());
 
                 let player = self.player()?;
-                if let Some(item) = item {
-                    self.use_item_ability(player, *item, *ability)
-                } else {
-                    self.use_ability(player, *ability)
-                }
+                self.use_ability(player, *ability, None, *item)
             }
 
             TargetedAbility { ability, dir, item } => {
                 debug_assert!(ability.is_targeted());
                 let player = self.player()?;
-                if let Some(item) = item {
-                    self.use_targeted_item_ability(player, *item, *ability, *dir)
-                } else {
-                    self.use_targeted_ability(player, *ability, *dir)
-                }
+                self.use_ability(player, *ability, Some(*dir), *item)
             }
         }
     }
@@ -1,5 +1,5 @@
 use crate::{
-    grammar::{GrammarPart, Noun, Pronoun},
+    grammar::{self, GrammarPart, Noun, Pronoun},
     World,
 };
 use calx_ecs::Entity;
@@ -42,13 +42,29 @@ pub enum Icon {
 
     PlaceholderMob,
     InvisibleMob,
+    Corpse,
 
     Sword,
+    Bow,
+    Arrow,
     Helmet,
+    Shield,
     Armor,
     Wand1,
     Wand2,
+    Wand3,
+    Wand4,
+    Wand5,
     Scroll1,
+    Scroll2,
+    Scroll3,
+    Scroll4,
+    Scroll5,
+    Amulet1,
+    Amulet2,
+    Amulet3,
+    Ration,
+    Torch,
 }
 
 /// Entity name and appearance.
@@ -71,23 +87,10 @@ impl Desc {
         // .to_string() everywhere with the calls that mostly use string
         // literals.
 
-        let singular_name;
-        let plural_name;
-        if name.contains('|') {
-            let parts: Vec<&str> = name.split('|').collect();
-            if parts.len() != 2 {
-                panic!("Malformed name string '{}'", name);
-            }
-            singular_name = parts[0].to_string();
-            plural_name = Some(parts[1].to_string());
-        } else {
-            singular_name = name.to_string();
-            plural_name = None;
-        }
-
+        let (singular, plural) = grammar::split_plural(name);
         Desc {
-            singular_name,
-            plural_name,
+            singular_name: singular.to_string(),
+            plural_name: plural.map(|s| s.to_string()),
             icon,
         }
     }
@@ -108,16 +111,21 @@ impl Desc {
 
 impl World {
     /// Return visual brush for an entity.
-    pub fn entity_icon(&self, e: Entity) -> Option<Icon> { self.ecs().desc.get(e).map(|x| x.icon) }
+    pub fn entity_icon(&self, e: Entity) -> Option<Icon> { self.shown_icon(e) }
 
+    /// Return the name of an entity as the player knows it.
     pub fn entity_name(&self, e: Entity) -> String {
-        if let Some(desc) = self.ecs().desc.get(e) {
+        if let Some(desc) = self.shown_desc(e) {
             let count = self.count(e);
 
-            if count > 1 {
+            let name = if count > 1 {
                 format!("{} {}", count, desc.plural_name())
             } else {
                 desc.singular_name.clone()
+            };
+            match self.called_name(e) {
+                Some(called) => format!("{} called {}", name, called),
+                None => name,
             }
         } else {
             "N/A".to_string()
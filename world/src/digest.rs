@@ -0,0 +1,247 @@
+//! Canonical hashing of the world state for catching nondeterminism.
+
+use crate::{msg, Command, World, WorldSeed};
+use calx::{History, Incremental};
+use calx_ecs::ComponentData;
+use serde::Serialize;
+use std::hash::{Hash, Hasher};
+
+/// Hashes of the separate parts of the logical world state.
+///
+/// The digest does not depend on the iteration order of any containers in the world, and it leaves
+/// out animation state that is driven by the nondeterministic frontend clock.
+#[derive(Clone, Eq, PartialEq, Debug)]
+pub struct Digest(Vec<(&'static str, u64)>);
+
+impl Digest {
+    /// Combine the digest into a single hash value.
+    pub fn hash(&self) -> u64 {
+        let mut hasher = StableHasher::default();
+        self.0.hash(&mut hasher);
+        hasher.finish()
+    }
+
+    /// Return the names of the parts that differ between two digests.
+    pub fn diff(&self, other: &Digest) -> Vec<&'static str> {
+        self.0
+            .iter()
+            .zip(&other.0)
+            .filter(|(a, b)| a != b)
+            .map(|((name, _), _)| *name)
+            .collect()
+    }
+}
+
+/// 64-bit FNV-1a hasher.
+///
+/// Unlike `DefaultHasher`, the algorithm is fixed, so state hashes stored in replay files stay
+/// valid across Rust releases. Integers are hashed in little-endian order so that the hashes also
+/// match between platforms.
+struct StableHasher(u64);
+
+impl Default for StableHasher {
+    fn default() -> Self { StableHasher(0xcbf2_9ce4_8422_2325) }
+}
+
+impl Hasher for StableHasher {
+    fn finish(&self) -> u64 { self.0 }
+
+    fn write(&mut self, bytes: &[u8]) {
+        for &b in bytes {
+            self.0 ^= u64::from(b);
+            self.0 = self.0.wrapping_mul(0x0100_0000_01b3);
+        }
+    }
+
+    fn write_u16(&mut self, n: u16) { self.write(&n.to_le_bytes()) }
+    fn write_u32(&mut self, n: u32) { self.write(&n.to_le_bytes()) }
+    fn write_u64(&mut self, n: u64) { self.write(&n.to_le_bytes()) }
+    fn write_u128(&mut self, n: u128) { self.write(&n.to_le_bytes()) }
+    fn write_usize(&mut self, n: usize) { self.write_u64(n as u64) }
+}
+
+/// Point where two runs of the same history ended up in different states.
+#[derive(Clone, Eq, PartialEq, Debug)]
+pub struct Desync {
+    /// World tick at which the digests first differed.
+    pub tick: u64,
+    /// Parts of the world state that differed.
+    pub parts: Vec<&'static str>,
+}
+
+impl World {
+    /// Compute the canonical digest of the world state.
+    pub fn digest(&self) -> Digest {
+        let ecs = &self.ecs;
+        let mut rng = StableHasher::default();
+        serialized(&self.rng, &mut rng);
+
+        Digest(vec![
+            ("entities", unordered(ecs.iter().map(hash_one))),
+            ("abilities", component(&ecs.abilities, |a, h| a.hash(h))),
+            // Only the parts of the animation that run on the world clock.
+            (
+                "anim",
+                component(&ecs.anim, |a, h| (a.state, a.anim_done_world_tick).hash(h)),
+            ),
+            ("brain", component(&ecs.brain, serialized)),
+            ("desc", component(&ecs.desc, serialized)),
+            ("health", component(&ecs.health, serialized)),
+            ("nutrition", component(&ecs.nutrition, serialized)),
+            ("item", component(&ecs.item, serialized)),
+            ("map_memory", component(&ecs.map_memory, |m, h| m.hash(h))),
+            ("on_death", component(&ecs.on_death, serialized)),
+            ("stacking", component(&ecs.stacking, serialized)),
+            ("stats", component(&ecs.stats, serialized)),
+            ("status", component(&ecs.status, serialized)),
+            (
+                "spatial",
+                unordered(ecs.iter().map(|&e| hash_one(&(e, self.spatial.get(e))))),
+            ),
+            // Camera and anim_tick are only used by the frontend.
+            (
+                "flags",
+                hash_one(&(
+                    self.flags.tick,
+                    self.flags.player_acted,
+                    self.flags.player,
+                    self.flags.depth,
+                    &self.flags.reputation,
+                )),
+            ),
+            ("rng", rng.finish()),
+            ("terrain_overrides", hash_one(&self.terrain_overrides)),
+            ("fires", hash_one(&self.fires)),
+            (
+                "identification",
+                hash_one(&(self.identification.known(), self.identification.called())),
+            ),
+            (
+                "generated_spawns",
+                unordered(self.generated_spawns.iter().map(hash_one)),
+            ),
+        ])
+    }
+
+    /// Hash the logical game state.
+    ///
+    /// Worlds built from the same history will have the same hash.
+    pub fn state_hash(&self) -> u64 { self.digest().hash() }
+}
+
+/// Run a history twice side by side and compare the world digests after every tick.
+///
+/// Return the first point where the runs diverge, if any. Since hash containers in the two worlds
+/// get different random iteration orders, this catches world logic that depends on them.
+pub fn find_desync(history: &History<WorldSeed, Command>) -> Option<Desync> {
+    let (ret, _) = msg::capture_msgs(|| {
+        let mut a = World::from_seed(&history.seed);
+        let mut b = World::from_seed(&history.seed);
+
+        let check = |a: &World, b: &World| {
+            let parts = a.digest().diff(&b.digest());
+            if parts.is_empty() {
+                None
+            } else {
+                Some(Desync {
+                    tick: a.get_tick(),
+                    parts,
+                })
+            }
+        };
+
+        if let Some(desync) = check(&a, &b) {
+            return Some(desync);
+        }
+        for e in &history.events {
+            a.update(e);
+            b.update(e);
+            if let Some(desync) = check(&a, &b) {
+                return Some(desync);
+            }
+        }
+        None
+    });
+    ret
+}
+
+fn hash_one(value: &impl Hash) -> u64 {
+    let mut hasher = StableHasher::default();
+    value.hash(&mut hasher);
+    hasher.finish()
+}
+
+/// Combine hashes so that the result does not depend on their order.
+fn unordered(hashes: impl Iterator<Item = u64>) -> u64 { hashes.fold(0, u64::wrapping_add) }
+
+/// Hash a value through its serialization.
+///
+/// Only valid for types that serialize deterministically.
+fn serialized(value: &impl Serialize, hasher: &mut StableHasher) {
+    ron::ser::to_string(value)
+        .expect("Failed to serialize world state")
+        .hash(hasher);
+}
+
+/// Hash a component of every entity that has one.
+fn component<C>(data: &ComponentData<C>, f: impl Fn(&C, &mut StableHasher)) -> u64 {
+    unordered(data.ent_iter().map(|&e| {
+        let mut hasher = StableHasher::default();
+        e.hash(&mut hasher);
+        f(&data[e], &mut hasher);
+        hasher.finish()
+    }))
+}
+
+#[cfg(test)]
+mod test {
+    use super::{find_desync, StableHasher};
+    use crate::{Command, Simulation, WorldSeed};
+    use calx::Dir6;
+
+    #[test]
+    fn test_digest() {
+        let mut sim1 = Simulation::new(WorldSeed::test(1));
+        let mut sim2 = Simulation::new(WorldSeed::test(1));
+        assert_eq!(sim1.world().digest(), sim2.world().digest());
+
+        sim1.step(Command::Step(Dir6::South));
+        let diff = sim1.world().digest().diff(&sim2.world().digest());
+        assert!(diff.contains(&"flags"));
+
+        sim2.step(Command::Step(Dir6::South));
+        assert_eq!(sim1.world().state_hash(), sim2.world().state_hash());
+    }
+
+    #[test]
+    fn test_stable_hasher() {
+        use std::hash::Hasher;
+
+        // Reference values of the FNV-1a algorithm.
+        let fnv = |bytes: &[u8]| {
+            let mut hasher = StableHasher::default();
+            hasher.write(bytes);
+            hasher.finish()
+        };
+        assert_eq!(fnv(b""), 0xcbf2_9ce4_8422_2325);
+        assert_eq!(fnv(b"a"), 0xaf63_dc4c_8601_ec8c);
+        assert_eq!(fnv(b"foobar"), 0x8594_4171_f739_67e8);
+
+        // Integers hash the same on every platform.
+        let mut hasher = StableHasher::default();
+        hasher.write_u32(0x6261_6f66);
+        assert_eq!(hasher.finish(), fnv(b"foab"));
+    }
+
+    #[test]
+    fn test_no_desync() {
+        let mut sim = Simulation::new(WorldSeed::test(1));
+        sim.run(vec![
+            Command::Step(Dir6::North),
+            Command::Step(Dir6::Northeast),
+            Command::Step(Dir6::Southeast),
+            Command::Pass,
+        ]);
+        assert_eq!(find_desync(sim.history()), None);
+    }
+}
@@ -1,39 +1,332 @@
+use crate::{
+    loot::LootTable, stats::Intrinsic, status::Status, volume::Volume, AnimState, Location, World,
+};
 use serde_derive::{Deserialize, Serialize};
+use std::slice;
 
 /// Game system effects on entities.
 #[derive(Copy, Clone, Eq, PartialEq, Debug)]
 pub enum Effect {
     /// Deal an amount of damage of a specific type.
     Hit { amount: u32, damage: Damage },
-    /// Cause erratic behavior for a time.
-    Confuse,
+    /// Put a status on the target for a number of frames.
+    Inflict(Status, u32),
+    /// Heal an amount of wounds.
+    Heal(u32),
+    /// Make the target side with the source.
+    Charm,
+    /// Identify an unknown item the target is carrying.
+    Identify,
 }
 
-#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+/// Types of damage that entities can resist or be vulnerable to.
+#[derive(Copy, Clone, Eq, PartialEq, Debug, Serialize, Deserialize)]
 pub enum Damage {
     Physical,
     Fire,
     Electricity,
+    Poison,
+    Cold,
+    Holy,
+}
+
+impl Damage {
+    /// Return the intrinsic that makes an entity completely immune to the damage type.
+    pub fn immunity(self) -> Option<Intrinsic> {
+        match self {
+            Damage::Fire => Some(Intrinsic::FireImmunity),
+            Damage::Poison => Some(Intrinsic::PoisonImmunity),
+            _ => None,
+        }
+    }
 }
 
 /// Actions a being can do
-#[derive(Copy, Clone, Eq, PartialEq, Debug, Serialize, Deserialize)]
+#[derive(Copy, Clone, Eq, PartialEq, Hash, Debug, Serialize, Deserialize)]
 pub enum Ability {
     // --- Untargeted ---
     LightningBolt,
+    Regenerate,
+    Invisibility,
+    Heal,
+    Identify,
+    Smite,
     // MagicMap
 
     // --- Targeted ---
     Fireball,
     Confuse,
+    Charm,
+    PoisonSpit,
+    FrostBolt,
+}
+
+impl Ability {
+    pub fn iter() -> slice::Iter<'static, Ability> {
+        use Ability::*;
+        const ABILITIES: [Ability; 11] = [
+            LightningBolt,
+            Regenerate,
+            Invisibility,
+            Heal,
+            Identify,
+            Smite,
+            Fireball,
+            Confuse,
+            Charm,
+            PoisonSpit,
+            FrostBolt,
+        ];
+        ABILITIES.iter()
+    }
+}
+
+/// Innate abilities of an entity.
+pub type Abilities = Vec<Ability>;
+
+/// Something that happens when a creature dies.
+#[derive(Clone, Eq, PartialEq, Debug, Serialize, Deserialize)]
+pub enum DeathTrigger {
+    /// Drop everything the creature was carrying.
+    DropItems,
+    /// Leave a corpse on the floor.
+    Corpse,
+    /// Blow up and hurt everything nearby.
+    Explode {
+        radius: u32,
+        amount: u32,
+        damage: Damage,
+    },
+    /// Spawn new creatures in the place of the dead one.
+    Spawn { name: String, count: u32 },
+    /// Drop random items.
+    Loot(LootTable),
 }
 
+/// On-death triggers of an entity.
+pub type DeathTriggers = Vec<DeathTrigger>;
+
 impl Ability {
-    pub fn is_targeted(self) -> bool {
+    pub fn is_targeted(self) -> bool { self.data().targeting == Targeting::Projectile }
+
+    pub fn name(self) -> &'static str { self.data().name }
+
+    pub fn data(self) -> AbilityData {
         use Ability::*;
         match self {
-            LightningBolt => false,
-            _ => true,
+            LightningBolt => AbilityData {
+                name: "lightning bolt",
+                range: 4,
+                targeting: Targeting::RandomMob,
+                effects: &[
+                    Effect::Hit {
+                        amount: 12,
+                        damage: Damage::Electricity,
+                    },
+                    Effect::Inflict(Status::Stunned, 12),
+                ],
+                use_msg: Some("There is a peal of thunder."),
+                noise: 12,
+                cost: Cost {
+                    charges: 1,
+                    mana: 6,
+                },
+                ..Default::default()
+            },
+            Smite => AbilityData {
+                name: "smite",
+                range: 4,
+                targeting: Targeting::RandomMob,
+                effects: &[Effect::Hit {
+                    amount: 10,
+                    damage: Damage::Holy,
+                }],
+                use_msg: Some("A blinding light falls from above."),
+                cost: Cost {
+                    charges: 1,
+                    mana: 6,
+                },
+                ..Default::default()
+            },
+            Heal => AbilityData {
+                name: "heal",
+                effects: &[Effect::Heal(10)],
+                cost: Cost {
+                    charges: 1,
+                    mana: 4,
+                },
+                ..Default::default()
+            },
+            Identify => AbilityData {
+                name: "identify",
+                effects: &[Effect::Identify],
+                ..Default::default()
+            },
+            Regenerate => AbilityData {
+                name: "regenerate",
+                effects: &[Effect::Inflict(Status::Regenerating, 240)],
+                cost: Cost {
+                    charges: 1,
+                    mana: 4,
+                },
+                ..Default::default()
+            },
+            Invisibility => AbilityData {
+                name: "invisibility",
+                effects: &[Effect::Inflict(Status::Invisible, 360)],
+                cost: Cost {
+                    charges: 1,
+                    mana: 5,
+                },
+                ..Default::default()
+            },
+            Fireball => AbilityData {
+                name: "fireball",
+                range: 9,
+                targeting: Targeting::Projectile,
+                shape: Shape::Sphere(1),
+                effects: &[
+                    Effect::Hit {
+                        amount: 6,
+                        damage: Damage::Fire,
+                    },
+                    Effect::Inflict(Status::Burning, 36),
+                ],
+                missile_anim: Some(AnimState::Firespell),
+                impact_anim: Some(AnimState::Explosion),
+                noise: 10,
+                cost: Cost {
+                    charges: 1,
+                    mana: 6,
+                },
+                ..Default::default()
+            },
+            Confuse => AbilityData {
+                name: "confuse",
+                range: 9,
+                targeting: Targeting::Projectile,
+                effects: &[Effect::Inflict(Status::Confused, 40)],
+                cost: Cost {
+                    charges: 1,
+                    mana: 3,
+                },
+                ..Default::default()
+            },
+            Charm => AbilityData {
+                name: "charm",
+                range: 6,
+                targeting: Targeting::Projectile,
+                effects: &[Effect::Charm],
+                cost: Cost {
+                    charges: 1,
+                    mana: 5,
+                },
+                ..Default::default()
+            },
+            PoisonSpit => AbilityData {
+                name: "poison spit",
+                range: 3,
+                targeting: Targeting::Projectile,
+                effects: &[Effect::Inflict(Status::Poisoned, 60)],
+                cost: Cost {
+                    charges: 1,
+                    mana: 2,
+                },
+                ..Default::default()
+            },
+            FrostBolt => AbilityData {
+                name: "frost bolt",
+                range: 9,
+                targeting: Targeting::Projectile,
+                effects: &[
+                    Effect::Hit {
+                        amount: 8,
+                        damage: Damage::Cold,
+                    },
+                    Effect::Inflict(Status::Slowed, 40),
+                ],
+                cost: Cost {
+                    charges: 1,
+                    mana: 5,
+                },
+                ..Default::default()
+            },
+        }
+    }
+}
+
+/// How an ability picks the location it hits.
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+pub enum Targeting {
+    /// Hit the location of the user.
+    User,
+    /// Hit a random other mob within range of the user.
+    RandomMob,
+    /// Fly in the target direction until hitting a mob or a wall or running out of range.
+    Projectile,
+}
+
+/// Area around the hit location that an ability affects.
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+pub enum Shape {
+    Point,
+    Sphere(u32),
+}
+
+impl Shape {
+    pub(crate) fn volume(self, w: &World, center: Location) -> Volume {
+        match self {
+            Shape::Point => Volume::point(center),
+            Shape::Sphere(radius) => Volume::sphere(w, center, radius),
+        }
+    }
+}
+
+/// What using an ability uses up.
+#[derive(Copy, Clone, Eq, PartialEq, Debug, Default)]
+pub struct Cost {
+    /// Charges drained from the item the ability is used through.
+    pub charges: u32,
+    /// Mana spent when the ability is cast without an item.
+    pub mana: i32,
+}
+
+/// Static description of what an ability does.
+#[derive(Copy, Clone, Debug)]
+pub struct AbilityData {
+    /// Name shown in the spellbook.
+    pub name: &'static str,
+    pub range: u32,
+    pub targeting: Targeting,
+    pub shape: Shape,
+    pub effects: &'static [Effect],
+    /// Animation for a missile that flies from the user to the hit location.
+    pub missile_anim: Option<AnimState>,
+    /// Animation shown on every location in the affected area.
+    pub impact_anim: Option<AnimState>,
+    /// Message shown when the ability is used.
+    pub use_msg: Option<&'static str>,
+    /// Loudness of the noise made where the ability hits.
+    pub noise: u32,
+    pub cost: Cost,
+}
+
+impl Default for AbilityData {
+    fn default() -> Self {
+        AbilityData {
+            name: "",
+            range: 0,
+            targeting: Targeting::User,
+            shape: Shape::Point,
+            effects: &[],
+            missile_anim: None,
+            impact_anim: None,
+            use_msg: None,
+            noise: 0,
+            cost: Cost {
+                charges: 1,
+                mana: 0,
+            },
         }
     }
 }
@@ -0,0 +1,237 @@
+//! Burning terrain
+
+use crate::{msg, sector::SECTOR_WIDTH, status::Status, AnimState, Location, Terrain, World};
+use calx::{seeded_rng, Dir6, RngExt};
+use calx_ecs::Entity;
+
+/// How many ticks pass between fires spreading and smoking.
+const FIRE_PERIOD: u64 = 12;
+
+/// How far from the player fires keep burning.
+///
+/// Fires further away are frozen until the player comes back.
+const FIRE_RANGE: i32 = SECTOR_WIDTH;
+
+/// How long mobs caught in a fire keep burning.
+const CATCH_FIRE_DURATION: u32 = 24;
+
+/// Return how many ticks the terrain burns for, or `None` if it doesn't burn.
+fn burn_time(terrain: Terrain) -> Option<u32> {
+    match terrain {
+        Terrain::Grass | Terrain::Grass2 => Some(36),
+        Terrain::Tree | Terrain::DeadTree => Some(120),
+        _ => None,
+    }
+}
+
+impl World {
+    /// Return whether the terrain at location is on fire.
+    pub fn is_burning(&self, loc: Location) -> bool { self.fires.contains_key(&loc) }
+
+    /// Return whether location is next to water and too damp to burn.
+    fn is_damp(&self, loc: Location) -> bool {
+        Dir6::iter().any(|&dir| self.terrain(loc.jump(self, dir)).is_water())
+    }
+
+    /// Return whether fire can start at location.
+    pub(crate) fn can_ignite(&self, loc: Location) -> bool {
+        burn_time(self.terrain(loc)).is_some() && !self.is_burning(loc) && !self.is_damp(loc)
+    }
+
+    /// Set the terrain at location on fire.
+    pub(crate) fn ignite(&mut self, loc: Location) {
+        if self.start_fire(loc) {
+            // Smoke blocks sight.
+            self.refresh_fov();
+        }
+    }
+
+    /// Set the terrain at location on fire without updating fields of view.
+    ///
+    /// Return whether a fire was started.
+    fn start_fire(&mut self, loc: Location) -> bool {
+        if !self.can_ignite(loc) {
+            return false;
+        }
+        let terrain = self.terrain(loc);
+        if self.player_sees(loc) {
+            msg!("The {} catches fire.", terrain.name());
+        }
+        self.fires.insert(loc, burn_time(terrain).unwrap());
+        self.spawn_fx(loc, AnimState::Smoke);
+        self.forget_light();
+        true
+    }
+
+    /// Run the fires near the player.
+    ///
+    /// Burning cells spread fire to flammable neighbors, set mobs in them on fire and finally
+    /// burn down to bare ground. Fires that water has ended up in or next to go out.
+    pub(crate) fn tick_fire(&mut self) {
+        if self.fires.is_empty() {
+            return;
+        }
+        let origin = match self.player().and_then(|p| self.location(p)) {
+            Some(loc) => loc,
+            None => return,
+        };
+        let active: Vec<Location> = self
+            .fires
+            .keys()
+            .cloned()
+            .filter(|&loc| origin.distance_from(loc).is_some_and(|d| d <= FIRE_RANGE))
+            .collect();
+
+        let spread = self.get_tick().is_multiple_of(FIRE_PERIOD);
+        let mut burned_out = false;
+        let mut put_out = false;
+        let mut started = false;
+        for loc in active {
+            if self.is_damp(loc) || burn_time(self.terrain(loc)).is_none() {
+                self.fires.remove(&loc);
+                put_out = true;
+                continue;
+            }
+            let left = self.fires[&loc] - 1;
+            if left == 0 {
+                self.fires.remove(&loc);
+                self.override_terrain(loc, Terrain::Ground);
+                burned_out = true;
+                continue;
+            }
+            self.fires.insert(loc, left);
+
+            if !spread {
+                continue;
+            }
+            self.spawn_fx(loc, AnimState::Smoke);
+            if let Some(mob) = self.mob_at(loc) {
+                if !self.has_status(mob, Status::Burning) {
+                    self.gain_status(mob, Status::Burning, CATCH_FIRE_DURATION);
+                }
+            }
+            for &dir in Dir6::iter() {
+                let next = loc.jump(self, dir);
+                // Seed with the cell and the time so the spread doesn't depend on the order the
+                // fires are processed in.
+                let mut rng = seeded_rng(&(self.rng_seed(), next, self.get_tick()));
+                if self.can_ignite(next) && rng.one_chance_in(2) {
+                    started |= self.start_fire(next);
+                }
+            }
+        }
+
+        // Update caches once for all the changes.
+        if burned_out {
+            self.forget_flow_fields();
+        }
+        if burned_out || put_out {
+            self.forget_light();
+        }
+        if burned_out || put_out || started {
+            self.refresh_fov();
+        }
+    }
+
+    /// Put out a burning mob that is standing in water.
+    pub(crate) fn douse(&mut self, e: Entity) {
+        if !self.has_status(e, Status::Burning) {
+            return;
+        }
+        if let Some(loc) = self.location(e) {
+            if self.terrain(loc).is_water() {
+                self.cure_status(e, Status::Burning);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{status::Status, ExternalEntity, Location, Terrain, World, WorldSeed};
+    use euclid::vec2;
+
+    /// Cover the ground around the player with grass and return the cells.
+    ///
+    /// The first cell is right next to the player's neighbors, the second one is next to it.
+    fn meadow(world: &mut World) -> Vec<Location> {
+        let origin = world.location(world.player().unwrap()).unwrap();
+        let mut cells = Vec::new();
+        for y in -4..=4 {
+            for x in -4..=4 {
+                let loc = origin + vec2(x, y);
+                if (2..=4).contains(&origin.distance_from(loc).unwrap()) {
+                    cells.push(loc);
+                }
+            }
+        }
+        cells.sort_by_key(|&loc| origin.distance_from(loc));
+        let next = cells[1..]
+            .iter()
+            .position(|&loc| cells[0].distance_from(loc) == Some(1))
+            .unwrap();
+        cells.swap(1, next + 1);
+        for &loc in &cells {
+            world.override_terrain(loc, Terrain::Grass);
+        }
+        cells
+    }
+
+    #[test]
+    fn test_fire_spread() {
+        let run = || {
+            let mut world = World::new(&WorldSeed::test(1));
+            let cells = meadow(&mut world);
+            world.ignite(cells[0]);
+            assert!(world.is_burning(cells[0]));
+            for _ in 0..200 {
+                world.tick_fire();
+                world.flags.tick += 1;
+            }
+            let burned: Vec<bool> =
+                cells.iter().map(|&loc| world.terrain(loc) == Terrain::Ground).collect();
+            (burned, world.state_hash())
+        };
+        let (burned, hash) = run();
+        // The lit cell burns down and spreads to its neighbors.
+        assert!(burned[0]);
+        assert!(burned.iter().filter(|&&b| b).count() > 1);
+        // Fires run the same way every time.
+        assert_eq!(run(), (burned, hash));
+    }
+
+    #[test]
+    fn test_fire_and_water() {
+        let mut world = World::new(&WorldSeed::test(1));
+        let cells = meadow(&mut world);
+
+        // Grass next to water doesn't burn.
+        assert!(world.can_ignite(cells[1]));
+        world.set_terrain(cells[1], Terrain::Shallows);
+        assert!(!world.can_ignite(cells[0]));
+        world.set_terrain(cells[1], Terrain::Grass);
+
+        // Mobs in a burning cell catch fire and water puts them out.
+        let rat = world.spawn(&ExternalEntity::from_name("rat").unwrap(), cells[0]);
+        world.ignite(cells[0]);
+        while world.is_burning(cells[0]) && !world.has_status(rat, Status::Burning) {
+            world.tick_fire();
+            world.flags.tick += 1;
+        }
+        assert!(world.has_status(rat, Status::Burning));
+        world.set_terrain(cells[0], Terrain::Shallows);
+        world.douse(rat);
+        assert!(!world.has_status(rat, Status::Burning));
+
+        // Fires go out when water ends up in or next to them.
+        world.tick_fire();
+        assert!(!world.is_burning(cells[0]));
+        world.set_terrain(cells[0], Terrain::Grass);
+        world.ignite(cells[1]);
+        assert!(world.is_burning(cells[1]));
+        world.set_terrain(cells[0], Terrain::Shallows);
+        world.tick_fire();
+        assert!(!world.is_burning(cells[1]));
+        assert_eq!(world.terrain(cells[1]), Terrain::Grass);
+    }
+}
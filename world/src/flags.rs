@@ -1,6 +1,7 @@
-use crate::location::Location;
+use crate::{ai::Alignment, location::Location};
 use calx_ecs::Entity;
 use serde_derive::{Deserialize, Serialize};
+use std::collections::BTreeMap;
 
 #[derive(Serialize, Deserialize, Default)]
 pub struct Flags {
@@ -11,4 +12,7 @@ pub struct Flags {
     /// Store the player entity here for fast access.
     pub player: Option<Entity>,
     pub depth: i32,
+    /// Player's standing with the factions of the world.
+    #[serde(default)]
+    pub reputation: BTreeMap<Alignment, i32>,
 }
@@ -0,0 +1,258 @@
+//! Cached distance maps for AI movement.
+
+use crate::{Location, World};
+use calx::{Dijkstra, Dir6};
+use calx_ecs::Entity;
+use std::cmp::Reverse;
+use std::collections::{BinaryHeap, HashMap};
+
+/// How many steps away from its target a flow field reaches.
+const FLOW_FIELD_RANGE: u32 = 32;
+
+/// Multiplier for turning walking distances into a flee field.
+///
+/// Anything past -1 makes a distant escape route worth a few steps back towards the target, so
+/// fleeing mobs run for open space instead of backing into the nearest dead end.
+const FLEE_FACTOR: f32 = -1.2;
+
+/// Walking distances to the location of a target entity.
+struct FlowField {
+    /// Where the target was when the field was built.
+    origin: Location,
+    map: Dijkstra<Location>,
+    /// Inverted field for running away from the target, built when first needed.
+    flee: Option<HashMap<Location, i32>>,
+}
+
+/// Flow fields for all entities that are being pathed towards or away from.
+///
+/// This is a pure cache, it can be dropped at any time without changing the game state. A field
+/// needs to be rebuilt when its target moves, and all fields are dropped when terrain changes.
+#[derive(Default)]
+pub(crate) struct FlowFieldCache {
+    fields: HashMap<Entity, FlowField>,
+}
+
+impl World {
+    /// Return the walking distance from location to target entity.
+    ///
+    /// Return `None` if the location is too far from the target or the target can't be reached
+    /// from it. The distance follows portals and stairs and ignores other mobs.
+    pub fn walk_distance(&self, target: Entity, loc: Location) -> Option<u32> {
+        self.update_flow_field(target)?;
+        self.flow_fields.borrow().fields[&target].map.weights.get(&loc).cloned()
+    }
+
+    /// Return the value of the flee field of the target entity at location.
+    ///
+    /// Running away from the target means moving towards lower values.
+    fn flee_value(&self, target: Entity, loc: Location) -> Option<i32> {
+        self.update_flow_field(target)?;
+        let mut cache = self.flow_fields.borrow_mut();
+        let field = cache.fields.get_mut(&target)?;
+        if field.flee.is_none() {
+            field.flee = Some(self.flee_field(&field.map));
+        }
+        field.flee.as_ref().and_then(|flee| flee.get(&loc).cloned())
+    }
+
+    /// Rebuild the flow field of the target if the target has moved.
+    ///
+    /// Return `None` if the target has no location.
+    fn update_flow_field(&self, target: Entity) -> Option<()> {
+        let origin = self.location(target)?;
+        let mut cache = self.flow_fields.borrow_mut();
+
+        if cache.fields.get(&target).map(|field| field.origin) != Some(origin) {
+            // Target has moved or doesn't have a field yet.
+            let map = Dijkstra::from_fn(
+                vec![origin],
+                |&loc: &Location| self.walk_neighbors(loc),
+                FLOW_FIELD_RANGE,
+            );
+            cache.fields.insert(target, FlowField { origin, map, flee: None });
+        }
+        Some(())
+    }
+
+    /// Return the walkable cells one step away from location, following portals.
+    fn walk_neighbors(&self, loc: Location) -> Vec<Location> {
+        Dir6::iter()
+            .map(|&dir| loc.jump(self, dir))
+            .filter(|&loc| !self.terrain_blocks_walk(loc))
+            .collect()
+    }
+
+    /// Build a flee field from the walking distances of a flow field.
+    ///
+    /// The distances are scaled by `FLEE_FACTOR` and the result is rescanned so that no cell is
+    /// more than one step above its lowest neighbor.
+    fn flee_field(&self, map: &Dijkstra<Location>) -> HashMap<Location, i32> {
+        let mut ret: HashMap<Location, i32> = map
+            .weights
+            .iter()
+            .map(|(&loc, &dist)| (loc, (dist as f32 * FLEE_FACTOR) as i32))
+            .collect();
+
+        let mut open: BinaryHeap<_> = ret.iter().map(|(&loc, &v)| Reverse((v, loc))).collect();
+        while let Some(Reverse((v, loc))) = open.pop() {
+            if ret[&loc] < v {
+                // Already lowered since this was queued.
+                continue;
+            }
+            for next in self.walk_neighbors(loc) {
+                match ret.get_mut(&next) {
+                    Some(w) if *w > v + 1 => {
+                        *w = v + 1;
+                        open.push(Reverse((v + 1, next)));
+                    }
+                    _ => {}
+                }
+            }
+        }
+        ret
+    }
+
+    /// Drop the flow field for an entity that no longer exists.
+    pub(crate) fn forget_flow_field(&self, target: Entity) {
+        self.flow_fields.borrow_mut().fields.remove(&target);
+    }
+
+    /// Drop all flow fields after the terrain they were built on has changed.
+    pub(crate) fn forget_flow_fields(&self) { self.flow_fields.borrow_mut().fields.clear(); }
+
+    /// Return the direction the entity can step in to move towards the target along its flow
+    /// field or away from it along its flee field.
+    ///
+    /// Only returns directions that go downhill on the field.
+    pub(crate) fn flow_dir(&self, e: Entity, target: Entity, towards: bool) -> Option<Dir6> {
+        let value = |loc| {
+            if towards {
+                self.walk_distance(target, loc).map(|dist| dist as i32)
+            } else {
+                self.flee_value(target, loc)
+            }
+        };
+        let loc = self.location(e)?;
+        let current = value(loc)?;
+
+        let mut best: Option<(i32, Dir6)> = None;
+        for &dir in Dir6::iter() {
+            let next = loc.jump(self, dir);
+            let v = match value(next) {
+                Some(v) => v,
+                None => continue,
+            };
+            if !self.can_enter(e, next) {
+                continue;
+            }
+            if v < best.map_or(current, |(v, _)| v) {
+                best = Some((v, dir));
+            }
+        }
+        best.map(|(_, dir)| dir)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{sector::Sector, ExternalEntity, Location, Terrain, World, WorldSeed};
+    use calx_ecs::Entity;
+    use euclid::vec2;
+
+    /// Return a world with the player at the origin of a patch of terrain made with `terrain`.
+    fn arena(terrain: impl Fn(i32, i32) -> Terrain) -> (World, Location) {
+        let mut world = World::new(&WorldSeed::test(1));
+        let origin = world.location(world.player().unwrap()).unwrap();
+        for y in -6..18 {
+            for x in -8..11 {
+                world.set_terrain(origin + vec2(x, y), terrain(x, y));
+            }
+        }
+        (world, origin)
+    }
+
+    /// Move the mob along the flow field of the player until it stops and return where it ends up.
+    fn follow(world: &mut World, mob: Entity, towards: bool) -> Location {
+        let player = world.player().unwrap();
+        for _ in 0..40 {
+            match world.flow_dir(mob, player, towards) {
+                Some(dir) => {
+                    world.entity_step(mob, dir);
+                }
+                None => break,
+            }
+        }
+        world.location(mob).unwrap()
+    }
+
+    #[test]
+    fn test_chase_out_of_u() {
+        // A cup between the mob and the player that opens away from the player.
+        let (mut world, origin) = arena(|x, y| {
+            let base = y == 2 && (-3..=5).contains(&x);
+            let arm = (x == -3 || x == 5) && (2..=6).contains(&y);
+            if base || arm {
+                Terrain::Wall
+            } else {
+                Terrain::Ground
+            }
+        });
+        let player = world.player().unwrap();
+        let mob = world.spawn(&ExternalEntity::from_name("dreg").unwrap(), origin + vec2(1, 5));
+
+        let end = follow(&mut world, mob, true);
+        assert_eq!(world.walk_distance(player, end), Some(1));
+    }
+
+    #[test]
+    fn test_flee_past_dead_end() {
+        // A corridor from the player to a junction with a short dead end and a long escape route.
+        let (mut world, origin) = arena(|x, y| {
+            let corridor = y == 0 && (0..=3).contains(&x);
+            let branch = x == 3 && (-3..=14).contains(&y);
+            if corridor || branch {
+                Terrain::Ground
+            } else {
+                Terrain::Wall
+            }
+        });
+        let player = world.player().unwrap();
+        let mob = world.spawn(&ExternalEntity::from_name("dreg").unwrap(), origin + vec2(3, 0));
+
+        // Walking distance alone would lead straight into the dead end.
+        assert_eq!(world.walk_distance(player, origin + vec2(3, -3)), Some(6));
+        assert_eq!(world.walk_distance(player, origin + vec2(3, 1)), Some(3));
+
+        let end = follow(&mut world, mob, false);
+        assert_eq!(end, origin + vec2(3, 14));
+        assert_eq!(world.walk_distance(player, end), Some(16));
+    }
+
+    #[test]
+    fn test_flow_through_stairs() {
+        let mut world = World::new(&WorldSeed::test(1));
+        let stairs = Sector::new(0, 0, 0)
+            .iter()
+            .find(|&loc| world.portal(loc).is_some())
+            .unwrap();
+        let below = world.portal(stairs).unwrap();
+        assert_ne!(stairs.z, below.z);
+
+        // The stairs lead back up next to where they were entered from.
+        let start = stairs - vec2(1, 1);
+        assert!(!world.blocks_walk(start));
+        let goal = calx::Dir6::iter()
+            .map(|&dir| below + dir.to_v2())
+            .find(|&loc| world.portal(loc).is_none() && !world.blocks_walk(loc))
+            .unwrap();
+        let target = world.spawn(&ExternalEntity::from_name("dreg").unwrap(), goal);
+        let mob = world.spawn(&ExternalEntity::from_name("dreg").unwrap(), start);
+
+        assert_eq!(world.walk_distance(target, below), Some(1));
+        assert_eq!(world.walk_distance(target, start), Some(2));
+        let dir = world.flow_dir(mob, target, true).unwrap();
+        world.entity_step(mob, dir);
+        assert_eq!(world.location(mob), Some(below));
+    }
+}
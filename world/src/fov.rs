@@ -46,7 +46,9 @@ impl<'a> FovValue for SightFov<'a> {
             ret.origin = dest - offset;
         }
 
-        if self.w.terrain(ret.origin + offset).blocks_sight() {
+        // Smoke from fires blocks sight like walls do.
+        let loc = ret.origin + offset;
+        if self.w.terrain(loc).blocks_sight() || self.w.is_burning(loc) {
             ret.is_edge = true;
         }
 
@@ -102,3 +104,43 @@ impl<'a> FovValue for SphereVolumeFov<'a> {
         Some(ret)
     }
 }
+
+#[derive(Clone)]
+/// Field that covers every cell within range, used to find things that may affect the visible
+/// area from out of sight.
+pub struct AreaFov<'a> {
+    w: &'a World,
+    range: u32,
+    pub origin: Location,
+}
+
+impl<'a> AreaFov<'a> {
+    pub fn new(w: &'a World, range: u32, origin: Location) -> AreaFov<'a> {
+        AreaFov { w, range, origin }
+    }
+}
+
+impl<'a> PartialEq for AreaFov<'a> {
+    fn eq(&self, other: &Self) -> bool {
+        std::ptr::eq(self.w, other.w)
+            && self.range == other.range
+            && self.origin == other.origin
+    }
+}
+
+impl<'a> Eq for AreaFov<'a> {}
+
+impl<'a> FovValue for AreaFov<'a> {
+    fn advance(&self, offset: CellVector) -> Option<Self> {
+        if offset.hex_dist() as u32 > self.range {
+            return None;
+        }
+
+        // Follow the same portals as sight does so that the area matches the player's chart.
+        let mut ret = self.clone();
+        if let Some(dest) = self.w.visible_portal(self.origin + offset) {
+            ret.origin = dest - offset;
+        }
+        Some(ret)
+    }
+}
@@ -272,6 +272,19 @@ impl Templater for ObjectTemplater {
     }
 }
 
+/// Split a name given as "goose|geese" into the singular and the irregular plural.
+///
+/// Names without a bar have no irregular plural.
+pub fn split_plural(name: &str) -> (&str, Option<&str>) {
+    let mut parts = name.split('|');
+    let singular = parts.next().unwrap_or("");
+    let plural = parts.next();
+    if parts.next().is_some() {
+        panic!("Malformed name string '{}'", name);
+    }
+    (singular, plural)
+}
+
 pub fn is_capitalized(word: &str) -> bool {
     word.chars()
         .next()
@@ -0,0 +1,257 @@
+//! Hunger and eating
+
+use crate::{
+    ai::Action,
+    grammar::{GrammarPart, Noun},
+    item::ItemType,
+    modifier::{Modifier, ModifierOp, Stat},
+    msg,
+    status::Status,
+    ActionOutcome, World,
+};
+use calx::RngExt;
+use calx_ecs::Entity;
+use serde_derive::{Deserialize, Serialize};
+
+/// How much nutrition fits in a stomach.
+///
+/// One unit of nutrition is burned every frame.
+pub const MAX_NUTRITION: i32 = 12000;
+
+/// Nutrition spent on healing wounds when resting.
+const REST_COST: i32 = 10;
+
+/// How often a fainting creature may pass out.
+const FAINT_INTERVAL: u64 = 120;
+
+/// How hungry a creature is.
+#[derive(Copy, Clone, Eq, PartialEq, Ord, PartialOrd, Debug)]
+pub enum Hunger {
+    Satiated,
+    NotHungry,
+    Hungry,
+    Weak,
+    Fainting,
+}
+
+impl Hunger {
+    fn from_nutrition(nutrition: i32) -> Hunger {
+        match nutrition {
+            n if n >= 10000 => Hunger::Satiated,
+            n if n > 3000 => Hunger::NotHungry,
+            n if n > 1000 => Hunger::Hungry,
+            n if n > 0 => Hunger::Weak,
+            _ => Hunger::Fainting,
+        }
+    }
+
+    /// Name shown in the status bar.
+    pub fn name(self) -> &'static str {
+        match self {
+            Hunger::Satiated => "satiated",
+            Hunger::NotHungry => "not hungry",
+            Hunger::Hungry => "hungry",
+            Hunger::Weak => "weak",
+            Hunger::Fainting => "fainting",
+        }
+    }
+
+    /// Message shown when a creature gets hungrier.
+    fn message(self) -> Option<&'static str> {
+        match self {
+            Hunger::Hungry => Some("[One] [is] getting hungry."),
+            Hunger::Weak => Some("[One] [is] weak with hunger."),
+            Hunger::Fainting => Some("[One] [is] fainting from hunger."),
+            _ => None,
+        }
+    }
+
+    /// Return the stat modifiers of the hunger state.
+    pub fn modifiers(self) -> &'static [Modifier] {
+        use {ModifierOp::*, Stat::*};
+        match self {
+            Hunger::Weak => &[
+                Modifier {
+                    stat: Power,
+                    op: Add(-2),
+                },
+                Modifier {
+                    stat: Attack,
+                    op: Add(-2),
+                },
+            ],
+            Hunger::Fainting => &[
+                Modifier {
+                    stat: Power,
+                    op: Add(-4),
+                },
+                Modifier {
+                    stat: Attack,
+                    op: Add(-4),
+                },
+                Modifier {
+                    stat: Defense,
+                    op: Percent(50),
+                },
+            ],
+            _ => &[],
+        }
+    }
+}
+
+/// Stomach of a creature that needs to eat.
+///
+/// Only given to the player when the world is generated with hunger enabled.
+#[derive(Copy, Clone, Eq, PartialEq, Debug, Serialize, Deserialize)]
+pub struct Nutrition {
+    pub amount: i32,
+}
+
+impl Default for Nutrition {
+    fn default() -> Self {
+        Nutrition {
+            amount: MAX_NUTRITION * 3 / 4,
+        }
+    }
+}
+
+impl World {
+    /// Return how hungry a creature is, or `None` if it doesn't need to eat.
+    pub fn hunger(&self, e: Entity) -> Option<Hunger> {
+        self.ecs()
+            .nutrition
+            .get(e)
+            .map(|n| Hunger::from_nutrition(n.amount))
+    }
+
+    /// Return the nutrition value of a food item.
+    pub fn food_value(&self, item: Entity) -> Option<i32> {
+        match self.item_type(item)? {
+            ItemType::Food(amount) => Some(amount),
+            _ => None,
+        }
+    }
+
+    /// Change the amount of nutrition a creature has and report a change in how hungry it is.
+    fn change_nutrition(&mut self, e: Entity, amount: i32) {
+        let old = match self.hunger(e) {
+            Some(hunger) => hunger,
+            None => return,
+        };
+        if let Some(n) = self.ecs_mut().nutrition.get_mut(e) {
+            n.amount = (n.amount + amount).clamp(0, MAX_NUTRITION);
+        }
+        let new = self.hunger(e).unwrap_or(old);
+        if new != old {
+            self.rebuild_stats(e);
+            if new > old && self.is_player(e) {
+                if let Some(text) = new.message() {
+                    msg!(text; self.subject(e));
+                }
+            }
+        }
+    }
+
+    /// Burn nutrition as time passes.
+    pub(crate) fn tick_hunger(&mut self, e: Entity) {
+        if self.hunger(e).is_none() {
+            return;
+        }
+        self.change_nutrition(e, -1);
+
+        if self.hunger(e) == Some(Hunger::Fainting)
+            && self.get_tick().is_multiple_of(FAINT_INTERVAL)
+            && self.rng().one_chance_in(3)
+        {
+            if self.is_player(e) {
+                msg!("[One] faint[s] from hunger."; self.subject(e));
+            }
+            self.gain_status(e, Status::Stunned, 24);
+        }
+    }
+
+    /// Consume the nutrition needed to heal when resting.
+    ///
+    /// Return false if the entity is too hungry to heal.
+    pub(crate) fn consume_nutrition(&mut self, e: Entity) -> bool {
+        match self.hunger(e) {
+            None => true,
+            Some(hunger) if hunger >= Hunger::Weak => false,
+            Some(_) => {
+                self.change_nutrition(e, -REST_COST);
+                true
+            }
+        }
+    }
+
+    /// Eat a food item.
+    pub(crate) fn eat(&mut self, e: Entity, item: Entity) -> ActionOutcome {
+        let amount = self.food_value(item)?;
+        if self.hunger(e)? == Hunger::Satiated {
+            if self.is_player(e) {
+                msg!("You're too full to eat.");
+            }
+            return None;
+        }
+
+        if self.is_player(e) {
+            // Only name a single item even if eating from a stack.
+            let food = Noun::new(self.shown_desc(item)?.singular_name.clone());
+            msg!("[One] eat[s] [a thing]."; self.subject(e), GrammarPart::Object(food));
+        }
+        self.kill_entity(item);
+        self.change_nutrition(e, amount);
+        self.end_turn(e, Action::Eat);
+        Some(true)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{Hunger, MAX_NUTRITION};
+    use crate::{item::Slot, Command, ExternalEntity, Stat, World, WorldSeed};
+
+    fn world(hunger: bool) -> World {
+        World::new(&WorldSeed { hunger, ..WorldSeed::test(1) })
+    }
+
+    #[test]
+    fn test_no_hunger() {
+        let mut world = world(false);
+        let player = world.player().unwrap();
+        assert_eq!(world.hunger(player), None);
+        assert!(world.consume_nutrition(player));
+    }
+
+    #[test]
+    fn test_hunger() {
+        let mut world = world(true);
+        let player = world.player().unwrap();
+        let attack = world.stat_breakdown(player, Stat::Attack).total;
+        assert_eq!(world.hunger(player), Some(Hunger::NotHungry));
+
+        world.ecs_mut().nutrition[player].amount = 2;
+        world.tick_hunger(player);
+        assert_eq!(world.hunger(player), Some(Hunger::Weak));
+        assert_eq!(world.stat_breakdown(player, Stat::Attack).total, attack - 2);
+        // Too weak to heal when resting.
+        assert!(!world.consume_nutrition(player));
+        world.tick_hunger(player);
+        assert_eq!(world.hunger(player), Some(Hunger::Fainting));
+        assert_eq!(world.stat_breakdown(player, Stat::Attack).total, attack - 4);
+
+        let loc = world.location(player).unwrap();
+        let ration = world.spawn(&ExternalEntity::from_name("ration").unwrap(), loc);
+        world.entity_take(player, ration);
+        let slot = world.entity_slot(ration).unwrap();
+        assert!(world.check_command(&Command::Eat(slot)).is_ok());
+        assert_eq!(world.eat(player, ration), Some(true));
+        assert_eq!(world.hunger(player), Some(Hunger::NotHungry));
+        assert_eq!(world.stat_breakdown(player, Stat::Attack).total, attack);
+        assert!(!world.is_alive(ration));
+        assert!(world.check_command(&Command::Eat(Slot::Bag(0))).is_err());
+
+        world.ecs_mut().nutrition[player].amount = MAX_NUTRITION;
+        assert_eq!(world.hunger(player), Some(Hunger::Satiated));
+    }
+}
@@ -0,0 +1,276 @@
+//! Unidentified item appearances and what the player knows about them.
+
+use crate::{
+    desc::{Desc, Icon},
+    grammar, msg, spec, ItemType, World,
+};
+use calx::seeded_rng;
+use calx_ecs::Entity;
+use rand::seq::SliceRandom;
+use serde_derive::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet};
+
+/// Kinds of items that look the same until identified.
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+enum Kind {
+    Wand,
+    Scroll,
+    Amulet,
+}
+
+impl Kind {
+    fn of(item_type: ItemType) -> Option<Kind> {
+        match item_type {
+            ItemType::TargetedUsable(_) => Some(Kind::Wand),
+            ItemType::UntargetedUsable(_) => Some(Kind::Scroll),
+            ItemType::Trinket => Some(Kind::Amulet),
+            _ => None,
+        }
+    }
+
+    /// Return the unidentified appearances items of this kind can have.
+    fn appearances(self) -> &'static [(&'static str, Icon)] {
+        use Icon::*;
+        match self {
+            Kind::Wand => &[
+                ("ruby wand", Wand1),
+                ("glass wand", Wand2),
+                ("coral wand", Wand3),
+                ("oak wand", Wand4),
+                ("bone wand", Wand5),
+            ],
+            Kind::Scroll => &[
+                ("scroll labeled ANSUR VELD|scrolls labeled ANSUR VELD", Scroll1),
+                ("scroll labeled KORO MAHT|scrolls labeled KORO MAHT", Scroll2),
+                ("scroll labeled ISSA ONDU|scrolls labeled ISSA ONDU", Scroll3),
+                ("scroll labeled THAARN|scrolls labeled THAARN", Scroll4),
+                ("scroll labeled ELU PRIM|scrolls labeled ELU PRIM", Scroll5),
+            ],
+            Kind::Amulet => &[
+                ("runed amulet", Amulet1),
+                ("jade amulet", Amulet2),
+                ("silver amulet", Amulet3),
+            ],
+        }
+    }
+}
+
+/// What the player knows about the item kinds in the world.
+///
+/// Item kinds are keyed by the singular name of their spec.
+#[derive(Clone, Eq, PartialEq, Debug, Default, Serialize, Deserialize)]
+pub struct Identification {
+    /// How unidentified item kinds look, shuffled separately for every game.
+    ///
+    /// Item kinds that aren't in the map are always identified.
+    appearances: BTreeMap<String, Desc>,
+    /// Item kinds the player has identified.
+    known: BTreeSet<String>,
+    /// Names the player has given to unidentified item kinds.
+    called: BTreeMap<String, String>,
+}
+
+impl Identification {
+    /// Assign shuffled appearances to the item kinds in the spec database.
+    pub fn new(seed: u32) -> Identification {
+        let mut rng = seeded_rng(&(seed, "identify"));
+        let mut ret = Identification::default();
+
+        for &kind in &[Kind::Wand, Kind::Scroll, Kind::Amulet] {
+            let mut appearances = kind.appearances().to_vec();
+            appearances.shuffle(&mut rng);
+            let names = spec::iter_specs().filter_map(|spec| match spec.item_type() {
+                Some(t) if Kind::of(t) == Some(kind) => {
+                    Some(grammar::split_plural(spec.name()).0.to_string())
+                }
+                _ => None,
+            });
+            // If there are more item kinds than appearances, the rest are always identified.
+            for (name, (appearance, icon)) in names.zip(appearances) {
+                ret.appearances.insert(name, Desc::new(appearance, icon));
+            }
+        }
+        ret
+    }
+
+    /// Return the item kinds the player has identified.
+    pub(crate) fn known(&self) -> &BTreeSet<String> { &self.known }
+
+    /// Return the names the player has given to unidentified item kinds.
+    pub(crate) fn called(&self) -> &BTreeMap<String, String> { &self.called }
+}
+
+impl World {
+    /// Return the unidentified appearance of an entity's item kind.
+    fn appearance(&self, e: Entity) -> Option<&Desc> {
+        let name = &self.ecs().desc.get(e)?.singular_name;
+        self.identification.appearances.get(name)
+    }
+
+    /// Return the description to show for an entity.
+    ///
+    /// Unidentified items are shown with their appearance.
+    pub(crate) fn shown_desc(&self, e: Entity) -> Option<&Desc> {
+        if self.is_identified(e) {
+            self.ecs().desc.get(e)
+        } else {
+            self.appearance(e)
+        }
+    }
+
+    /// Return the icon to show for an entity.
+    ///
+    /// Items keep their appearance's icon after they're identified so that they don't change
+    /// looks.
+    pub(crate) fn shown_icon(&self, e: Entity) -> Option<Icon> {
+        self.appearance(e)
+            .or_else(|| self.ecs().desc.get(e))
+            .map(|desc| desc.icon)
+    }
+
+    /// Return whether the player knows what an item is.
+    pub fn is_identified(&self, e: Entity) -> bool {
+        match self.ecs().desc.get(e) {
+            Some(desc) => {
+                !self.identification.appearances.contains_key(&desc.singular_name)
+                    || self.identification.known.contains(&desc.singular_name)
+            }
+            None => true,
+        }
+    }
+
+    /// Return the name the player has given to an unidentified item's kind.
+    pub fn called_name(&self, e: Entity) -> Option<&str> {
+        if self.is_identified(e) {
+            return None;
+        }
+        let name = &self.ecs().desc.get(e)?.singular_name;
+        self.identification.called.get(name).map(|s| &s[..])
+    }
+
+    /// Give a custom name to the kind of an unidentified item.
+    ///
+    /// An empty name removes the custom name.
+    pub(crate) fn call_item(&mut self, e: Entity, name: &str) {
+        if self.is_identified(e) {
+            return;
+        }
+        let kind = match self.ecs().desc.get(e) {
+            Some(desc) => desc.singular_name.clone(),
+            None => return,
+        };
+        let name = name.trim();
+        if name.is_empty() {
+            self.identification.called.remove(&kind);
+        } else {
+            self.identification.called.insert(kind, name.to_string());
+        }
+    }
+
+    /// Make the player know the kind of an item.
+    ///
+    /// Return whether the item was unidentified before.
+    pub(crate) fn identify(&mut self, e: Entity) -> bool {
+        if self.is_identified(e) {
+            return false;
+        }
+        let kind = self.ecs().desc[e].singular_name.clone();
+        let before = self.subject(e);
+        self.identification.known.insert(kind.clone());
+        self.identification.called.remove(&kind);
+        msg!("[One] [is] [a thing]."; before, self.object(e));
+        true
+    }
+
+    /// Identify the first unidentified item a creature is carrying.
+    pub(crate) fn identify_carried(&mut self, e: Entity) {
+        let item = self
+            .entities_in(e)
+            .into_iter()
+            .map(|(_, item)| item)
+            .find(|&item| !self.is_identified(item));
+        match item {
+            Some(item) => {
+                self.identify(item);
+            }
+            None => {
+                msg!("You have nothing left to identify.");
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::Identification;
+    use crate::{Command, ExternalEntity, World, WorldSeed};
+    use calx::Incremental;
+
+    #[test]
+    fn test_appearances() {
+        let a = Identification::new(1);
+        assert_eq!(a.appearances.len(), 11);
+        let names: Vec<_> = a.appearances.values().map(|d| &d.singular_name).collect();
+        let mut unique = names.clone();
+        unique.sort();
+        unique.dedup();
+        assert_eq!(names.len(), unique.len());
+
+        // Different seeds shuffle the appearances differently.
+        assert!((2..10).any(|seed| Identification::new(seed).appearances != a.appearances));
+    }
+
+    #[test]
+    fn test_identify() {
+        let mut world = World::new(&WorldSeed::test(1));
+        let player = world.player().unwrap();
+        let loc = world.location(player).unwrap();
+        let wand = world.spawn(
+            &ExternalEntity::from_name("wand of fireball|wands of fireball").unwrap(),
+            loc,
+        );
+        let appearance = world.identification.appearances["wand of fireball"].clone();
+        assert!(!world.is_identified(wand));
+        assert_eq!(world.entity_name(wand), appearance.singular_name);
+        assert_eq!(world.entity_icon(wand), Some(appearance.icon));
+
+        // Naming goes through the command history so that undo and replays keep the names.
+        while !world.player_can_act() {
+            world.update(&Command::Wait);
+        }
+        let call = Command::CallItem(wand, " zapper ".into());
+        assert!(world.can_command(&call));
+        world.update(&call);
+        assert_eq!(
+            world.entity_name(wand),
+            format!("{} called zapper", appearance.singular_name)
+        );
+
+        assert!(world.identify(wand));
+        assert!(!world.identify(wand));
+        assert_eq!(world.entity_name(wand), "wand of fireball");
+        assert_eq!(world.called_name(wand), None);
+        assert!(!world.can_command(&call));
+        // The icon stays the same.
+        assert_eq!(world.entity_icon(wand), Some(appearance.icon));
+    }
+
+    #[test]
+    fn test_names_are_hashed() {
+        let hash_with = |name: &str| {
+            let mut world = World::new(&WorldSeed::test(1));
+            let loc = world.location(world.player().unwrap()).unwrap();
+            let wand = world.spawn(
+                &ExternalEntity::from_name("wand of fireball|wands of fireball").unwrap(),
+                loc,
+            );
+            while !world.player_can_act() {
+                world.update(&Command::Wait);
+            }
+            world.update(&Command::CallItem(wand, name.into()));
+            world.state_hash()
+        };
+        assert_eq!(hash_with("zapper"), hash_with("zapper"));
+        assert_ne!(hash_with("zapper"), hash_with("blaster"));
+    }
+}
@@ -1,6 +1,6 @@
 //! Item and equipment logic
 
-use crate::{msg, Ability, ActionOutcome, Location, World};
+use crate::{ai::Action, command::CommandError, msg, Ability, ActionOutcome, Location, Stat, World};
 use calx::{hex_neighbors, CellVector, HexGeom};
 use calx_ecs::Entity;
 use euclid::vec2;
@@ -11,7 +11,7 @@ use std::slice;
 pub const BAG_CAPACITY: u32 = 50;
 
 /// Inventory slots.
-#[derive(Copy, Eq, PartialEq, Clone, Debug, PartialOrd, Ord, Serialize, Deserialize)]
+#[derive(Copy, Eq, PartialEq, Clone, Hash, Debug, PartialOrd, Ord, Serialize, Deserialize)]
 pub enum Slot {
     Bag(u32),
     Head,
@@ -36,8 +36,12 @@ impl Slot {
     pub fn accepts(self, equip_type: Option<EquipType>) -> bool {
         use self::Slot::*;
         match self {
-            RightHand => equip_type == Some(EquipType::Melee),
-            LeftHand => false, // TODO: Shields etc
+            RightHand => {
+                equip_type == Some(EquipType::Melee) || equip_type == Some(EquipType::TwoHanded)
+            }
+            LeftHand => {
+                equip_type == Some(EquipType::Shield) || equip_type == Some(EquipType::Light)
+            }
             Ranged => equip_type == Some(EquipType::Ranged),
             Head => equip_type == Some(EquipType::Head),
             Body => equip_type == Some(EquipType::Body),
@@ -60,7 +64,14 @@ impl Slot {
 #[derive(Copy, Eq, PartialEq, Clone, Debug, Serialize, Deserialize)]
 pub enum ItemType {
     MeleeWeapon,
+    /// Melee weapon that needs both hands, can't be used with a shield.
+    TwoHandedWeapon,
+    Shield,
+    /// Carried in the off hand to light up the surroundings.
+    Light,
     RangedWeapon,
+    /// Shot from a ranged weapon, spent one at a time from the stack.
+    Ammo,
     Helmet,
     Armor,
     Boots,
@@ -73,11 +84,16 @@ pub enum ItemType {
     TargetedUsable(Ability),
     /// Consumed instantly when stepped on.
     Instant(Ability),
+    /// Eaten for an amount of nutrition.
+    Food(i32),
 }
 
 #[derive(Copy, Eq, PartialEq, Clone, Debug, Serialize, Deserialize)]
 pub enum EquipType {
     Melee,
+    TwoHanded,
+    Shield,
+    Light,
     Ranged,
     Head,
     Body,
@@ -91,6 +107,21 @@ pub struct Item {
     pub item_type: ItemType,
     /// How many uses a wand or similar has left.
     pub charges: u32,
+    /// Cursed items can't be taken off once they're equipped.
+    #[serde(default)]
+    pub cursed: bool,
+    /// What a creature needs to have to equip the item.
+    #[serde(default)]
+    pub requirements: Vec<Requirement>,
+}
+
+/// Something a creature needs to meet to equip an item.
+#[derive(Copy, Clone, Eq, PartialEq, Debug, Serialize, Deserialize)]
+pub enum Requirement {
+    /// Minimum character level.
+    Level(i32),
+    /// Minimum value of a stat, counting the bonuses from other equipment.
+    Stat(Stat, i32),
 }
 
 /// An entity that can become a stack of multiple copies.
@@ -114,13 +145,63 @@ impl World {
     }
 
     pub(crate) fn entity_take(&mut self, e: Entity, item: Entity) -> ActionOutcome {
+        if self.pick_up(e, item) {
+            self.end_turn(e, Action::Take);
+            Some(true)
+        } else {
+            None
+        }
+    }
+
+    /// Take part of a stack lying on the floor.
+    pub(crate) fn entity_take_count(
+        &mut self,
+        e: Entity,
+        item: Entity,
+        count: u32,
+    ) -> ActionOutcome {
+        let on_floor = self.items_at(self.location(e)?).contains(&item);
+        if !on_floor || count == 0 || count > self.count(item) {
+            return None;
+        }
+        let taken = self.split_stack(item, count);
+        if self.pick_up(e, taken) {
+            self.end_turn(e, Action::Take);
+            Some(true)
+        } else {
+            // Put the split off part back in the pile.
+            if taken != item {
+                self.merge_stack(taken, item);
+            }
+            None
+        }
+    }
+
+    /// Take everything from the floor a creature is standing on.
+    pub(crate) fn entity_take_all(&mut self, e: Entity) -> ActionOutcome {
+        let mut took_any = false;
+        for item in self.items_at(self.location(e)?) {
+            took_any |= self.pick_up(e, item);
+        }
+        if took_any {
+            self.end_turn(e, Action::Take);
+            Some(true)
+        } else {
+            None
+        }
+    }
+
+    /// Move an item in the inventory of a creature.
+    ///
+    /// Return whether the item was picked up.
+    fn pick_up(&mut self, e: Entity, item: Entity) -> bool {
         // Only mobs can take items.
         if !self.is_mob(e) {
-            return None;
+            return false;
         }
 
         if !self.is_item(item) {
-            return None;
+            return false;
         }
 
         // Somehow trying to pick up something we're inside of. Pls don't break the universe.
@@ -128,48 +209,96 @@ impl World {
             panic!("Trying to pick up an entity you are inside of. This shouldn't happen");
         }
 
+        // Grab the name now, the item entity may get merged into a stack.
+        let object = self.object(item);
+        let mut picked_up = false;
+
         // Item might go into a stack, look for stacks.
         if self.is_stackable(item) {
-            let bag = self.entities_in_bag(e);
-            for (_, e) in &bag {
-                // Even if we could stack with this, it's already full, ignore.
-                if self.count(*e) == self.max_stack_size(*e) {
-                    continue;
-                }
-
-                if self.can_stack_with(item, *e) {
-                    let stack_size = self.count(*e) + self.count(item);
-                    let max_size = self.max_stack_size(*e);
-
-                    if stack_size <= max_size {
-                        // Merge into an existing stack, delete incoming item.
-                        self.ecs_mut().stacking[*e].count = stack_size;
-                        self.kill_entity(item);
-                        // Item was consumed, so we're done here.
-                        return Some(true);
-                    } else {
-                        // Top up the stack, our item remains so we keep looking for a place or
-                        // more items to merge it with.
-                        let overflow = stack_size - max_size;
-                        self.ecs_mut().stacking[*e].count = max_size;
-                        self.ecs_mut().stacking[item].count = overflow;
-                    }
+            for (_, stack) in self.entities_in_bag(e) {
+                if self.merge_stack(item, stack) {
+                    // Item was consumed, so we're done here.
+                    picked_up = true;
+                    break;
                 }
             }
         }
 
-        if let Some(slot) = self.free_bag_slot(e) {
-            self.equip_item(item, e, slot);
-            if self.is_player(e) {
-                msg!("[One] pick[s] up [a thing].";
-                    self.subject(e), self.object(item));
+        if !picked_up {
+            match self.free_bag_slot(e) {
+                Some(slot) => self.equip_item(item, e, slot),
+                // No more inventory space
+                None => return false,
             }
+        }
 
-            self.end_turn(e);
-            Some(true)
+        if self.is_player(e) {
+            msg!("[One] pick[s] up [a thing]."; self.subject(e), object);
+        }
+        true
+    }
+
+    /// Merge as much of a stack into another stack as fits.
+    ///
+    /// Return true if the whole stack was merged and the item entity is gone.
+    fn merge_stack(&mut self, item: Entity, stack: Entity) -> bool {
+        // Even if we could stack with this, it's already full, ignore.
+        if item == stack
+            || self.count(stack) == self.max_stack_size(stack)
+            || !self.can_stack_with(item, stack)
+        {
+            return false;
+        }
+
+        let stack_size = self.count(stack) + self.count(item);
+        let max_size = self.max_stack_size(stack);
+        if stack_size <= max_size {
+            // Merge into an existing stack, delete incoming item.
+            self.ecs_mut().stacking[stack].count = stack_size;
+            // Not kill_entity, that would only take one item off the stack.
+            self.spatial.remove(item);
+            true
         } else {
-            // No more inventory space
-            None
+            // Top up the stack, our item remains so we keep looking for a place or more items to
+            // merge it with.
+            self.ecs_mut().stacking[stack].count = max_size;
+            self.ecs_mut().stacking[item].count = stack_size - max_size;
+            false
+        }
+    }
+
+    /// Split a number of items off a stack on the floor into a new entity in the same pile.
+    ///
+    /// Return the stack itself if the whole stack is split off.
+    fn split_stack(&mut self, item: Entity, count: u32) -> Entity {
+        let total = self.count(item);
+        if count >= total {
+            return item;
+        }
+        let mut copy = self.extract(item).expect("Splitting a nonexistent stack");
+        copy.loadout.stacking = Some(Stacking { count });
+        self.ecs_mut().stacking[item].count = total - count;
+
+        let ret = self.inject(&copy);
+        if let Some(loc) = self.location(item) {
+            self.set_entity_location(ret, loc);
+        }
+        ret
+    }
+
+    /// Drop an item on the floor, merging it with a matching stack already there.
+    pub(crate) fn drop_item(&mut self, item: Entity, loc: Location) {
+        self.place_entity(item, loc);
+        let loc = match self.location(item) {
+            Some(loc) => loc,
+            None => return,
+        };
+        if self.is_stackable(item) {
+            for stack in self.items_at(loc) {
+                if self.merge_stack(item, stack) {
+                    break;
+                }
+            }
         }
     }
 
@@ -220,12 +349,11 @@ impl World {
         }
     }
 
-    /// Find a drop position for an item, trying to keep one item per cell.
+    /// Find a drop position for an item.
     ///
-    /// Dropping several items in the same location will cause them to spread out to the adjacent
-    /// cells. If there is no room for the items to spread out, they will be stacked on the initial
-    /// drop site.
-    pub fn empty_item_drop_location(&self, origin: Location) -> Location {
+    /// Items pile up at the drop site if they can lie there, otherwise they go to the nearest cell
+    /// where they can. If there is no such cell nearby, they stay at the drop site.
+    pub fn item_drop_location(&self, origin: Location) -> Location {
         static MAX_SPREAD_DISTANCE: i32 = 8;
         let is_valid = |v: CellVector| {
             self.can_drop_item_at(origin.jump(self, v)) && v.hex_dist() <= MAX_SPREAD_DISTANCE
@@ -242,7 +370,7 @@ impl World {
             seen.insert(offset);
 
             let loc = origin.jump(self, offset);
-            if self.item_at(loc).is_none() {
+            if self.can_drop_item_at(loc) {
                 return loc;
             }
 
@@ -262,6 +390,24 @@ impl World {
         self.entities_at(loc).into_iter().find(|&e| self.is_item(e))
     }
 
+    /// Return all the items in the pile at given location.
+    pub fn items_at(&self, loc: Location) -> Vec<Entity> {
+        self.entities_at(loc)
+            .into_iter()
+            .filter(|&e| self.is_item(e))
+            .collect()
+    }
+
+    /// Return whether a creature has room in its bag for an item.
+    pub fn has_room_for(&self, e: Entity, item: Entity) -> bool {
+        self.free_bag_slot(e).is_some()
+            || (self.is_stackable(item)
+                && self.entities_in_bag(e).into_iter().any(|(_, stack)| {
+                    self.count(stack) < self.max_stack_size(stack)
+                        && self.can_stack_with(item, stack)
+                }))
+    }
+
     pub fn can_drop_item_at(&self, loc: Location) -> bool {
         if !self.is_valid_location(loc) {
             return false;
@@ -296,6 +442,28 @@ impl World {
         self.ecs().item.get(item).map_or(0, |i| i.charges)
     }
 
+    /// Return the ammunition the entity would fire from its ranged weapon.
+    pub fn ammo(&self, e: Entity) -> Option<Entity> {
+        self.entities_in_bag(e)
+            .into_iter()
+            .map(|(_, item)| item)
+            .find(|&item| self.item_type(item) == Some(ItemType::Ammo))
+    }
+
+    /// Split a single item off an item stack.
+    ///
+    /// The new item is not placed anywhere. If the item isn't a stack of more than one, the item
+    /// itself is returned.
+    pub(crate) fn split_one(&mut self, e: Entity) -> Entity {
+        if self.count(e) <= 1 {
+            return e;
+        }
+        let mut single = self.extract(e).unwrap();
+        single.loadout.stacking = Some(Stacking::default());
+        self.ecs_mut().stacking[e].count -= 1;
+        self.inject(&single)
+    }
+
     pub fn destroy_after_use(&self, item: Entity) -> bool {
         // XXX: Fragile. What we want here is to tag potions and scrolls as destroyed when used and
         // wands to stick around. Current item data doesn't have is_potion or is_scroll, but
@@ -314,6 +482,9 @@ impl World {
         use crate::ItemType::*;
         match self.item_type(item) {
             Some(MeleeWeapon) => Some(EquipType::Melee),
+            Some(TwoHandedWeapon) => Some(EquipType::TwoHanded),
+            Some(Shield) => Some(EquipType::Shield),
+            Some(Light) => Some(EquipType::Light),
             Some(RangedWeapon) => Some(EquipType::Ranged),
             Some(Helmet) => Some(EquipType::Head),
             Some(Armor) => Some(EquipType::Body),
@@ -323,6 +494,65 @@ impl World {
         }
     }
 
+    pub fn is_cursed(&self, item: Entity) -> bool {
+        self.ecs().item.get(item).map(|i| i.cursed) == Some(true)
+    }
+
+    /// Check whether a creature can take off an item it's wearing.
+    pub fn check_unequip(&self, e: Entity, item: Entity) -> Result<(), CommandError> {
+        let is_equipped = self.entity_slot(item).map(Slot::is_equipment_slot) == Some(true);
+        if is_equipped && self.entity_contains(e, item) && self.is_cursed(item) {
+            return Err(CommandError::Cursed(item));
+        }
+        Ok(())
+    }
+
+    /// Check whether a creature can wear an item in an equipment slot.
+    ///
+    /// Items already in the way in the slot itself are assumed to get moved out.
+    pub fn check_equip(&self, e: Entity, item: Entity, slot: Slot) -> Result<(), CommandError> {
+        if !slot.is_equipment_slot() {
+            return Ok(());
+        }
+        if !slot.accepts(self.equip_type(item)) {
+            return Err(CommandError::WrongSlot);
+        }
+
+        let requirements = self
+            .ecs()
+            .item
+            .get(item)
+            .map(|i| i.requirements.clone())
+            .unwrap_or_default();
+        let stats = self.stats(e);
+        for req in requirements {
+            match req {
+                Requirement::Level(level) if stats.level < level => {
+                    return Err(CommandError::LevelTooLow(level));
+                }
+                Requirement::Stat(stat, value) if stat.get(&stats) < value => {
+                    return Err(CommandError::StatTooLow(stat, value));
+                }
+                _ => {}
+            }
+        }
+
+        // Two-handed weapons can't be used with a shield, but a light can still be carried along.
+        let other_hand = match slot {
+            Slot::RightHand if self.equip_type(item) == Some(EquipType::TwoHanded) => self
+                .entity_equipped(e, Slot::LeftHand)
+                .filter(|&x| self.equip_type(x) == Some(EquipType::Shield)),
+            Slot::LeftHand if self.equip_type(item) == Some(EquipType::Shield) => self
+                .entity_equipped(e, Slot::RightHand)
+                .filter(|&x| self.equip_type(x) == Some(EquipType::TwoHanded)),
+            _ => None,
+        };
+        match other_hand {
+            Some(x) if x != item => Err(CommandError::HandsFull),
+            _ => Ok(()),
+        }
+    }
+
     pub(crate) fn drain_charge(&mut self, item: Entity) {
         if self.destroy_after_use(item) {
             self.kill_entity(item);
@@ -335,3 +565,112 @@ impl World {
         }
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::Slot;
+    use crate::{Command, CommandError, ExternalEntity, Stat, World, WorldSeed};
+    use calx_ecs::Entity;
+
+    fn give(world: &mut World, name: &str) -> Entity {
+        let player = world.player().unwrap();
+        let loc = world.location(player).unwrap();
+        let item = world.spawn(&ExternalEntity::from_name(name).unwrap(), loc);
+        world.entity_take(player, item);
+        item
+    }
+
+    #[test]
+    fn test_equip_restrictions() {
+        let mut world = World::new(&WorldSeed::test(1));
+        let player = world.player().unwrap();
+        let shield = give(&mut world, "shield");
+        let greatsword = give(&mut world, "greatsword");
+        let crown = give(&mut world, "crown of thorns|crowns of thorns");
+
+        // Shields go in the off hand.
+        assert_eq!(
+            world.check_command(&Command::InventoryPlace(shield, Slot::RightHand)),
+            Err(CommandError::WrongSlot)
+        );
+        assert!(world.check_command(&Command::InventoryPlace(shield, Slot::LeftHand)).is_ok());
+        world.equip_item(shield, player, Slot::LeftHand);
+
+        // Too weak for the greatsword.
+        let place_greatsword = Command::InventoryPlace(greatsword, Slot::RightHand);
+        assert_eq!(
+            world.check_command(&place_greatsword),
+            Err(CommandError::StatTooLow(Stat::Power, 12))
+        );
+        world.ecs_mut().stats[player].base.level = 1;
+        world.rebuild_stats(player);
+        // Can't hold it with a shield in the other hand.
+        assert_eq!(world.check_command(&place_greatsword), Err(CommandError::HandsFull));
+        world.equip_item(shield, player, Slot::Bag(10));
+        assert!(world.check_command(&place_greatsword).is_ok());
+
+        // Torches go in the off hand too, and fit next to a two-handed weapon.
+        let torch = give(&mut world, "torch");
+        assert_eq!(
+            world.check_command(&Command::InventoryPlace(torch, Slot::RightHand)),
+            Err(CommandError::WrongSlot)
+        );
+        world.equip_item(greatsword, player, Slot::RightHand);
+        assert!(world.check_command(&Command::InventoryPlace(torch, Slot::LeftHand)).is_ok());
+        assert_eq!(
+            world.check_command(&Command::InventoryPlace(shield, Slot::LeftHand)),
+            Err(CommandError::HandsFull)
+        );
+
+        // Cursed items won't come off.
+        world.equip_item(crown, player, Slot::Head);
+        assert_eq!(
+            world.check_command(&Command::Equip(Slot::Head)),
+            Err(CommandError::Cursed(crown))
+        );
+        assert_eq!(
+            world.check_command(&Command::Drop(Slot::Head)),
+            Err(CommandError::Cursed(crown))
+        );
+        assert_eq!(
+            world.check_command(&Command::InventorySwap(Slot::Head, Slot::Bag(20))),
+            Err(CommandError::Cursed(crown))
+        );
+    }
+
+    #[test]
+    fn test_item_piles() {
+        let mut world = World::new(&WorldSeed::test(1));
+        let player = world.player().unwrap();
+        let loc = world.location(player).unwrap();
+        let shield = world.spawn(&ExternalEntity::from_name("shield").unwrap(), loc);
+        let scrolls = world.spawn(
+            &ExternalEntity::from_name("scroll of lightning|scrolls of lightning").unwrap(),
+            loc,
+        );
+        world.ecs_mut().stacking[scrolls].count = 5;
+        // Items pile up instead of spreading out.
+        assert_eq!(world.items_at(loc), vec![shield, scrolls]);
+
+        // Take part of a stack.
+        assert_eq!(
+            world.check_command(&Command::TakeItem(scrolls, 6)),
+            Err(CommandError::Invalid)
+        );
+        assert_eq!(world.entity_take_count(player, scrolls, 2), Some(true));
+        assert_eq!(world.count(scrolls), 3);
+        let (_, taken) = world.entities_in_bag(player)[0];
+        assert_ne!(taken, scrolls);
+        assert_eq!(world.count(taken), 2);
+
+        // Dropped items merge back into the matching stack on the floor.
+        world.drop_item(taken, loc);
+        assert_eq!(world.items_at(loc), vec![shield, scrolls]);
+        assert_eq!(world.count(scrolls), 5);
+
+        assert_eq!(world.entity_take_all(player), Some(true));
+        assert!(world.items_at(loc).is_empty());
+        assert_eq!(world.entities_in_bag(player).len(), 2);
+        assert_eq!(world.check_command(&Command::TakeAll), Err(CommandError::Invalid));
+    }
+}
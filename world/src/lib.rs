@@ -5,55 +5,92 @@ mod ai;
 mod animations;
 pub use animations::{Anim, AnimState, LerpLocation, PhysicsSpace, PhysicsVector};
 
+mod clock;
+pub use clock::DayPhase;
+
 mod command;
-pub use command::{ActionOutcome, Command};
+pub use command::{ActionOutcome, Command, CommandError};
 
 mod components;
 
 mod desc;
 pub use desc::Icon;
 
+mod digest;
+pub use digest::{find_desync, Desync, Digest};
+
 mod effect;
-pub use effect::Ability;
+pub use effect::{Ability, Damage};
 
 mod extract;
 pub use extract::ExternalEntity;
 
+mod fire;
+
 mod flags;
 
+mod flow_field;
+
 mod fov;
 
 mod grammar;
 
+mod hunger;
+pub use hunger::Hunger;
+
+mod identify;
+pub use identify::Identification;
+
 mod item;
-pub use item::{ItemType, Slot};
+pub use item::{ItemType, Requirement, Slot};
+
+mod light;
 
 mod location;
 pub use location::{Location, Portal};
 
 mod location_set;
 
+mod loot;
+
 mod mapsave;
 pub use mapsave::MapSave;
 
 mod map;
 
+mod modifier;
+pub use modifier::{ModifierOp, ModifierSource, Stat, StatBreakdown};
+
 mod movement;
 
 mod msg;
-pub use msg::{register_msg_receiver, MsgReceiver};
+pub use msg::{capture_msgs, register_msg_receiver, MsgReceiver};
 
 mod mutate;
 
+mod noise;
+
 mod query;
 
+mod replay;
+pub use replay::{Replay, REPLAY_VERSION};
+
 mod sector;
 pub use sector::{Sector, SectorDir, SectorVec, WorldSkeleton, SECTOR_HEIGHT, SECTOR_WIDTH};
 
+mod sim;
+pub use sim::{PlayerReport, Report, Simulation};
+
 mod spatial;
+
 mod spec;
+pub use spec::{load_specs, SpecError};
+
 mod stats;
 
+mod status;
+pub use status::Status;
+
 pub mod terrain;
 pub use terrain::Terrain;
 
@@ -62,7 +99,7 @@ mod vaults;
 mod volume;
 
 mod world;
-pub use crate::world::{Ecs, World, WorldSeed};
+pub use crate::world::{Ecs, World, WorldSeed, GAME_VERSION};
 
 mod world_cache;
 
@@ -82,11 +119,19 @@ pub trait Distribution<T> {
 /// The combat formula.
 ///
 /// Given a deciban roll and the relevant stats, determine amount of damage dealt.
-/// Advantage is attacker skill - target defense.
-pub fn attack_damage(roll: f32, advantage: i32, weapon_power: i32) -> i32 {
+/// Advantage is attacker skill - target defense. Target armor deflects physical blows but does
+/// nothing against other types of damage.
+pub fn attack_damage(
+    roll: f32,
+    advantage: i32,
+    weapon_power: i32,
+    armor: i32,
+    damage: Damage,
+) -> i32 {
     const MAX_DAMAGE_MULTIPLIER: f32 = 4.0;
 
-    let roll = roll + advantage as f32;
+    let armor = if damage == Damage::Physical { armor } else { 0 };
+    let roll = roll + (advantage - 2 * armor) as f32;
     (weapon_power as f32 * (0.0..=MAX_DAMAGE_MULTIPLIER).clamp((roll - 2.0) * 0.05)) as i32
 }
 
@@ -0,0 +1,241 @@
+//! Light sources and how they light up the world
+
+use crate::{
+    fov::{AreaFov, SightFov},
+    Location, World,
+};
+use calx::{CellVector, HexFov, HexGeom};
+use calx_ecs::Entity;
+use std::collections::HashMap;
+
+/// How far from the player light sources are looked for.
+///
+/// Must cover the sight range plus the radius of the brightest light.
+const LIGHT_SEARCH_RANGE: u32 = 16;
+
+/// Light radius of glowing terrain like magma.
+const TERRAIN_LIGHT: i32 = 3;
+
+/// Light radius of burning terrain.
+const FIRE_LIGHT: i32 = 2;
+
+/// Brightness of the cells around the player, computed from the light sources nearby.
+///
+/// Lit cells also store their offset from the light source that lights them up the most. Cells
+/// that aren't in the map are in complete darkness.
+#[derive(Default)]
+pub(crate) struct LightMap(HashMap<Location, (f32, CellVector)>);
+
+impl LightMap {
+    fn get(&self, loc: Location) -> f32 { self.0.get(&loc).map_or(0.0, |&(level, _)| level) }
+
+    /// Spread light from a source along the lines of sight from it.
+    ///
+    /// Light fades linearly with distance and doesn't reach past the radius.
+    fn shine(&mut self, world: &World, source: Location, radius: i32) {
+        for (pos, a) in HexFov::new(SightFov::new(world, radius as u32, source)) {
+            let level = 1.0 - pos.hex_dist() as f32 / (radius + 1) as f32;
+            let cell = self.0.entry(a.origin + pos).or_insert((0.0, pos));
+            if level > cell.0 {
+                *cell = (level, pos);
+            }
+        }
+    }
+}
+
+impl World {
+    /// Return the brightness of a location between 0 (pitch dark) and 1 (fully lit).
+    pub fn light_level(&self, loc: Location) -> f32 {
+        let ambient = self.ambient_light(loc);
+        if ambient >= 1.0 {
+            return ambient;
+        }
+
+        ambient.max(self.with_light_map(|map| map.get(loc)))
+    }
+
+    /// Return the offset of a location from the light source that lights it up the most.
+    ///
+    /// Returns `None` if the location gets more light from the sky than from light sources.
+    pub fn light_direction(&self, loc: Location) -> Option<CellVector> {
+        let ambient = self.ambient_light(loc);
+        if ambient >= 1.0 {
+            return None;
+        }
+        self.with_light_map(|map| match map.0.get(&loc) {
+            Some(&(level, offset)) if level > ambient => Some(offset),
+            _ => None,
+        })
+    }
+
+    /// Run a function on the light map, computing the map first if it isn't cached.
+    fn with_light_map<T>(&self, f: impl FnOnce(&LightMap) -> T) -> T {
+        if self.light_map.borrow().is_none() {
+            *self.light_map.borrow_mut() = Some(self.compute_light_map());
+        }
+        f(self.light_map.borrow().as_ref().unwrap())
+    }
+
+    /// Return the light level at location without any light sources.
+    fn ambient_light(&self, loc: Location) -> f32 {
+        // The surface is lit by the sun or the night sky, underground is dark.
+        if self.is_underground(loc) {
+            0.0
+        } else {
+            self.daylight()
+        }
+    }
+
+    /// Return the radius of the light given off by the things at location.
+    pub fn light_radius(&self, loc: Location) -> i32 {
+        let mut ret = 0;
+        if self.terrain(loc).is_luminous() {
+            ret = TERRAIN_LIGHT;
+        }
+        if self.is_burning(loc) {
+            ret = ret.max(FIRE_LIGHT);
+        }
+        // Items lying on the floor and mobs with their equipment.
+        for e in self.entities_at(loc) {
+            ret = ret.max(self.stats(e).light);
+        }
+        ret
+    }
+
+    /// Drop the cached light map after light sources have moved or changed.
+    pub(crate) fn forget_light(&self) { *self.light_map.borrow_mut() = None; }
+
+    /// Drop the cached light map if the entity is the player or gives off light.
+    ///
+    /// Call this when the entity moves, appears or disappears.
+    pub(crate) fn forget_light_of(&self, e: Entity) {
+        if self.player() == Some(e) || self.stats(e).light > 0 {
+            self.forget_light();
+        }
+    }
+
+    /// Return whether light sources have changed since the player's view was last updated.
+    pub(crate) fn light_changed(&self) -> bool { self.light_map.borrow().is_none() }
+
+    /// Light up the area around the player.
+    fn compute_light_map(&self) -> LightMap {
+        let mut ret = LightMap::default();
+        let origin = match self.player().and_then(|p| self.location(p)) {
+            Some(loc) => loc,
+            None => return ret,
+        };
+
+        // Look for sources along the player's chart so that light shines through portals.
+        for (pos, a) in HexFov::new(AreaFov::new(self, LIGHT_SEARCH_RANGE, origin)) {
+            let loc = a.origin + pos;
+            let radius = self.light_radius(loc);
+            if radius > 0 {
+                ret.shine(self, loc, radius);
+            }
+        }
+        ret
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{clock::DAY_LENGTH, ExternalEntity, Location, Slot, Terrain, World, WorldSeed};
+    use calx::Dir6;
+    use euclid::vec2;
+
+    /// Return how brightly light sources light up a location.
+    fn lit(world: &World, loc: Location) -> f32 { world.compute_light_map().get(loc) }
+
+    #[test]
+    fn test_light_sources() {
+        let mut world = World::new(&WorldSeed::test(1));
+        let player = world.player().unwrap();
+        let origin = world.location(player).unwrap();
+        let dir = Dir6::iter()
+            .cloned()
+            .find(|&dir| !world.blocks_walk(origin.jump(&world, dir)))
+            .unwrap();
+        let next = origin.jump(&world, dir);
+
+        // The player carries a small light.
+        assert_eq!(world.light_radius(origin), 3);
+        assert_eq!(lit(&world, origin), 1.0);
+        assert_eq!(lit(&world, next), 0.75);
+
+        // A torch on the floor shines further.
+        let torch = world.spawn(&ExternalEntity::from_name("torch").unwrap(), next);
+        assert_eq!(world.light_radius(next), 6);
+        assert_eq!(lit(&world, next), 1.0);
+
+        // Carried light sources don't shine, but equipped ones replace a dimmer light.
+        world.entity_take(player, torch);
+        assert_eq!(world.light_radius(next), 0);
+        assert_eq!(world.light_radius(origin), 3);
+        world.equip_item(torch, player, Slot::LeftHand);
+        assert_eq!(world.light_radius(origin), 6);
+    }
+
+    #[test]
+    fn test_light_direction() {
+        let mut world = World::new(&WorldSeed::test(1));
+        let player = world.player().unwrap();
+        let origin = world.location(player).unwrap();
+        let dir = Dir6::iter()
+            .cloned()
+            .find(|&dir| !world.blocks_walk(origin.jump(&world, dir)))
+            .unwrap();
+        let next = origin.jump(&world, dir);
+
+        // Daylight doesn't come from any light source.
+        assert_eq!(world.light_direction(next), None);
+
+        // At night the player's light shines outwards from the player.
+        world.flags.tick = DAY_LENGTH / 2;
+        assert_eq!(world.light_direction(origin), Some(vec2(0, 0)));
+        assert_eq!(world.light_direction(next), Some(dir.to_v2()));
+
+        // Nearer and brighter lights take over.
+        let beyond = next.jump(&world, dir);
+        assert_eq!(world.light_direction(beyond), Some(dir.to_v2() * 2));
+        world.spawn(&ExternalEntity::from_name("torch").unwrap(), next);
+        assert_eq!(world.light_direction(beyond), Some(dir.to_v2()));
+    }
+
+    #[test]
+    fn test_light_map_updates() {
+        let mut world = World::new(&WorldSeed::test(1));
+        let player = world.player().unwrap();
+        let origin = world.location(player).unwrap();
+        let spots: Vec<Location> = Dir6::iter()
+            .map(|&dir| origin.jump(&world, dir))
+            .filter(|&loc| !world.blocks_walk(loc))
+            .collect();
+        let relight =
+            |world: &World| *world.light_map.borrow_mut() = Some(world.compute_light_map());
+
+        // Things that don't give off light can come and go without redoing the lighting.
+        relight(&world);
+        let rat = world.spawn(&ExternalEntity::from_name("rat").unwrap(), spots[0]);
+        world.place_entity(rat, spots[1]);
+        world.kill_entity(rat);
+        assert!(!world.light_changed());
+
+        // Light sources appearing, being picked up or equipped change the lighting.
+        let torch = world.spawn(&ExternalEntity::from_name("torch").unwrap(), spots[0]);
+        assert!(world.light_changed());
+        relight(&world);
+        world.entity_take(player, torch);
+        assert!(world.light_changed());
+        relight(&world);
+        world.equip_item(torch, player, Slot::LeftHand);
+        assert!(world.light_changed());
+
+        // So do the player moving and the terrain changing.
+        relight(&world);
+        world.place_entity(player, spots[1]);
+        assert!(world.light_changed());
+        relight(&world);
+        world.set_terrain(spots[0], Terrain::Magma);
+        assert!(world.light_changed());
+    }
+}
@@ -1,6 +1,7 @@
 use crate::location::Location;
 use serde_derive::{Deserialize, Serialize};
 use std::collections::HashMap;
+use std::hash::{Hash, Hasher};
 
 /// Compact Location set collection
 #[derive(Eq, PartialEq, Clone, Debug, Default, Serialize, Deserialize)]
@@ -54,3 +55,12 @@ impl LocationSet {
         }
     }
 }
+
+impl Hash for LocationSet {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        // Hash map iteration order is arbitrary, sort the chunks to get a stable hash.
+        let mut chunks: Vec<(u64, u64)> = self.chunks.iter().map(|(&k, &v)| (k, v)).collect();
+        chunks.sort();
+        chunks.hash(state);
+    }
+}
@@ -0,0 +1,136 @@
+//! Random items dropped by dying creatures
+
+use crate::{ExternalEntity, Location, World};
+use calx::WeightedChoice;
+use rand::Rng;
+use serde_derive::{Deserialize, Serialize};
+
+/// Item that can be picked from a loot table.
+#[derive(Clone, Eq, PartialEq, Debug, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct LootEntry {
+    /// Spec name of the item.
+    pub item: String,
+    /// How likely the item is to get picked relative to the other entries.
+    pub weight: u32,
+    /// Smallest sector depth where the item can drop.
+    pub depth: i32,
+}
+
+impl Default for LootEntry {
+    fn default() -> Self {
+        LootEntry {
+            item: String::new(),
+            weight: 1,
+            depth: 0,
+        }
+    }
+}
+
+/// What a creature may drop when it dies.
+#[derive(Clone, Eq, PartialEq, Debug, Default, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct LootTable {
+    /// Percent chance of dropping a random item at depth zero.
+    pub chance: u32,
+    /// How many percentage points the drop chance goes up with each level of depth.
+    pub chance_per_depth: u32,
+    /// Items the random drop is picked from.
+    pub entries: Vec<LootEntry>,
+    /// Items that are always dropped.
+    ///
+    /// Used to make sure unique monsters are worth the trouble of killing.
+    pub guaranteed: Vec<String>,
+}
+
+impl LootTable {
+    pub fn is_empty(&self) -> bool { self.entries.is_empty() && self.guaranteed.is_empty() }
+
+    /// Return the spec names of all the items the table can drop.
+    pub fn item_names(&self) -> impl Iterator<Item = &str> {
+        self.guaranteed
+            .iter()
+            .chain(self.entries.iter().map(|e| &e.item))
+            .map(String::as_str)
+    }
+
+    /// Pick the items to drop at the given depth.
+    pub fn roll<R: Rng + ?Sized>(&self, rng: &mut R, depth: i32) -> Vec<&str> {
+        let depth = depth.max(0);
+        let mut ret: Vec<&str> = self.guaranteed.iter().map(String::as_str).collect();
+
+        let chance = self.chance + self.chance_per_depth * depth as u32;
+        if rng.gen_range(0, 100) < chance {
+            if let Some(entry) = self
+                .entries
+                .iter()
+                .filter(|e| e.depth <= depth)
+                .weighted_choice(rng, |e| e.weight as f32)
+            {
+                ret.push(&entry.item);
+            }
+        }
+        ret
+    }
+}
+
+impl World {
+    /// Roll a loot table and drop the items around a location.
+    pub(crate) fn drop_loot(&mut self, table: &LootTable, loc: Location) {
+        let depth = self.depth(loc);
+        let items: Vec<String> = table
+            .roll(self.rng(), depth)
+            .into_iter()
+            .map(String::from)
+            .collect();
+        for name in items {
+            if let Ok(item) = ExternalEntity::sample_from_name(self.rng(), &name) {
+                self.spawn(&item, loc);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{LootEntry, LootTable};
+
+    #[test]
+    fn test_loot_roll() {
+        let mut rng = calx::seeded_rng(&1);
+        let table = LootTable {
+            chance: 0,
+            chance_per_depth: 50,
+            entries: vec![
+                LootEntry {
+                    item: "sword".into(),
+                    ..Default::default()
+                },
+                LootEntry {
+                    item: "armor".into(),
+                    weight: 3,
+                    depth: 4,
+                },
+            ],
+            guaranteed: vec!["helmet".into()],
+        };
+
+        // Nothing random at the surface, the guaranteed drop is always there.
+        for _ in 0..100 {
+            assert_eq!(table.roll(&mut rng, 0), vec!["helmet"]);
+        }
+        // Deep item doesn't show up before its depth.
+        for _ in 0..100 {
+            assert_eq!(table.roll(&mut rng, 2), vec!["helmet", "sword"]);
+        }
+        let mut armor = 0;
+        for _ in 0..100 {
+            let drops = table.roll(&mut rng, 4);
+            assert_eq!(drops.len(), 2);
+            if drops[1] == "armor" {
+                armor += 1;
+            }
+        }
+        assert!(armor > 50 && armor < 100);
+    }
+}
@@ -0,0 +1,351 @@
+//! Typed stat modifiers
+
+use crate::{
+    hunger::Hunger,
+    stats::{Intrinsic, Stats},
+    status::Status,
+    Slot, World,
+};
+use calx_ecs::Entity;
+use serde_derive::{Deserialize, Serialize};
+use std::slice;
+
+/// A numeric stat that modifiers can change.
+#[derive(Copy, Clone, Eq, PartialEq, Debug, Serialize, Deserialize)]
+pub enum Stat {
+    Power,
+    Attack,
+    Defense,
+    Armor,
+    Mana,
+    RangedRange,
+    RangedPower,
+    Speed,
+    Light,
+}
+
+impl Stat {
+    pub fn iter() -> slice::Iter<'static, Stat> {
+        use Stat::*;
+        const STATS: [Stat; 9] =
+            [Power, Attack, Defense, Armor, Mana, RangedRange, RangedPower, Speed, Light];
+        STATS.iter()
+    }
+
+    pub fn name(self) -> &'static str {
+        use Stat::*;
+        match self {
+            Power => "power",
+            Attack => "attack",
+            Defense => "defense",
+            Armor => "armor",
+            Mana => "mana",
+            RangedRange => "range",
+            RangedPower => "ranged power",
+            Speed => "speed",
+            Light => "light",
+        }
+    }
+
+    pub fn get(self, stats: &Stats) -> i32 {
+        use Stat::*;
+        match self {
+            Power => stats.base_power,
+            Attack => stats.base_attack,
+            Defense => stats.base_defense,
+            Armor => stats.armor,
+            Mana => stats.mana,
+            RangedRange => stats.ranged_range as i32,
+            RangedPower => stats.ranged_power,
+            Speed => stats.speed,
+            Light => stats.light,
+        }
+    }
+
+    fn set(self, stats: &mut Stats, value: i32) {
+        use Stat::*;
+        match self {
+            Power => stats.base_power = value,
+            Attack => stats.base_attack = value,
+            Defense => stats.base_defense = value,
+            Armor => stats.armor = value,
+            Mana => stats.mana = value,
+            RangedRange => stats.ranged_range = value.max(0) as u32,
+            RangedPower => stats.ranged_power = value,
+            Speed => stats.speed = value,
+            Light => stats.light = value,
+        }
+    }
+}
+
+/// How a modifier changes a stat.
+///
+/// All additive modifiers are applied first, then the multiplicative ones and finally the
+/// minimums.
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+pub enum ModifierOp {
+    /// Add to the stat.
+    Add(i32),
+    /// Scale the stat by a percentage.
+    Percent(i32),
+    /// Raise the stat to at least the value.
+    ///
+    /// Used for stats that replace each other instead of adding up, like the range of a bow and a
+    /// natural ranged attack.
+    Max(i32),
+}
+
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+pub struct Modifier {
+    pub stat: Stat,
+    pub op: ModifierOp,
+}
+
+impl Modifier {
+    pub fn new(stat: Stat, op: ModifierOp) -> Modifier { Modifier { stat, op } }
+}
+
+/// Where a stat modifier comes from.
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+pub enum ModifierSource {
+    Level,
+    Intrinsic(Intrinsic),
+    Item(Entity),
+    Status(Status),
+    Hunger(Hunger),
+}
+
+/// How the value of a stat was derived.
+#[derive(Clone, Eq, PartialEq, Debug)]
+pub struct StatBreakdown {
+    pub stat: Stat,
+    /// Value from the entity's base stats.
+    pub base: i32,
+    /// Modifiers in the order they were applied.
+    pub modifiers: Vec<(ModifierSource, ModifierOp)>,
+    /// Final value of the stat.
+    pub total: i32,
+}
+
+impl Intrinsic {
+    /// Return the stat modifiers of the intrinsic.
+    pub fn modifiers(self) -> &'static [Modifier] {
+        use {ModifierOp::*, Stat::*};
+        match self {
+            Intrinsic::Slow => &[Modifier {
+                stat: Speed,
+                op: Add(-1),
+            }],
+            Intrinsic::Quick => &[Modifier {
+                stat: Speed,
+                op: Add(1),
+            }],
+            _ => &[],
+        }
+    }
+
+    fn iter() -> slice::Iter<'static, Intrinsic> {
+        use Intrinsic::*;
+        const INTRINSICS: [Intrinsic; 7] = [
+            Slow,
+            Quick,
+            Hands,
+            Deathsplosion,
+            Hyperactive,
+            PoisonImmunity,
+            FireImmunity,
+        ];
+        INTRINSICS.iter()
+    }
+}
+
+/// Apply modifiers to a stat value.
+fn apply(stat: Stat, base: i32, modifiers: &[(ModifierSource, Modifier)]) -> i32 {
+    let ops = || modifiers.iter().filter(|(_, m)| m.stat == stat).map(|(_, m)| m.op);
+    let mut value = base;
+    for op in ops() {
+        if let ModifierOp::Add(x) = op {
+            value += x;
+        }
+    }
+    for op in ops() {
+        if let ModifierOp::Percent(x) = op {
+            value = value * x / 100;
+        }
+    }
+    for op in ops() {
+        if let ModifierOp::Max(x) = op {
+            value = value.max(x);
+        }
+    }
+    value
+}
+
+/// Sort key that puts modifiers in the order they get applied.
+fn op_order(op: ModifierOp) -> u32 {
+    match op {
+        ModifierOp::Add(_) => 0,
+        ModifierOp::Percent(_) => 1,
+        ModifierOp::Max(_) => 2,
+    }
+}
+
+impl World {
+    /// Return the stat modifiers that come from an item equipped by a creature.
+    fn item_modifiers(&self, item: Entity) -> Vec<Modifier> {
+        let stats = self.stats(item);
+        Stat::iter()
+            .filter_map(|&stat| {
+                let value = stat.get(&stats);
+                if value == 0 {
+                    return None;
+                }
+                let op = match stat {
+                    // Don't add up the ranges of several ranged attacks or light sources.
+                    Stat::RangedRange | Stat::Light => ModifierOp::Max(value),
+                    _ => ModifierOp::Add(value),
+                };
+                Some(Modifier::new(stat, op))
+            })
+            .collect()
+    }
+
+    /// Return all the modifiers that apply to the stats of an entity.
+    pub(crate) fn stat_modifiers(&self, e: Entity) -> Vec<(ModifierSource, Modifier)> {
+        use ModifierOp::*;
+        let mut ret = Vec::new();
+        let base = self.base_stats(e);
+
+        if base.level != 0 {
+            for &stat in &[Stat::Power, Stat::Attack, Stat::Defense] {
+                ret.push((ModifierSource::Level, Modifier::new(stat, Add(base.level * 2))));
+            }
+        }
+
+        let mut intrinsics = base.intrinsics;
+        for &slot in Slot::equipment_iter() {
+            if let Some(item) = self.entity_equipped(e, slot) {
+                intrinsics |= self.stats(item).intrinsics;
+                for m in self.item_modifiers(item) {
+                    ret.push((ModifierSource::Item(item), m));
+                }
+            }
+        }
+
+        for &intrinsic in Intrinsic::iter() {
+            if intrinsics & (1 << intrinsic as u32) != 0 {
+                for &m in intrinsic.modifiers() {
+                    ret.push((ModifierSource::Intrinsic(intrinsic), m));
+                }
+            }
+        }
+
+        for (status, _) in self.active_statuses(e) {
+            for &m in status.data().modifiers {
+                ret.push((ModifierSource::Status(status), m));
+            }
+        }
+
+        if let Some(hunger) = self.hunger(e) {
+            for &m in hunger.modifiers() {
+                ret.push((ModifierSource::Hunger(hunger), m));
+            }
+        }
+
+        ret
+    }
+
+    /// Compute the derived stats of an entity from its base stats and modifiers.
+    pub(crate) fn derive_stats(&self, e: Entity) -> Stats {
+        let mut stats = self.base_stats(e);
+
+        // Flags from items just get merged in.
+        for &slot in Slot::equipment_iter() {
+            if let Some(item) = self.entity_equipped(e, slot) {
+                let item = self.stats(item);
+                stats.intrinsics |= item.intrinsics;
+                stats.resistances |= item.resistances;
+                stats.vulnerabilities |= item.vulnerabilities;
+            }
+        }
+
+        let modifiers = self.stat_modifiers(e);
+        for &stat in Stat::iter() {
+            let value = apply(stat, stat.get(&stats), &modifiers);
+            stat.set(&mut stats, value);
+        }
+        stats
+    }
+
+    /// Explain where the value of a stat of an entity comes from.
+    pub fn stat_breakdown(&self, e: Entity, stat: Stat) -> StatBreakdown {
+        let base = stat.get(&self.base_stats(e));
+        let mut modifiers: Vec<_> = self
+            .stat_modifiers(e)
+            .into_iter()
+            .filter(|(_, m)| m.stat == stat)
+            .collect();
+        modifiers.sort_by_key(|(_, m)| op_order(m.op));
+
+        StatBreakdown {
+            stat,
+            base,
+            total: apply(stat, base, &modifiers),
+            modifiers: modifiers.into_iter().map(|(s, m)| (s, m.op)).collect(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{apply, Modifier, ModifierOp::*, ModifierSource, Stat};
+    use crate::{stats::Intrinsic, status::Status, World, WorldSeed};
+
+    #[test]
+    fn test_modifier_order() {
+        let modifiers = vec![
+            (ModifierSource::Level, Modifier::new(Stat::Attack, Percent(50))),
+            (ModifierSource::Level, Modifier::new(Stat::Attack, Add(6))),
+            (
+                ModifierSource::Intrinsic(Intrinsic::Quick),
+                Modifier::new(Stat::Speed, Add(1)),
+            ),
+            (ModifierSource::Status(Status::Stunned), Modifier::new(Stat::Attack, Max(10))),
+        ];
+
+        // (4 + 6) * 50% = 5, raised to 10.
+        assert_eq!(apply(Stat::Attack, 4, &modifiers), 10);
+        // (20 + 6) * 50% = 13.
+        assert_eq!(apply(Stat::Attack, 20, &modifiers), 13);
+        assert_eq!(apply(Stat::Speed, 0, &modifiers), 1);
+        assert_eq!(apply(Stat::Power, 7, &modifiers), 7);
+    }
+
+    #[test]
+    fn test_stat_breakdown() {
+        let mut world = World::new(&WorldSeed::test(1));
+        let player = world.player().unwrap();
+        assert_eq!(world.speed(player), 3);
+
+        world.gain_status(player, Status::Hasted, 10);
+        world.ecs_mut().stats[player].base.add_intrinsic(Intrinsic::Quick);
+        world.rebuild_stats(player);
+        assert_eq!(world.speed(player), 5);
+
+        let breakdown = world.stat_breakdown(player, Stat::Speed);
+        assert_eq!(breakdown.base, 0);
+        assert_eq!(breakdown.total, 2);
+        assert_eq!(
+            breakdown.modifiers,
+            vec![
+                (ModifierSource::Intrinsic(Intrinsic::Quick), Add(1)),
+                (ModifierSource::Status(Status::Hasted), Add(1)),
+            ]
+        );
+
+        for _ in 0..10 {
+            world.tick_statuses(player);
+        }
+        assert_eq!(world.speed(player), 4);
+    }
+}
@@ -1,15 +1,19 @@
 //! Logic for movement and game world space
 use crate::{
-    stats::{Intrinsic, Status},
+    ai::Action,
+    noise,
+    stats::Intrinsic,
+    status::Status,
     ActionOutcome, Location, Sector, World,
 };
-use calx::{Clamp, Dir6, RngExt};
+use calx::{Dir6, RngExt};
 use calx_ecs::Entity;
 use rand::Rng;
 
 impl World {
     /// Mark an entity as dead, but don't remove it from the system yet.
     pub(crate) fn kill_entity(&mut self, e: Entity) {
+        self.forget_light_of(e);
         if self.count(e) > 1 {
             self.ecs_mut().stacking[e].count -= 1;
         } else {
@@ -21,17 +25,23 @@ impl World {
     ///
     /// You generally do not want to call this directly. Mark the entity as dead and it will be
     /// removed at the end of the turn.
-    pub(crate) fn remove_entity(&mut self, e: Entity) { self.ecs.remove(e); }
+    pub(crate) fn remove_entity(&mut self, e: Entity) {
+        self.forget_flow_field(e);
+        self.ecs.remove(e);
+    }
 
     pub(crate) fn place_entity(&mut self, e: Entity, mut loc: Location) {
         if self.is_item(e) {
-            loc = self.empty_item_drop_location(loc);
+            loc = self.item_drop_location(loc);
         }
         self.set_entity_location(e, loc);
         self.after_entity_moved(e);
     }
 
-    pub(crate) fn after_entity_moved(&mut self, e: Entity) { self.do_fov(e); }
+    pub(crate) fn after_entity_moved(&mut self, e: Entity) {
+        self.forget_light_of(e);
+        self.do_fov(e);
+    }
 
     pub(crate) fn entity_step(&mut self, e: Entity, dir: Dir6) -> ActionOutcome {
         if self.confused_move(e) {
@@ -46,8 +56,13 @@ impl World {
         let loc = origin.jump(self, dir);
         if self.can_enter(e, loc) {
             self.place_entity(e, loc);
+            if self.terrain(loc).is_door() {
+                self.mob_noise(e, noise::DOOR_NOISE);
+            } else {
+                self.mob_noise(e, noise::FOOTSTEP_NOISE);
+            }
 
-            let delay = self.action_delay(e);
+            let delay = self.action_delay(e, Action::Move);
             debug_assert!(delay > 0);
             let anim_tick = self.get_anim_tick();
             if let Some(anim) = self.ecs_mut().anim.get_mut(e) {
@@ -55,7 +70,7 @@ impl World {
                 anim.tween_start = anim_tick;
                 anim.tween_duration = delay;
             }
-            self.end_turn(e);
+            self.end_turn(e, Action::Move);
             return Some(true);
         }
 
@@ -177,26 +192,13 @@ impl World {
 
     pub fn is_underground(&self, loc: Location) -> bool { loc.z < 0 }
 
-    pub fn light_level(&self, loc: Location) -> f32 {
-        // Lit terrain is lit.
-        if self.terrain(loc).is_luminous() {
-            return 1.0;
-        }
-
-        // In dark arears, far-away things are dim.
-        if self.is_underground(loc) {
-            if let Some(player) = self.player() {
-                if let Some(player_loc) = self.location(player) {
-                    // XXX: This is going to get so messed up with portals, should be done in
-                    // player chart space, not here...
-                    if let Some(dist) = player_loc.distance_from(loc) {
-                        return (0.0..=1.0).clamp(1.0 - (dist as f32 / 8.0));
-                    }
-                }
-            }
-        }
-
-        // Otherwise things are bright.
-        1.0
+    /// Return how deep in the world the location is.
+    ///
+    /// Deeper locations have more dangerous monsters and better loot.
+    pub fn depth(&self, loc: Location) -> i32 {
+        self.world_cache
+            .sector_depth(Sector::from(loc))
+            .unwrap_or(0)
+            .max(0)
     }
 }
@@ -1,4 +1,5 @@
 use crate::grammar;
+use std::cell::RefCell;
 
 /// Message receiver that is implemented in client
 pub trait MsgReceiver: Sync + Send {
@@ -14,6 +15,22 @@ pub fn register_msg_receiver(receiver: Box<dyn MsgReceiver>) {
     }
 }
 
+thread_local! {
+    /// Message buffer that overrides the registered receiver when set.
+    static CAPTURE: RefCell<Option<Vec<String>>> = const { RefCell::new(None) };
+}
+
+/// Run a function and collect the messages it emits instead of passing them to the receiver.
+///
+/// Capturing is thread-local, so headless worlds running in parallel (eg. in unit tests) won't
+/// see each other's messages.
+pub fn capture_msgs<T>(f: impl FnOnce() -> T) -> (T, Vec<String>) {
+    let outer = CAPTURE.with(|c| c.borrow_mut().replace(Vec::new()));
+    let ret = f();
+    let msgs = CAPTURE.with(|c| std::mem::replace(&mut *c.borrow_mut(), outer));
+    (ret, msgs.unwrap_or_default())
+}
+
 pub(crate) fn grammatize(fmt: &str, elements: &[grammar::GrammarPart]) -> String {
     // Because of the macro system, element set is a loose bag of stuff. Expecting to find Subject
     // and Object in there if they are used in the message.
@@ -36,8 +53,19 @@ pub(crate) fn grammatize(fmt: &str, elements: &[grammar::GrammarPart]) -> String
 }
 
 pub(crate) fn dispatch_msg(msg: &str) {
-    unsafe {
-        MSG_RECEIVER.msg(msg);
+    let captured = CAPTURE.with(|c| {
+        if let Some(buf) = c.borrow_mut().as_mut() {
+            buf.push(msg.to_string());
+            true
+        } else {
+            false
+        }
+    });
+
+    if !captured {
+        unsafe {
+            MSG_RECEIVER.msg(msg);
+        }
     }
 }
 
@@ -1,17 +1,25 @@
 //! Gameplay logic that changes things
 
 use crate::{
-    ai::Brain,
-    effect::{Damage, Effect},
-    msg,
-    sector::SECTOR_WIDTH,
-    stats::Status,
+    ai::{Action, Brain},
+    desc::{Desc, Icon},
+    effect::{AbilityData, Damage, DeathTrigger, Effect, Targeting},
+    hunger,
+    item::{Item, ItemType},
+    msg, noise,
+    stats::Intrinsic,
+    status::Status,
     volume::Volume,
-    Ability, ActionOutcome, Anim, AnimState, Ecs, ExternalEntity, Location, Slot, World,
+    world::Loadout,
+    Ability, ActionOutcome, Anim, AnimState, Ecs, ExternalEntity, Location, Slot, Terrain, World,
 };
-use calx::Dir6;
+use calx::{Dir6, RngExt};
 use calx_ecs::Entity;
-use rand::seq::SliceRandom;
+use rand::{seq::SliceRandom, Rng};
+use std::iter;
+
+/// Number of animation frames a missile takes to reach its destination.
+const PROJECTILE_TIME: u32 = 8;
 
 /// World-mutating methods that are not exposed outside the crate.
 impl World {
@@ -21,10 +29,21 @@ impl World {
         self.tick_anims();
 
         self.ai_main();
+        self.tick_fire();
+        self.tick_clock();
 
         self.clean_dead();
         self.flags.tick += 1;
 
+        // Update what the player sees in the dark if light sources have changed.
+        if let Some(player) = self.player() {
+            if self.light_changed()
+                && self.location(player).is_some_and(|loc| self.is_underground(loc))
+            {
+                self.do_fov(player);
+            }
+        }
+
         // Expiring entities (animation effects) disappear if their time is up.
         let es: Vec<Entity> = self.ecs.anim.ent_iter().cloned().collect();
         for e in es.into_iter() {
@@ -41,6 +60,8 @@ impl World {
     }
 
     pub(crate) fn equip_item(&mut self, e: Entity, parent: Entity, slot: Slot) {
+        // A light source picked up from the floor stops shining.
+        self.forget_light_of(e);
         self.spatial.equip(e, parent, slot);
         self.rebuild_stats(parent);
     }
@@ -57,16 +78,23 @@ impl World {
 
         if let Some(origin) = self.location(e) {
             const DEFAULT_FOV_RANGE: i32 = 7;
-            const OVERLAND_FOV_RANGE: i32 = SECTOR_WIDTH;
 
-            // Long-range sight while in overworld.
+            // Long-range sight while in overworld, shorter at night.
             let range = if self.is_underground(origin) {
                 DEFAULT_FOV_RANGE
             } else {
-                OVERLAND_FOV_RANGE
+                self.surface_sight_range()
             };
 
-            let fov = self.fov_from(origin, range);
+            // Only cells that are lit or right next to the viewer can be seen.
+            let fov: Vec<Location> = self
+                .fov_from(origin, range)
+                .into_iter()
+                .filter(|&loc| {
+                    self.light_level(loc) > 0.0
+                        || origin.distance_from(loc).is_some_and(|d| d <= 1)
+                })
+                .collect();
 
             let memory = &mut self.ecs.map_memory[e];
             memory.seen.clear();
@@ -78,6 +106,90 @@ impl World {
         }
     }
 
+    /// Change the terrain at a location.
+    ///
+    /// Setting the terrain back to the generated one removes the override.
+    pub(crate) fn set_terrain(&mut self, loc: Location, terrain: Terrain) {
+        self.override_terrain(loc, terrain);
+
+        // Paths, sight lines and glowing terrain may have changed.
+        self.forget_flow_fields();
+        self.forget_light();
+        self.refresh_fov();
+    }
+
+    /// Change the terrain at a location without updating the caches that depend on it.
+    ///
+    /// For batching many changes, the caller must update the caches like `set_terrain` does.
+    pub(crate) fn override_terrain(&mut self, loc: Location, terrain: Terrain) {
+        if self.world_cache.get_terrain(loc) == terrain {
+            self.terrain_overrides.remove(&loc);
+        } else {
+            self.terrain_overrides.insert(loc, terrain);
+        }
+    }
+
+    /// Recompute the field of view of everything that sees after a change in what blocks sight.
+    pub(crate) fn refresh_fov(&mut self) {
+        let viewers: Vec<Entity> = self.ecs.map_memory.ent_iter().cloned().collect();
+        for e in viewers {
+            self.do_fov(e);
+        }
+    }
+
+    /// Return whether the entity can dig through the terrain in direction.
+    pub fn can_dig(&self, e: Entity, dir: Dir6) -> bool {
+        match self.location(e) {
+            Some(loc) => self.terrain(loc.jump(self, dir)).is_diggable(),
+            None => false,
+        }
+    }
+
+    /// Dig a passage through the terrain in direction.
+    pub(crate) fn dig(&mut self, e: Entity, dir: Dir6) -> ActionOutcome {
+        if !self.can_dig(e, dir) {
+            return None;
+        }
+        let target = self.location(e)?.jump(self, dir);
+        msg!("[One] dig[s] through the {}.", self.terrain(target).name(); self.subject(e));
+        self.set_terrain(target, Terrain::Ground);
+        self.mob_noise(e, noise::DIG_NOISE);
+        self.end_turn(e, Action::Dig);
+        Some(true)
+    }
+
+    /// Return whether the entity can open or close the door in direction.
+    pub fn can_toggle_door(&self, e: Entity, dir: Dir6) -> bool {
+        let target = match self.location(e) {
+            Some(loc) => loc.jump(self, dir),
+            None => return false,
+        };
+        match self.unoccupied_terrain(target) {
+            Terrain::Door => true,
+            // Can't close a door on something that's in the way.
+            Terrain::OpenDoor => self.entities_at(target).is_empty(),
+            _ => false,
+        }
+    }
+
+    /// Open or close the door in direction.
+    pub(crate) fn toggle_door(&mut self, e: Entity, dir: Dir6) -> ActionOutcome {
+        if !self.can_toggle_door(e, dir) {
+            return None;
+        }
+        let target = self.location(e)?.jump(self, dir);
+        if self.unoccupied_terrain(target) == Terrain::Door {
+            msg!("[One] open[s] the door."; self.subject(e));
+            self.set_terrain(target, Terrain::OpenDoor);
+        } else {
+            msg!("[One] close[s] the door."; self.subject(e));
+            self.set_terrain(target, Terrain::Door);
+        }
+        self.mob_noise(e, noise::DOOR_NOISE);
+        self.end_turn(e, Action::Operate);
+        Some(true)
+    }
+
     /// Access the persistent random number generator.
     pub(crate) fn rng(&mut self) -> &mut crate::Rng { &mut self.rng }
 
@@ -128,6 +240,16 @@ impl World {
         }
     }
 
+    pub(crate) fn entity_fire(&mut self, e: Entity, dir: Dir6) -> ActionOutcome {
+        // Confused shooters fumble their aim.
+        let dir = if self.has_status(e, Status::Confused) && self.rng().one_chance_in(3) {
+            self.rng().gen()
+        } else {
+            dir
+        };
+        self.really_fire(e, dir)
+    }
+
     /// The entity spends its action waiting.
     pub(crate) fn idle(&mut self, e: Entity) -> ActionOutcome {
         if self.consume_nutrition(e) {
@@ -135,7 +257,7 @@ impl World {
                 // TODO: animate/message the healing.
             }
         }
-        self.end_turn(e);
+        self.end_turn(e, Action::Wait);
         Some(true)
     }
 
@@ -181,9 +303,25 @@ impl World {
             Hit { amount, damage } => {
                 self.damage(target, amount as i32, damage, source);
             }
-            Confuse => {
-                self.gain_status(target, Status::Confused, 40);
-                msg!("[One] [is] confused."; self.subject(target));
+            Inflict(status, duration) => {
+                self.gain_status(target, status, duration);
+            }
+            Heal(amount) => {
+                self.heal(target, amount as i32);
+            }
+            Charm => {
+                if let Some(alignment) = source.and_then(|e| self.alignment(e)) {
+                    if self.is_npc(target) && self.alignment(target) != Some(alignment) {
+                        self.set_allegiance(target, alignment);
+                        msg!("[One] [is] charmed."; self.subject(target));
+                    }
+                }
+            }
+            Identify => {
+                // Only the player keeps track of what items are.
+                if self.is_player(target) {
+                    self.identify_carried(target);
+                }
             }
         }
     }
@@ -197,6 +335,13 @@ impl World {
         if let Some(mob) = self.mob_at(loc) {
             self.apply_effect_to_entity(effect, mob, source);
         }
+        if let Effect::Hit {
+            damage: Damage::Fire,
+            ..
+        } = effect
+        {
+            self.ignite(loc);
+        }
     }
 
     pub(crate) fn apply_effect(
@@ -210,128 +355,409 @@ impl World {
         }
     }
 
+    /// Kill a creature and run its on-death triggers.
+    pub(crate) fn die(&mut self, e: Entity) {
+        let loc = self.location(e);
+        let mut triggers = self.ecs().on_death.get(e).cloned().unwrap_or_default();
+        if self.has_intrinsic(e, Intrinsic::Deathsplosion) {
+            triggers.push(DeathTrigger::Explode {
+                radius: 1,
+                amount: self.power(e).max(1) as u32,
+                damage: Damage::Fire,
+            });
+        }
+        let name = self.entity_name(e);
+        let corpse_nutrition = (self.max_hp(e) * 500).min(hunger::MAX_NUTRITION / 2);
+        let brain_state = self.brain_state(e);
+
+        // Items need to be dropped before the creature is removed along with its contents.
+        if let Some(loc) = loc {
+            if triggers.contains(&DeathTrigger::DropItems) {
+                for (_, item) in self.entities_in(e) {
+                    self.drop_item(item, loc);
+                }
+            }
+        }
+        self.kill_entity(e);
+
+        // The rest of the triggers happen where the creature died.
+        let loc = match loc {
+            Some(loc) => loc,
+            None => return,
+        };
+        for trigger in triggers {
+            match trigger {
+                DeathTrigger::DropItems => {}
+                DeathTrigger::Loot(table) => self.drop_loot(&table, loc),
+                DeathTrigger::Corpse => {
+                    let desc = Desc::new(&format!("{} corpse", name), Icon::Corpse);
+                    let item = Item {
+                        item_type: ItemType::Food(corpse_nutrition),
+                        charges: 1,
+                        cursed: false,
+                        requirements: Vec::new(),
+                    };
+                    self.spawn(&ExternalEntity::new(Loadout::default().c(desc).c(item)), loc);
+                }
+                DeathTrigger::Explode {
+                    radius,
+                    amount,
+                    damage,
+                } => {
+                    let volume = Volume::sphere(self, loc, radius);
+                    for &pt in &volume.0 {
+                        self.spawn_fx(pt, AnimState::Explosion);
+                    }
+                    self.apply_effect(&Effect::Hit { amount, damage }, &volume, None);
+                    self.make_noise(loc, noise::EXPLOSION_NOISE, None);
+                }
+                DeathTrigger::Spawn { name, count } => {
+                    let spots: Vec<Location> = iter::once(loc)
+                        .chain(Dir6::iter().map(|&dir| loc.jump(self, dir)))
+                        .filter(|&loc| !self.blocks_walk(loc))
+                        .take(count as usize)
+                        .collect();
+                    for spot in spots {
+                        let spawn = match ExternalEntity::sample_from_name(self.rng(), &name) {
+                            Ok(spawn) => spawn,
+                            Err(_) => continue,
+                        };
+                        let new = self.spawn(&spawn, spot);
+                        // Offspring carry on what the parent was doing.
+                        if let (Some(state), Some(brain)) =
+                            (brain_state, self.ecs_mut().brain.get_mut(new))
+                        {
+                            brain.state = state;
+                        }
+                    }
+                }
+            }
+        }
+    }
+
     /// Run autonomous updates on entity that happen each turn
     ///
     /// This runs regardless of the action speed or awakeness status of the entity. The exact same
     /// is run for player and AI entities.
-    pub(crate) fn heartbeat(&mut self, e: Entity) { self.tick_statuses(e); }
-
-    pub(crate) fn use_ability(&mut self, _e: Entity, _a: Ability) -> ActionOutcome {
-        // TODO
-        None
+    pub(crate) fn heartbeat(&mut self, e: Entity) {
+        self.tick_statuses(e);
+        self.recover_energy(e);
+        self.tick_mana_recovery(e);
+        self.tick_hunger(e);
+        self.douse(e);
     }
 
-    pub(crate) fn use_item_ability(
+    /// Use an ability, either an innate one or one invoked through an item.
+    ///
+    /// Targeted abilities need a direction, untargeted ones must not be given one.
+    pub(crate) fn use_ability(
         &mut self,
         e: Entity,
-        item: Entity,
         a: Ability,
+        dir: Option<Dir6>,
+        item: Option<Entity>,
     ) -> ActionOutcome {
-        debug_assert!(!a.is_targeted());
-        // TODO: Lift to generic ability use method
-        if !self.has_ability(item, a) {
+        if a.is_targeted() != dir.is_some() {
             return None;
         }
+        let data = a.data();
+        match item {
+            Some(item) => {
+                if !self.entity_contains(e, item) || !self.has_ability(item, a) {
+                    return None;
+                }
+            }
+            None => {
+                if !self.has_ability(e, a) {
+                    return None;
+                }
+                // Innate abilities are spells that are powered by mana.
+                if self.mana(e) < data.cost.mana {
+                    if self.is_player(e) {
+                        msg!("You don't have enough mana.");
+                    }
+                    return None;
+                }
+            }
+        }
         let origin = self.location(e)?;
 
-        match a {
-            Ability::LightningBolt => {
-                const LIGHTNING_RANGE: u32 = 4;
-                const LIGHTNING_EFFECT: Effect = Effect::Hit {
-                    amount: 12,
-                    damage: Damage::Electricity,
-                };
-
+        let center = match data.targeting {
+            Targeting::User => Some(origin),
+            Targeting::RandomMob => {
                 // TODO: Make an API, more efficient lookup of entities within an area
-
                 let targets: Vec<Entity> = self
-                    .sphere_volume(origin, LIGHTNING_RANGE)
+                    .sphere_volume(origin, data.range)
                     .0
                     .into_iter()
                     .flat_map(|loc| self.entities_at(loc))
                     .filter(|&x| self.is_mob(x) && x != e)
                     .collect();
+                targets.choose(self.rng()).and_then(|&t| self.location(t))
+            }
+            Targeting::Projectile => {
+                Some(self.projected_explosion_center(origin, dir?, data.range))
+            }
+        };
 
-                if let Some(target) = targets.choose(self.rng()) {
-                    msg!("There is a peal of thunder.");
-                    let loc = self.location(*target).unwrap();
-                    self.apply_effect(&LIGHTNING_EFFECT, &Volume::point(loc), Some(e));
-                } else {
-                    msg!("The spell fizzles.");
+        // Using an item shows what it does.
+        if let Some(item) = item {
+            if self.is_player(e) {
+                self.identify(item);
+            }
+        }
+
+        if let Some(center) = center {
+            if let Some(text) = data.use_msg {
+                msg!(text);
+            }
+            let volume = data.shape.volume(self, center);
+            for effect in data.effects {
+                self.apply_effect(effect, &volume, Some(e));
+            }
+            self.spawn_ability_fx(&data, origin, center, &volume);
+            self.make_noise(center, data.noise, Some(e));
+        } else {
+            msg!("The spell fizzles.");
+        }
+
+        match item {
+            Some(item) => {
+                for _ in 0..data.cost.charges {
+                    self.drain_charge(item);
                 }
             }
-            _ => {
-                msg!("TODO cast untargeted spell {:?}", a);
+            None => {
+                self.spend_mana(e, data.cost.mana);
             }
         }
-        self.drain_charge(item);
+        self.end_turn(e, Action::Cast);
         Some(true)
     }
 
-    pub(crate) fn use_targeted_ability(
+    fn spawn_ability_fx(
         &mut self,
-        _e: Entity,
-        _a: Ability,
-        _dir: Dir6,
-    ) -> ActionOutcome {
-        // TODO
-        None
+        data: &AbilityData,
+        origin: Location,
+        center: Location,
+        volume: &Volume,
+    ) {
+        // Impact effects start when the missile arrives.
+        let delay = if data.missile_anim.is_some() {
+            PROJECTILE_TIME as u64
+        } else {
+            0
+        };
+
+        if let Some(state) = data.impact_anim {
+            for &pt in &volume.0 {
+                let fx = self.spawn_fx(pt, state);
+                self.anim_mut(fx).unwrap().anim_start += delay;
+            }
+        }
+
+        if let Some(state) = data.missile_anim {
+            self.spawn_missile_fx(origin, center, state);
+        }
     }
 
-    pub(crate) fn use_targeted_item_ability(
+    /// Spawn an effect that flies from origin to destination.
+    pub(crate) fn spawn_missile_fx(
         &mut self,
-        e: Entity,
-        item: Entity,
-        a: Ability,
-        dir: Dir6,
-    ) -> ActionOutcome {
-        debug_assert!(a.is_targeted());
-        if !self.has_ability(item, a) {
-            return None;
-        }
-        let origin = self.location(e)?;
+        origin: Location,
+        destination: Location,
+        state: AnimState,
+    ) -> Entity {
+        let anim_tick = self.get_anim_tick();
+        let missile = self.spawn_fx(destination, state);
+        let anim = self.anim_mut(missile).unwrap();
+        anim.tween_from = origin;
+        anim.tween_start = anim_tick;
+        anim.tween_duration = PROJECTILE_TIME;
+        missile
+    }
+}
 
-        // TODO: Lift to generic ability use method
-
-        match a {
-            Ability::Fireball => {
-                const FIREBALL_RANGE: u32 = 9;
-                const FIREBALL_RADIUS: u32 = 1;
-                const FIREBALL_EFFECT: Effect = Effect::Hit {
-                    amount: 6,
-                    damage: Damage::Fire,
-                };
-                let center = self.projected_explosion_center(origin, dir, FIREBALL_RANGE);
-                let volume = self.sphere_volume(center, FIREBALL_RADIUS);
-                self.apply_effect(&FIREBALL_EFFECT, &volume, Some(e));
-
-                // TODO: Maybe move anim generation to own procedure?
-                const PROJECTILE_TIME: u64 = 8;
-                for &pt in &volume.0 {
-                    let fx = self.spawn_fx(pt, AnimState::Explosion);
-                    self.anim_mut(fx).unwrap().anim_start += PROJECTILE_TIME;
-                }
+#[cfg(test)]
+mod test {
+    use crate::{
+        effect::Damage, Ability, Command, ExternalEntity, Location, Slot, Terrain, World,
+        WorldSeed,
+    };
+    use calx::Dir6;
+
+    /// Find an open location next to the player.
+    fn open_spot(world: &World) -> Location {
+        let origin = world.location(world.player().unwrap()).unwrap();
+        Dir6::iter()
+            .map(|&dir| origin.jump(world, dir))
+            .find(|&loc| !world.blocks_walk(loc))
+            .expect("Player is boxed in")
+    }
 
-                let anim_tick = self.get_anim_tick();
-                let projectile = self.spawn_fx(center, AnimState::Firespell);
-                {
-                    let anim = self.anim_mut(projectile).unwrap();
-                    anim.tween_from = origin;
-                    anim.tween_start = anim_tick;
-                    anim.tween_duration = PROJECTILE_TIME as u32;
-                }
-            }
-            Ability::Confuse => {
-                const CONFUSION_RANGE: u32 = 9;
+    #[test]
+    fn test_death_triggers() {
+        let mut world = World::new(&WorldSeed::test(1));
+        let loc = open_spot(&world);
+
+        // Ogres drop their stuff and leave a corpse.
+        let ogre = world.spawn(&ExternalEntity::from_name("ogre").unwrap(), loc);
+        let sword = world.spawn(&ExternalEntity::from_name("sword").unwrap(), loc);
+        world.entity_take(ogre, sword);
+        assert!(world.entity_contains(ogre, sword));
+        world.damage(ogre, 1000, Damage::Physical, None);
+        assert!(!world.is_alive(ogre));
+        assert!(world.is_alive(sword));
+        assert!(world
+            .entities_at(loc)
+            .into_iter()
+            .any(|e| world.entity_name(e) == "ogre corpse"));
+
+        // Gelatinous cubes split into oozes.
+        let cube = world.spawn(&ExternalEntity::from_name("gelatinous cube").unwrap(), loc);
+        world.damage(cube, 1000, Damage::Physical, None);
+        assert!(!world.is_alive(cube));
+        let oozes = world
+            .entities()
+            .filter(|&&e| world.is_alive(e) && world.entity_name(e) == "ooze")
+            .count();
+        assert_eq!(oozes, 2);
+
+        // Unique monsters always drop their treasure.
+        let moloch = world.spawn(&ExternalEntity::from_name("moloch").unwrap(), loc);
+        world.damage(moloch, 1000, Damage::Physical, None);
+        assert!(!world.is_alive(moloch));
+        assert!(world
+            .entities()
+            .any(|&e| world.is_alive(e) && world.spawn_name(e) == Some("wand of fireball")));
+
+        // A creature that isn't on the map still dies, it just doesn't leave anything behind.
+        let ogre = world.spawn(&ExternalEntity::from_name("ogre").unwrap(), loc);
+        let sack = world.spawn(&ExternalEntity::from_name("sword").unwrap(), loc);
+        world.spatial.remove(sack);
+        world.spatial.equip(ogre, sack, Slot::Bag(0));
+        assert_eq!(world.location(ogre), None);
+        let corpses = |world: &World| {
+            world.entities().filter(|&&e| world.entity_name(e) == "ogre corpse").count()
+        };
+        let before = corpses(&world);
+        world.damage(ogre, 1000, Damage::Physical, None);
+        assert!(!world.entity_contains(sack, ogre));
+        assert_eq!(corpses(&world), before);
+    }
 
-                let center = self.projected_explosion_center(origin, dir, CONFUSION_RANGE);
-                self.apply_effect(&Effect::Confuse, &Volume::point(center), Some(e));
-            }
-            _ => {
-                msg!("TODO cast directed spell {:?}", a);
-            }
+    #[test]
+    fn test_fire() {
+        let mut world = World::new(&WorldSeed::test(1));
+        let player = world.player().unwrap();
+        let origin = world.location(player).unwrap();
+        let dir = Dir6::North;
+
+        // Clear a short shooting lane that ends in a wall.
+        let lane: Vec<Location> = (1..=4).map(|n| origin + dir.to_v2() * n).collect();
+        for &loc in &lane[..3] {
+            world.set_terrain(loc, Terrain::Ground);
         }
-        self.drain_charge(item);
-        Some(true)
+        world.set_terrain(lane[3], Terrain::Wall);
+        assert_eq!(world.trace_projectile(origin, dir, 6), (lane[2], None));
+
+        let spawn = |world: &mut World, name: &str| {
+            world.spawn(&ExternalEntity::from_name(name).unwrap(), origin)
+        };
+        let bow = spawn(&mut world, "bow");
+        world.entity_take(player, bow);
+        world.equip_item(bow, player, Slot::Ranged);
+        assert!(!world.can_fire(player));
+        let arrows = spawn(&mut world, "arrow");
+        world.ecs_mut().stacking[arrows].count = 12;
+        world.entity_take(player, arrows);
+        assert!(world.can_fire(player));
+
+        // A missed shot stops in front of the wall and leaves the arrow there.
+        assert!(world.check_command(&Command::Fire(dir)).is_ok());
+        assert!(world.entity_fire(player, dir).is_some());
+        assert_eq!(world.count(arrows), 11);
+        assert_eq!(world.items_at(lane[2]).len(), 1);
+
+        // Shots stop at the first mob in the way. Some arrows break, but only one is used up per
+        // shot.
+        let ogre = world.spawn(&ExternalEntity::from_name("ogre").unwrap(), lane[1]);
+        world.spawn(&ExternalEntity::from_name("dreg").unwrap(), lane[2]);
+        assert_eq!(world.trace_projectile(origin, dir, 6), (lane[1], Some(ogre)));
+        for shot in 1..=6 {
+            world.ecs_mut().health[ogre].wounds = 0;
+            assert!(world.entity_fire(player, dir).is_some());
+            assert_eq!(world.count(arrows), 11 - shot);
+        }
+        let landed: u32 = world.items_at(lane[1]).iter().map(|&e| world.count(e)).sum();
+        assert!(landed < 6);
+        assert_eq!(world.items_at(lane[2]).len(), 1);
+    }
+
+    #[test]
+    fn test_spell_mana() {
+        let mut world = World::new(&WorldSeed::test(1));
+        let player = world.player().unwrap();
+        let cost = Ability::Heal.data().cost.mana;
+        assert_eq!(world.mana(player), world.max_mana(player));
+        assert!(world.max_mana(player) >= cost);
+
+        world.damage(player, 5, Damage::Physical, None);
+        assert!(world.use_ability(player, Ability::Heal, None, None).is_some());
+        assert_eq!(world.hp(player), world.max_hp(player));
+        assert_eq!(world.mana(player), world.max_mana(player) - cost);
+
+        // Can't cast without enough mana.
+        world.ecs_mut().health[player].mana_used = world.max_mana(player);
+        assert!(world.use_ability(player, Ability::Heal, None, None).is_none());
+
+        // Abilities the caster doesn't know can't be cast at all.
+        world.recover_mana(player, 100);
+        assert!(world.use_ability(player, Ability::LightningBolt, None, None).is_none());
+        assert_eq!(world.mana(player), world.max_mana(player));
+    }
+
+    #[test]
+    fn test_terrain_changes() {
+        let mut world = World::new(&WorldSeed::test(1));
+        let player = world.player().unwrap();
+        let origin = world.location(player).unwrap();
+        let dir = Dir6::iter()
+            .cloned()
+            .find(|&dir| !world.blocks_walk(origin.jump(&world, dir)))
+            .unwrap();
+        let loc = origin.jump(&world, dir);
+        let generated = world.terrain(loc);
+        let hash = world.state_hash();
+        assert_eq!(world.walk_distance(player, loc), Some(1));
+
+        world.set_terrain(loc, Terrain::Rock);
+        assert_eq!(world.terrain(loc), Terrain::Rock);
+        assert_ne!(world.state_hash(), hash);
+        // Cached paths see the change.
+        assert_eq!(world.walk_distance(player, loc), None);
+
+        assert!(world.check_command(&Command::ToggleDoor(dir)).is_err());
+        // Digging and working doors make noise that wakes up sleepers.
+        let sleeper = world.spawn(&ExternalEntity::from_name("dreg").unwrap(), origin);
+        assert!(!world.is_active(sleeper));
+        assert!(world.dig(player, dir).is_some());
+        assert!(world.is_active(sleeper));
+        assert_eq!(world.terrain(loc), Terrain::Ground);
+        assert!(world.check_command(&Command::Dig(dir)).is_err());
+
+        world.set_terrain(loc, Terrain::Door);
+        assert!(world.terrain(loc).blocks_sight());
+        let sleeper = world.spawn(&ExternalEntity::from_name("dreg").unwrap(), origin);
+        assert!(world.toggle_door(player, dir).is_some());
+        assert!(world.is_active(sleeper));
+        assert_eq!(world.terrain(loc), Terrain::OpenDoor);
+        assert!(world.toggle_door(player, dir).is_some());
+        assert_eq!(world.terrain(loc), Terrain::Door);
+
+        // Restoring the generated terrain drops the override.
+        world.set_terrain(loc, generated);
+        assert!(world.terrain_overrides.is_empty());
+        assert_eq!(world.terrain(loc), generated);
     }
 }
@@ -0,0 +1,83 @@
+//! Sound propagation
+
+use crate::{Location, World};
+use calx::{Dijkstra, Dir6};
+use calx_ecs::Entity;
+use rand::Rng;
+
+/// Loudness of a creature walking around.
+pub const FOOTSTEP_NOISE: u32 = 3;
+
+/// Loudness of a creature walking through, opening or closing a door.
+pub const DOOR_NOISE: u32 = 5;
+
+/// Loudness of a creature digging through rock.
+pub const DIG_NOISE: u32 = 7;
+
+/// Loudness of a ranged attack.
+pub const FIRE_NOISE: u32 = 4;
+
+/// Loudness of a melee fight.
+pub const MELEE_NOISE: u32 = 6;
+
+/// Loudness of a creature blowing up.
+pub const EXPLOSION_NOISE: u32 = 8;
+
+/// Loudness of a creature shouting.
+pub const SHOUT_NOISE: u32 = 10;
+
+impl World {
+    /// Make a noise that may wake up sleeping mobs.
+    ///
+    /// The noise travels through walkable terrain and portals up to `loudness` steps from the
+    /// origin. Sleeping mobs are less likely to wake up the farther away from the origin they are.
+    /// The source of the noise, if any, doesn't hear itself.
+    pub(crate) fn make_noise(&mut self, origin: Location, loudness: u32, source: Option<Entity>) {
+        if loudness == 0 {
+            return;
+        }
+
+        let sleepers: Vec<(Entity, Location)> = self
+            .active_mobs()
+            .into_iter()
+            .filter(|&e| Some(e) != source && self.is_npc(e) && !self.is_active(e))
+            .filter_map(|e| self.location(e).map(|loc| (e, loc)))
+            .collect();
+        if sleepers.is_empty() {
+            return;
+        }
+
+        let sound = Dijkstra::from_fn(
+            vec![origin],
+            |&loc: &Location| {
+                Dir6::iter()
+                    .map(|&dir| loc.jump(self, dir))
+                    .filter(|&loc| self.carries_sound(loc))
+                    .collect()
+            },
+            loudness,
+        );
+
+        for (e, loc) in sleepers {
+            if let Some(&dist) = sound.weights.get(&loc) {
+                // Chance to wake up drops linearly with distance.
+                if self.rng().gen_range(0, loudness) >= dist {
+                    self.hear_noise(e, source);
+                }
+            }
+        }
+    }
+
+    /// Return whether sound travels through a location.
+    fn carries_sound(&self, loc: Location) -> bool {
+        let terrain = self.terrain(loc);
+        !terrain.blocks_walk() || terrain.is_door()
+    }
+
+    /// Make noise from a mob.
+    pub(crate) fn mob_noise(&mut self, e: Entity, loudness: u32) {
+        if let Some(loc) = self.location(e) {
+            self.make_noise(loc, loudness, Some(e));
+        }
+    }
+}
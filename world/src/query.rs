@@ -140,6 +140,33 @@ impl World {
         loc
     }
 
+    /// Trace the flight of a projectile.
+    ///
+    /// Projectiles stop at the first mob they hit or before blocking terrain. Return the location
+    /// where the projectile ends up and the mob it hit, if any.
+    pub fn trace_projectile(
+        &self,
+        origin: Location,
+        dir: Dir6,
+        range: u32,
+    ) -> (Location, Option<Entity>) {
+        let mut loc = origin;
+        for _ in 0..range {
+            let new_loc = loc.jump(self, dir);
+
+            if let Some(mob) = self.mob_at(new_loc) {
+                return (new_loc, Some(mob));
+            }
+
+            if self.terrain(new_loc).blocks_shot() {
+                return (loc, None);
+            }
+
+            loc = new_loc;
+        }
+        (loc, None)
+    }
+
     /// Return whether the player can currently directly see the given location.
     pub fn player_sees(&self, loc: Location) -> bool {
         self.fov_status(loc) == Some(FovStatus::Seen)
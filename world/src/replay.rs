@@ -0,0 +1,165 @@
+//! Compact recordings of game sessions.
+
+use crate::{Command, ExternalEntity, World, WorldSeed, WorldSkeleton};
+use calx::History;
+use serde_derive::{Deserialize, Serialize};
+use std::error::Error;
+use std::io::{Read, Write};
+
+/// Version of the replay file format.
+///
+/// Bump this whenever the layout of `Replay` or the way its state hash is computed changes.
+pub const REPLAY_VERSION: u32 = 3;
+
+/// Replays before this version have state hashes that are computed differently.
+const STATE_HASH_VERSION: u32 = 3;
+
+/// Recorded game session that can be played back to reproduce the game state.
+///
+/// The `Wait` events that make up most of the history are not stored, instead each player command
+/// is stored with the world tick it was given at.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Replay {
+    /// Version of the replay format.
+    pub version: u32,
+    /// Version of the game that recorded the replay.
+    pub game_version: String,
+    pub rng_seed: u32,
+    pub world_skeleton: WorldSkeleton,
+    pub player_character: ExternalEntity,
+    /// Whether the player needs to eat.
+    #[serde(default)]
+    pub hunger: bool,
+    /// Player commands and the ticks when they were given.
+    pub commands: Vec<(u64, Command)>,
+    /// Total number of ticks in the replay.
+    pub end_tick: u64,
+    /// Hash of the world state at the end of the replay, if known.
+    pub state_hash: Option<u64>,
+}
+
+impl Replay {
+    pub fn new(history: &History<WorldSeed, Command>) -> Replay {
+        let commands = history
+            .events
+            .iter()
+            .enumerate()
+            .filter(|(_, c)| **c != Command::Wait)
+            .map(|(t, c)| (t as u64, c.clone()))
+            .collect();
+
+        Replay {
+            version: REPLAY_VERSION,
+            game_version: crate::world::GAME_VERSION.to_string(),
+            rng_seed: history.seed.rng_seed,
+            world_skeleton: history.seed.world_skeleton.clone(),
+            player_character: history.seed.player_character.clone(),
+            hunger: history.seed.hunger,
+            commands,
+            end_tick: history.events.len() as u64,
+            state_hash: None,
+        }
+    }
+
+    /// Record the final state of the world the replay was made from.
+    pub fn with_state(mut self, world: &World) -> Replay {
+        self.state_hash = Some(world.state_hash());
+        self
+    }
+
+    pub fn seed(&self) -> WorldSeed {
+        WorldSeed {
+            rng_seed: self.rng_seed,
+            world_skeleton: self.world_skeleton.clone(),
+            player_character: self.player_character.clone(),
+            hunger: self.hunger,
+        }
+    }
+
+    /// Expand the replay into the full event history.
+    pub fn history(&self) -> History<WorldSeed, Command> {
+        let mut events = Vec::with_capacity(self.end_tick as usize);
+        for (t, cmd) in &self.commands {
+            while (events.len() as u64) < *t {
+                events.push(Command::Wait);
+            }
+            events.push(cmd.clone());
+        }
+        while (events.len() as u64) < self.end_tick {
+            events.push(Command::Wait);
+        }
+
+        History {
+            seed: self.seed(),
+            events,
+        }
+    }
+
+    pub fn load(input: impl Read) -> Result<Replay, Box<dyn Error>> {
+        let mut ret: Replay = ron::de::from_reader(input)?;
+        if ret.version == 0 || ret.version > REPLAY_VERSION {
+            return Err(format!(
+                "Unsupported replay version {}, expected {}",
+                ret.version, REPLAY_VERSION
+            )
+            .into());
+        }
+        if ret.version < STATE_HASH_VERSION {
+            // The commands still play back the same, but the old hash can't be checked.
+            ret.state_hash = None;
+        }
+        ret.version = REPLAY_VERSION;
+        Ok(ret)
+    }
+
+    pub fn save(&self, mut output: impl Write) -> Result<(), Box<dyn Error>> {
+        // Depth limit makes each command go on its own line.
+        let config = ron::ser::PrettyConfig {
+            depth_limit: 2,
+            ..Default::default()
+        };
+        output.write_all(ron::ser::to_string_pretty(self, config)?.as_bytes())?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::Replay;
+    use crate::{Command, Simulation, World, WorldSeed};
+    use calx::{Dir6, IncrementalState};
+
+    #[test]
+    fn test_replay_roundtrip() {
+        let mut sim = Simulation::new(WorldSeed::test(1));
+        sim.run(vec![
+            Command::Pass,
+            Command::Step(Dir6::North),
+            Command::Step(Dir6::South),
+            Command::Pass,
+        ]);
+
+        let replay = Replay::new(sim.history()).with_state(sim.world());
+        assert_eq!(replay.commands.len(), 4);
+
+        let mut buf = Vec::new();
+        replay.save(&mut buf).unwrap();
+        let replay = Replay::load(&buf[..]).unwrap();
+
+        let history = replay.history();
+        assert_eq!(history.events, sim.history().events);
+
+        let world: IncrementalState<World> = history.into();
+        assert_eq!(Some(world.state_hash()), replay.state_hash);
+
+        // Replays from before the current state hash still play but have no hash to check against.
+        for version in 1..=2 {
+            let old = String::from_utf8(buf.clone())
+                .unwrap()
+                .replacen("version: 3,", &format!("version: {},", version), 1);
+            let replay = Replay::load(old.as_bytes()).unwrap();
+            assert_eq!(replay.state_hash, None);
+            assert_eq!(replay.history().events, sim.history().events);
+        }
+    }
+}
@@ -762,7 +762,9 @@ impl<'a> ConnectedSectorSpec<'a> {
     }
 
     fn can_spawn(&self, spec: &dyn Spec) -> bool {
-        spec.min_depth() <= self.depth && (spec.habitat() & (1 << self.biome as u64)) != 0
+        // Nocturnal creatures come out on the surface at night, they aren't there from the start.
+        spec.can_spawn_in(self.biome, self.depth)
+            && (self.biome == Biome::Dungeon || !spec.is_nocturnal())
     }
 }
 
@@ -0,0 +1,204 @@
+//! Headless driver for running the world without a frontend.
+
+use crate::{msg, Command, Location, Slot, World, WorldSeed};
+use calx::{History, IncrementalState};
+use serde_derive::{Deserialize, Serialize};
+
+/// How many frames to wait for the player to get a turn before giving up.
+///
+/// The slowest possible actor acts well within this, hitting the limit means something is stuck.
+const MAX_WAIT_TICKS: u32 = 1000;
+
+/// World runner that is driven by a command script instead of a user interface.
+///
+/// The simulation always advances the world until the player can act again after each command,
+/// so a script of player commands maps directly into a game session. Game messages are collected
+/// into a buffer instead of being sent to the registered message receiver.
+pub struct Simulation {
+    world: IncrementalState<World>,
+    messages: Vec<String>,
+    turns: u64,
+}
+
+impl Simulation {
+    pub fn new(seed: WorldSeed) -> Simulation {
+        let (world, messages) = msg::capture_msgs(|| IncrementalState::new(seed));
+        let mut ret = Simulation {
+            world,
+            messages,
+            turns: 0,
+        };
+        ret.wait_for_player();
+        ret
+    }
+
+    /// Start a simulation by replaying an existing history.
+    pub fn from_history(history: History<WorldSeed, Command>) -> Simulation {
+        let turns = history.events.iter().filter(|&c| *c != Command::Wait).count() as u64;
+        let (world, messages) = msg::capture_msgs(|| IncrementalState::from(history));
+        let mut ret = Simulation {
+            world,
+            messages,
+            turns,
+        };
+        ret.wait_for_player();
+        ret
+    }
+
+    pub fn world(&self) -> &World { &self.world }
+
+    pub fn history(&self) -> &History<WorldSeed, Command> { self.world.history() }
+
+    /// Run a player command and advance the world until the player can act again.
+    ///
+    /// Return false if the command could not be given, either because the game is over or
+    /// because the command is not valid in the current world state.
+    pub fn step(&mut self, cmd: Command) -> bool {
+        if !self.world.player_can_act() || !self.world.can_command(&cmd) {
+            return false;
+        }
+
+        self.update(cmd);
+        self.turns += 1;
+        self.wait_for_player();
+        true
+    }
+
+    /// Run a sequence of player commands.
+    ///
+    /// Stops at the first command that can't be given. Return the number of commands that were
+    /// run.
+    pub fn run(&mut self, script: impl IntoIterator<Item = Command>) -> usize {
+        let mut n = 0;
+        for cmd in script {
+            if !self.step(cmd) {
+                break;
+            }
+            n += 1;
+        }
+        n
+    }
+
+    /// Return messages emitted since the simulation started or messages were last taken.
+    pub fn messages(&self) -> &[String] { &self.messages }
+
+    /// Remove and return the collected messages.
+    pub fn take_messages(&mut self) -> Vec<String> { std::mem::take(&mut self.messages) }
+
+    /// Return the number of player commands run so far.
+    pub fn turns(&self) -> u64 { self.turns }
+
+    /// Return current time of the world logic clock.
+    pub fn tick(&self) -> u64 { self.world.get_tick() }
+
+    /// Summarize the current world state.
+    pub fn report(&self) -> Report {
+        let world: &World = &self.world;
+
+        let player = world.player().map(|p| PlayerReport {
+            location: world.location(p).unwrap_or_default(),
+            hp: world.hp(p),
+            max_hp: world.max_hp(p),
+            level: world.stats(p).level,
+            xp: world.stats(p).xp,
+            inventory: world
+                .entities_in(p)
+                .into_iter()
+                .map(|(slot, e)| (slot, world.entity_name(e)))
+                .collect(),
+        });
+
+        Report {
+            tick: world.get_tick(),
+            turns: self.turns,
+            game_over: world.game_over(),
+            player,
+            mob_count: world.entities().filter(|&&e| world.is_mob(e)).count(),
+            item_count: world.entities().filter(|&&e| world.is_item(e)).count(),
+        }
+    }
+
+    fn update(&mut self, cmd: Command) {
+        let world = &mut self.world;
+        let ((), mut msgs) = msg::capture_msgs(|| world.update(cmd));
+        self.messages.append(&mut msgs);
+    }
+
+    fn wait_for_player(&mut self) {
+        for _ in 0..MAX_WAIT_TICKS {
+            if self.world.player().is_none() || self.world.player_can_act() {
+                return;
+            }
+            self.update(Command::Wait);
+        }
+    }
+}
+
+/// Summary of simulated world state.
+///
+/// Meant to be stable across changes to the internal world representation so that regression
+/// tests can compare against it.
+#[derive(Clone, Debug, Eq, PartialEq, Serialize, Deserialize)]
+pub struct Report {
+    pub tick: u64,
+    pub turns: u64,
+    pub game_over: bool,
+    pub player: Option<PlayerReport>,
+    pub mob_count: usize,
+    pub item_count: usize,
+}
+
+#[derive(Clone, Debug, Eq, PartialEq, Serialize, Deserialize)]
+pub struct PlayerReport {
+    pub location: Location,
+    pub hp: i32,
+    pub max_hp: i32,
+    pub level: i32,
+    pub xp: i32,
+    pub inventory: Vec<(Slot, String)>,
+}
+
+#[cfg(test)]
+mod test {
+    use super::Simulation;
+    use crate::{Command, WorldSeed};
+    use calx::Dir6;
+
+    fn script() -> Vec<Command> {
+        let mut ret = vec![Command::Pass; 5];
+        for &dir in &[Dir6::North, Dir6::Southeast, Dir6::Southwest, Dir6::North] {
+            ret.push(Command::Step(dir));
+        }
+        ret
+    }
+
+    #[test]
+    fn test_run_script() {
+        let mut sim = Simulation::new(WorldSeed::test(1));
+        assert!(sim.world().player_can_act());
+        assert_eq!(sim.turns(), 0);
+
+        assert_eq!(sim.run(vec![Command::Pass; 5]), 5);
+        assert_eq!(sim.turns(), 5);
+        assert!(sim.tick() > 0);
+        assert!(sim.world().player_can_act());
+
+        // Wait is never a valid player command.
+        assert!(!sim.step(Command::Wait));
+    }
+
+    #[test]
+    fn test_determinism() {
+        let mut sim1 = Simulation::new(WorldSeed::test(1));
+        let mut sim2 = Simulation::new(WorldSeed::test(1));
+        sim1.run(script());
+        sim2.run(script());
+
+        assert_eq!(sim1.report(), sim2.report());
+        assert_eq!(sim1.messages(), sim2.messages());
+
+        // Replaying the recorded history gets the same state.
+        let sim3 = Simulation::from_history(sim1.history().clone());
+        assert_eq!(sim1.report(), sim3.report());
+    }
+}
@@ -1,22 +1,30 @@
 //! Data for generating game entities.
 
 use crate::{
-    ai::{Brain, ShoutType},
+    ai::{Alignment, Brain, Relation, ShoutType},
     desc::{Desc, Icon},
+    effect::{Ability, Damage, DeathTrigger},
     item::ItemType,
-    item::{Item, Stacking},
+    item::{Item, Requirement, Stacking},
+    loot::{LootEntry, LootTable},
+    modifier::Stat,
     sector::Biome,
-    stats::{Health, Intrinsic, Stats, StatsComponent, Statuses},
+    stats::{Health, Intrinsic, Stats, StatsComponent},
+    status::Statuses,
     world::Loadout,
     Anim, Distribution, ExternalEntity, Rng,
 };
 use lazy_static::lazy_static;
+use rand::Rng as _;
 use serde;
+use serde_derive::Deserialize;
 use std::collections::BTreeMap;
 use std::error::Error;
 use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
 use std::str::FromStr;
-use std::sync::Arc;
+use std::sync::{Arc, RwLock};
 
 pub trait Spec: Distribution<ExternalEntity> + Sync + Send {
     /// How rare is this spec?
@@ -33,7 +41,18 @@ pub trait Spec: Distribution<ExternalEntity> + Sync + Send {
     /// What biomes can this spawn in
     fn habitat(&self) -> u64;
 
+    /// Does this only come out on the surface at night?
+    fn is_nocturnal(&self) -> bool { false }
+
+    /// Can this spawn in a biome at a depth?
+    fn can_spawn_in(&self, biome: Biome, depth: i32) -> bool {
+        self.min_depth() <= depth && (self.habitat() & (1 << biome as u64)) != 0
+    }
+
     fn name(&self) -> &str;
+
+    /// Type of the item the spec makes, if it makes an item.
+    fn item_type(&self) -> Option<ItemType> { None }
 }
 
 const EVERYWHERE: u64 = 0xffff_ffff_ffff_ffff;
@@ -42,16 +61,36 @@ const TEMPERATE: u64 = (1 << Biome::Grassland as u64) | (1 << Biome::Forest as u
 const ARID: u64 = (1 << Biome::Desert as u64) | (1 << Biome::Mountain as u64);
 const URBAN: u64 = (1 << Biome::City as u64);
 
-#[derive(Debug)]
+#[derive(Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
 pub struct MobSpec {
     name: String,
     icon: Icon,
     depth: i32,
     rarity: f32,
+    #[serde(deserialize_with = "deserialize_habitat")]
     habitat: u64,
     power: i32,
+    /// Size of the mana pool for casting innate abilities.
+    mana: i32,
     intrinsics: Vec<Intrinsic>,
     shout: ShoutType,
+    alignment: Alignment,
+    abilities: Vec<Ability>,
+    ranged_range: u32,
+    ranged_power: i32,
+    /// Damage types the mob takes half damage from.
+    resist: Vec<Damage>,
+    /// Damage types the mob takes double damage from.
+    vulnerable: Vec<Damage>,
+    /// Radius of the light the mob gives off.
+    light: i32,
+    /// Only spawns on the surface at night.
+    nocturnal: bool,
+    /// What happens when the mob dies.
+    on_death: Vec<DeathTrigger>,
+    /// Random items the mob drops when it dies.
+    loot: LootTable,
 }
 
 impl Default for MobSpec {
@@ -63,26 +102,51 @@ impl Default for MobSpec {
             rarity: 1.0,
             habitat: EVERYWHERE,
             power: 0,
+            mana: 0,
             intrinsics: Vec::new(),
             shout: ShoutType::Silent,
+            alignment: Alignment::Enemy,
+            abilities: Vec::new(),
+            ranged_range: 0,
+            ranged_power: 0,
+            resist: Vec::new(),
+            vulnerable: Vec::new(),
+            light: 0,
+            nocturnal: false,
+            on_death: vec![DeathTrigger::DropItems],
+            loot: LootTable::default(),
         }
     }
 }
 
 impl Distribution<ExternalEntity> for MobSpec {
     fn sample(&self, _: &mut Rng) -> ExternalEntity {
-        ExternalEntity::new(
-            Loadout::default()
-                .c(StatsComponent::new(Stats::new(
-                    self.power,
-                    &self.intrinsics,
-                )))
-                .c(Desc::new(&self.name, self.icon))
-                .c(Brain::enemy().shout(self.shout))
-                .c(Anim::default())
-                .c(Health::default())
-                .c(Statuses::default()),
-        )
+        let mut loadout = Loadout::default()
+            .c(StatsComponent::new(
+                Stats::new(self.power, &self.intrinsics)
+                    .mana(self.mana)
+                    .ranged_range(self.ranged_range)
+                    .ranged_power(self.ranged_power)
+                    .light(self.light)
+                    .resistances(&self.resist)
+                    .vulnerabilities(&self.vulnerable),
+            ))
+            .c(Desc::new(&self.name, self.icon))
+            .c(Brain::enemy().shout(self.shout).alignment(self.alignment))
+            .c(Anim::default())
+            .c(Health::default())
+            .c(Statuses::default());
+        if !self.abilities.is_empty() {
+            loadout = loadout.c(self.abilities.clone());
+        }
+        let mut on_death = self.on_death.clone();
+        if !self.loot.is_empty() {
+            on_death.push(DeathTrigger::Loot(self.loot.clone()));
+        }
+        if !on_death.is_empty() {
+            loadout = loadout.c(on_death);
+        }
+        ExternalEntity::new(loadout)
     }
 }
 
@@ -90,15 +154,18 @@ impl Spec for MobSpec {
     fn rarity(&self) -> f32 { self.rarity }
     fn min_depth(&self) -> i32 { self.depth }
     fn habitat(&self) -> u64 { self.habitat }
+    fn is_nocturnal(&self) -> bool { self.nocturnal }
     fn name(&self) -> &str { &self.name }
 }
 
-#[derive(Debug)]
+#[derive(Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
 pub struct ItemSpec {
     name: String,
     icon: Icon,
     depth: i32,
     rarity: f32,
+    #[serde(deserialize_with = "deserialize_habitat")]
     habitat: u64,
     item_type: ItemType,
     power: i32,
@@ -106,7 +173,21 @@ pub struct ItemSpec {
     attack: i32,
     defense: i32,
     intrinsics: Vec<Intrinsic>,
+    ranged_range: u32,
+    ranged_power: i32,
+    /// Damage types the wearer takes half damage from.
+    resist: Vec<Damage>,
+    /// Damage types the wearer takes double damage from.
+    vulnerable: Vec<Damage>,
+    /// Radius of the light the item gives off on the floor or when equipped.
+    light: i32,
     stacks: bool,
+    /// Largest stack size the item spawns with.
+    count: u32,
+    /// Cursed items can't be taken off once equipped.
+    cursed: bool,
+    /// What it takes to equip the item.
+    requirements: Vec<Requirement>,
 }
 
 impl Default for ItemSpec {
@@ -124,27 +205,47 @@ impl Default for ItemSpec {
             attack: 0,
             defense: 0,
             intrinsics: Vec::new(),
+            ranged_range: 0,
+            ranged_power: 0,
+            resist: Vec::new(),
+            vulnerable: Vec::new(),
+            light: 0,
             stacks: false,
+            count: 1,
+            cursed: false,
+            requirements: Vec::new(),
         }
     }
 }
 
 impl Distribution<ExternalEntity> for ItemSpec {
-    fn sample(&self, _: &mut Rng) -> ExternalEntity {
+    fn sample(&self, rng: &mut Rng) -> ExternalEntity {
         let mut loadout = Loadout::default()
             .c(Desc::new(&self.name, self.icon))
             .c(StatsComponent::new(
                 Stats::new(self.power, &self.intrinsics)
                     .armor(self.armor)
                     .attack(self.attack)
-                    .defense(self.defense),
+                    .defense(self.defense)
+                    .ranged_range(self.ranged_range)
+                    .ranged_power(self.ranged_power)
+                    .light(self.light)
+                    .resistances(&self.resist)
+                    .vulnerabilities(&self.vulnerable),
             ))
             .c(Item {
                 item_type: self.item_type,
                 charges: 1,
+                cursed: self.cursed,
+                requirements: self.requirements.clone(),
             });
         if self.stacks {
-            loadout = loadout.c(Stacking::default());
+            let count = if self.count > 1 {
+                rng.gen_range(1, self.count + 1)
+            } else {
+                1
+            };
+            loadout = loadout.c(Stacking { count });
         }
         ExternalEntity::new(loadout)
     }
@@ -155,23 +256,88 @@ impl Spec for ItemSpec {
     fn min_depth(&self) -> i32 { self.depth }
     fn habitat(&self) -> u64 { self.habitat }
     fn name(&self) -> &str { &self.name }
+    fn item_type(&self) -> Option<ItemType> { Some(self.item_type) }
 }
 
 macro_rules! specs {
     {$($item:expr,)+}
     =>
     {
-        lazy_static! {
-            pub static ref SPECS: BTreeMap<EntitySpawn, Arc<dyn Spec>> = {
-                let mut ret: BTreeMap<EntitySpawn, Arc<dyn Spec>> = BTreeMap::new();
-                $(ret.insert(EntitySpawn($item.name().to_string()), Arc::new($item));)+
-                ret
-            };
+        /// Build the spec database from the specs that are compiled into the game.
+        fn builtin_specs() -> BTreeMap<EntitySpawn, Arc<dyn Spec>> {
+            let mut ret: BTreeMap<EntitySpawn, Arc<dyn Spec>> = BTreeMap::new();
+            $(ret.insert(EntitySpawn($item.name().to_string()), Arc::new($item));)+
+            ret
         }
     }
 }
 
-pub fn iter_specs() -> impl Iterator<Item = Arc<dyn Spec + 'static>> { SPECS.values().cloned() }
+lazy_static! {
+    /// Spec database, built-in specs plus any that have been loaded from spec files.
+    static ref SPECS: RwLock<BTreeMap<EntitySpawn, Arc<dyn Spec>>> = RwLock::new(builtin_specs());
+}
+
+pub fn iter_specs() -> impl Iterator<Item = Arc<dyn Spec + 'static>> {
+    let specs: Vec<_> = SPECS.read().unwrap().values().cloned().collect();
+    specs.into_iter()
+}
+
+fn get_spec(spawn: &EntitySpawn) -> Option<Arc<dyn Spec>> {
+    SPECS.read().unwrap().get(spawn).cloned()
+}
+
+/// How a faction regards the other factions.
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct FactionSpec {
+    alignment: Alignment,
+    /// Attitudes towards other factions.
+    ///
+    /// A faction is always friendly to itself and hostile to the factions it doesn't list.
+    #[serde(default)]
+    relations: BTreeMap<Alignment, Relation>,
+}
+
+impl FactionSpec {
+    fn new(alignment: Alignment, relations: &[(Alignment, Relation)]) -> FactionSpec {
+        FactionSpec {
+            alignment,
+            relations: relations.iter().cloned().collect(),
+        }
+    }
+}
+
+/// Build the faction relations that are compiled into the game.
+fn builtin_factions() -> BTreeMap<Alignment, FactionSpec> {
+    use crate::ai::{Alignment::*, Relation::*};
+    // Enemies and animals are hostile to everyone else.
+    vec![
+        FactionSpec::new(Player, &[(Neutral, Indifferent)]),
+        FactionSpec::new(Neutral, &[(Player, Indifferent)]),
+    ]
+    .into_iter()
+    .map(|f| (f.alignment, f))
+    .collect()
+}
+
+lazy_static! {
+    /// Faction database, built-in factions plus any that have been loaded from spec files.
+    static ref FACTIONS: RwLock<BTreeMap<Alignment, FactionSpec>> =
+        RwLock::new(builtin_factions());
+}
+
+/// Return the attitude of a faction towards another faction in the faction database.
+pub(crate) fn faction_relation(faction: Alignment, other: Alignment) -> Relation {
+    if faction == other {
+        return Relation::Friendly;
+    }
+    FACTIONS
+        .read()
+        .unwrap()
+        .get(&faction)
+        .and_then(|f| f.relations.get(&other).cloned())
+        .unwrap_or(Relation::Hostile)
+}
 
 use self::Intrinsic::*;
 use self::ShoutType::*;
@@ -186,8 +352,11 @@ specs! {
         icon: I::Player,
         rarity: 0.0,
         power: 10,
+        mana: 8,
         intrinsics: vec![Hands],
+        abilities: vec![Heal],
         shout: Shout,
+        light: 3,
         ..d()
     },
     MobSpec {
@@ -214,6 +383,7 @@ specs! {
         habitat: DUNGEON,
         power: 3,
         shout: Gurgle,
+        resist: vec![Damage::Poison],
         ..d()
     },
     MobSpec {
@@ -242,6 +412,17 @@ specs! {
         power: 7,
         intrinsics: vec![Hands],
         shout: Shout,
+        on_death: vec![DeathTrigger::DropItems, DeathTrigger::Corpse],
+        loot: LootTable {
+            chance: 20,
+            chance_per_depth: 3,
+            entries: vec![
+                loot("sword", 2, 0),
+                loot("helmet", 2, 0),
+                loot("armor", 1, 6),
+            ],
+            ..d()
+        },
         ..d()
     },
     MobSpec {
@@ -252,6 +433,8 @@ specs! {
         rarity: 6.0,
         power: 10,
         intrinsics: vec![Hands],
+        resist: vec![Damage::Cold, Damage::Poison],
+        vulnerable: vec![Damage::Holy],
         ..d()
     },
     MobSpec {
@@ -261,7 +444,11 @@ specs! {
         habitat: DUNGEON,
         rarity: 8.0,
         power: 14,
-        intrinsics: vec![Hands],
+        intrinsics: vec![Hands, FireImmunity],
+        mana: 18,
+        abilities: vec![Fireball],
+        vulnerable: vec![Damage::Cold],
+        light: 4,
         ..d()
     },
     MobSpec {
@@ -281,6 +468,8 @@ specs! {
         habitat: TEMPERATE,
         power: 7,
         shout: Roar,
+        alignment: Alignment::Animal,
+        on_death: vec![DeathTrigger::DropItems, DeathTrigger::Corpse],
         ..d()
     },
     MobSpec {
@@ -291,6 +480,9 @@ specs! {
         power: 20,
         rarity: 10.0,
         shout: Hiss,
+        intrinsics: vec![PoisonImmunity],
+        mana: 6,
+        abilities: vec![PoisonSpit],
         ..d()
     },
     MobSpec {
@@ -309,6 +501,7 @@ specs! {
         habitat: DUNGEON | TEMPERATE | URBAN | ARID,
         power: 1,
         intrinsics: vec![Hyperactive],
+        nocturnal: true,
         ..d()
     },
     MobSpec {
@@ -320,6 +513,15 @@ specs! {
         rarity: 3.0,
         shout: Shout,
         intrinsics: vec![Hands],
+        ranged_range: 5,
+        ranged_power: 2,
+        on_death: vec![DeathTrigger::DropItems, DeathTrigger::Corpse],
+        loot: LootTable {
+            chance: 30,
+            chance_per_depth: 2,
+            entries: vec![loot("arrow", 3, 0), loot("bow", 1, 0)],
+            ..d()
+        },
         ..d()
     },
     MobSpec {
@@ -330,6 +532,8 @@ specs! {
         power: 8,
         rarity: 5.0,
         intrinsics: vec![Hands],
+        resist: vec![Damage::Fire, Damage::Poison],
+        vulnerable: vec![Damage::Electricity],
         ..d()
     },
     MobSpec {
@@ -340,6 +544,10 @@ specs! {
         power: 8,
         rarity: 5.0,
         shout: Shout,
+        mana: 9,
+        abilities: vec![Confuse],
+        resist: vec![Damage::Poison],
+        vulnerable: vec![Damage::Holy],
         ..d()
     },
     MobSpec {
@@ -350,6 +558,10 @@ specs! {
         power: 5,
         rarity: 3.0,
         shout: Gurgle,
+        on_death: vec![DeathTrigger::Spawn {
+            name: "ooze".into(),
+            count: 2,
+        }],
         ..d()
     },
     MobSpec {
@@ -360,6 +572,8 @@ specs! {
         power: 5,
         rarity: 2.0,
         shout: Roar,
+        alignment: Alignment::Animal,
+        on_death: vec![DeathTrigger::DropItems, DeathTrigger::Corpse],
         ..d()
     },
     MobSpec {
@@ -369,6 +583,7 @@ specs! {
         depth: 5,
         power: 5,
         rarity: 10.0,
+        light: 3,
         ..d()
     },
     MobSpec {
@@ -387,6 +602,13 @@ specs! {
         depth: 10,
         power: 50,
         rarity: 20.0,
+        loot: LootTable {
+            guaranteed: vec![
+                "wand of fireball|wands of fireball".into(),
+                "scroll of regeneration|scrolls of regeneration".into(),
+            ],
+            ..d()
+        },
         ..d()
     },
     MobSpec {
@@ -397,6 +619,15 @@ specs! {
         rarity: 2.0,
         shout: Shout,
         intrinsics: vec![Hands],
+        loot: LootTable {
+            chance: 10,
+            chance_per_depth: 2,
+            entries: vec![
+                loot("scroll of lightning|scrolls of lightning", 2, 0),
+                loot("wand of charming|wands of charming", 1, 2),
+            ],
+            ..d()
+        },
         ..d()
     },
     MobSpec {
@@ -428,17 +659,21 @@ specs! {
     MobSpec {
         name: "dog".into(),
         icon: I::Dog,
-        habitat: DUNGEON,
+        habitat: DUNGEON | URBAN,
         power: 3,
         rarity: 10.0,
+        shout: Bark,
+        alignment: Alignment::Neutral,
         ..d()
     },
     MobSpec {
         name: "cat".into(),
         icon: I::Cat,
-        habitat: DUNGEON,
+        habitat: DUNGEON | URBAN,
         power: 3,
         rarity: 10.0,
+        shout: Meow,
+        alignment: Alignment::Neutral,
         ..d()
     },
     MobSpec {
@@ -446,6 +681,9 @@ specs! {
         icon: I::Rat,
         habitat: DUNGEON | TEMPERATE | ARID,
         power: 1,
+        shout: Squeak,
+        alignment: Alignment::Animal,
+        nocturnal: true,
         ..d()
     },
 
@@ -459,6 +697,24 @@ specs! {
         attack: 6,
         ..d()
     },
+    ItemSpec {
+        name: "bow".into(),
+        icon: I::Bow,
+        item_type: RangedWeapon,
+        rarity: 10.0,
+        ranged_range: 6,
+        ranged_power: 4,
+        ..d()
+    },
+    ItemSpec {
+        name: "arrow".into(),
+        icon: I::Arrow,
+        item_type: Ammo,
+        rarity: 10.0,
+        stacks: true,
+        count: 12,
+        ..d()
+    },
     ItemSpec {
         name: "helmet".into(),
         icon: I::Helmet,
@@ -467,6 +723,44 @@ specs! {
         armor: 2,
         ..d()
     },
+    ItemSpec {
+        name: "shield".into(),
+        icon: I::Shield,
+        item_type: Shield,
+        rarity: 10.0,
+        defense: 3,
+        ..d()
+    },
+    ItemSpec {
+        name: "torch".into(),
+        icon: I::Torch,
+        item_type: Light,
+        rarity: 3.0,
+        light: 6,
+        ..d()
+    },
+    ItemSpec {
+        name: "greatsword".into(),
+        icon: I::Sword,
+        item_type: TwoHandedWeapon,
+        depth: 2,
+        rarity: 12.0,
+        attack: 8,
+        power: 3,
+        requirements: vec![Requirement::Stat(Stat::Power, 12)],
+        ..d()
+    },
+    ItemSpec {
+        name: "crown of thorns|crowns of thorns".into(),
+        icon: I::Helmet,
+        item_type: Helmet,
+        depth: 4,
+        rarity: 20.0,
+        attack: 4,
+        defense: -2,
+        cursed: true,
+        ..d()
+    },
     ItemSpec {
         name: "armor".into(),
         icon: I::Armor,
@@ -493,6 +787,24 @@ specs! {
         armor: 5,
         ..d()
     },
+    ItemSpec {
+        name: "wand of charming|wands of charming".into(),
+        icon: I::Wand3,
+        power: 5,
+        item_type: TargetedUsable(Charm),
+        rarity: 5.0,
+        depth: 2,
+        ..d()
+    },
+    ItemSpec {
+        name: "wand of frost|wands of frost".into(),
+        icon: I::Wand4,
+        power: 5,
+        item_type: TargetedUsable(FrostBolt),
+        rarity: 10.0,
+        depth: 4,
+        ..d()
+    },
     ItemSpec {
         name: "scroll of lightning|scrolls of lightning".into(),
         icon: I::Scroll1,
@@ -501,6 +813,70 @@ specs! {
         stacks: true,
         ..d()
     },
+    ItemSpec {
+        name: "scroll of regeneration|scrolls of regeneration".into(),
+        icon: I::Scroll2,
+        power: 1,
+        item_type: UntargetedUsable(Regenerate),
+        stacks: true,
+        depth: 2,
+        ..d()
+    },
+    ItemSpec {
+        name: "scroll of invisibility|scrolls of invisibility".into(),
+        icon: I::Scroll3,
+        power: 1,
+        item_type: UntargetedUsable(Invisibility),
+        stacks: true,
+        rarity: 5.0,
+        depth: 3,
+        ..d()
+    },
+    ItemSpec {
+        name: "scroll of smiting|scrolls of smiting".into(),
+        icon: I::Scroll4,
+        power: 1,
+        item_type: UntargetedUsable(Smite),
+        stacks: true,
+        rarity: 5.0,
+        depth: 4,
+        ..d()
+    },
+    ItemSpec {
+        name: "ration".into(),
+        icon: I::Ration,
+        item_type: Food(6000),
+        rarity: 2.0,
+        stacks: true,
+        ..d()
+    },
+    ItemSpec {
+        name: "scroll of identify|scrolls of identify".into(),
+        icon: I::Scroll1,
+        power: 1,
+        item_type: UntargetedUsable(Identify),
+        stacks: true,
+        count: 2,
+        ..d()
+    },
+    ItemSpec {
+        name: "amulet of speed|amulets of speed".into(),
+        icon: I::Amulet1,
+        item_type: Trinket,
+        rarity: 20.0,
+        depth: 3,
+        intrinsics: vec![Quick],
+        ..d()
+    },
+    ItemSpec {
+        name: "amulet of fire immunity|amulets of fire immunity".into(),
+        icon: I::Amulet2,
+        item_type: Trinket,
+        rarity: 10.0,
+        depth: 1,
+        intrinsics: vec![FireImmunity],
+        ..d()
+    },
 }
 
 /// String that's guaranteed to describe an entity spawn.
@@ -530,7 +906,7 @@ impl FromStr for EntitySpawn {
     type Err = SpawnError;
 
     fn from_str(s: &str) -> Result<Self, Self::Err> {
-        if !SPECS.contains_key(&EntitySpawn(s.to_string())) {
+        if !SPECS.read().unwrap().contains_key(&EntitySpawn(s.to_string())) {
             Err(SpawnError(s.to_string()))
         } else {
             Ok(EntitySpawn(s.to_string()))
@@ -553,17 +929,267 @@ impl<'a> serde::Deserialize<'a> for EntitySpawn {
 
 impl Distribution<ExternalEntity> for EntitySpawn {
     fn sample(&self, rng: &mut Rng) -> ExternalEntity {
-        SPECS
-            .get(self)
+        get_spec(self)
             .unwrap_or_else(|| panic!("EntitySpawn {:?} not found in spec database", self))
             .sample(rng)
     }
 }
 
+/// Entry in a spec file.
+#[derive(Debug, Deserialize)]
+enum SpecDef {
+    Mob(MobSpec),
+    Item(ItemSpec),
+    Faction(FactionSpec),
+}
+
+impl SpecDef {
+    /// Return the name that identifies the spec in error messages.
+    fn name(&self) -> String {
+        match self {
+            SpecDef::Mob(s) => s.name.clone(),
+            SpecDef::Item(s) => s.name.clone(),
+            SpecDef::Faction(s) => format!("{:?}", s.alignment),
+        }
+    }
+
+    /// Check spec values that deserialize fine but won't work in the game.
+    fn validate(&self) -> Result<(), (&'static str, String)> {
+        let (name, depth, rarity, power) = match self {
+            SpecDef::Mob(s) => (&s.name, s.depth, s.rarity, s.power),
+            SpecDef::Item(s) => (&s.name, s.depth, s.rarity, s.power),
+            SpecDef::Faction(s) => {
+                if s.relations.contains_key(&s.alignment) {
+                    return Err(("relations", "must not list the faction itself".into()));
+                }
+                return Ok(());
+            }
+        };
+
+        if name.is_empty() || name.split('|').any(str::is_empty) || name.matches('|').count() > 1 {
+            return Err((
+                "name",
+                "must be 'name' or 'singular|plural' with no empty parts".into(),
+            ));
+        }
+        if depth < 0 {
+            return Err(("depth", "must not be negative".into()));
+        }
+        if !rarity.is_finite() || rarity < 0.0 {
+            return Err(("rarity", "must be zero or a positive number".into()));
+        }
+        if power < 0 {
+            return Err(("power", "must not be negative".into()));
+        }
+        if let SpecDef::Mob(s) = self {
+            if s.mana < 0 {
+                return Err(("mana", "must not be negative".into()));
+            }
+            if s.loot.chance > 100 || s.loot.entries.iter().any(|e| e.weight == 0) {
+                return Err((
+                    "loot",
+                    "chance must be at most 100 and entry weights must be positive".into(),
+                ));
+            }
+        }
+        if let SpecDef::Item(s) = self {
+            if s.count == 0 || (s.count > 1 && !s.stacks) {
+                return Err((
+                    "count",
+                    "must be at least 1, and 1 for items that don't stack".into(),
+                ));
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Error in a spec file.
+#[derive(Debug)]
+pub struct SpecError {
+    pub file: PathBuf,
+    /// Line and column for errors in the file syntax.
+    pub position: Option<(usize, usize)>,
+    /// Spec name and field name for invalid values.
+    pub field: Option<(String, &'static str)>,
+    pub msg: String,
+}
+
+impl fmt::Display for SpecError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.file.display())?;
+        if let Some((line, col)) = self.position {
+            write!(f, ":{}:{}", line, col)?;
+        }
+        if let Some((ref spec, field)) = self.field {
+            write!(f, ": spec '{}', field '{}'", spec, field)?;
+        }
+        write!(f, ": {}", self.msg)
+    }
+}
+
+impl Error for SpecError {}
+
+/// Load specs from a RON spec file or a directory of `.ron` spec files.
+///
+/// A spec file is a list of `Mob(...)`, `Item(...)` and `Faction(...)` entries with the fields of
+/// `MobSpec`, `ItemSpec` and `FactionSpec`. Fields that are left out get their default values, and
+/// habitats are given as lists of biomes. Loaded specs are added to the spec database, a loaded
+/// spec replaces a built-in spec with the same name and a loaded faction replaces the relations of
+/// the built-in faction. Nothing is added if any of the files has errors.
+///
+/// Returns the number of specs and factions loaded.
+pub fn load_specs(path: impl AsRef<Path>) -> Result<usize, SpecError> {
+    let path = path.as_ref();
+    let io_error = |file: &Path, e: std::io::Error| SpecError {
+        file: file.to_path_buf(),
+        position: None,
+        field: None,
+        msg: e.to_string(),
+    };
+
+    let files = if path.is_dir() {
+        let mut files = Vec::new();
+        for entry in fs::read_dir(path).map_err(|e| io_error(path, e))? {
+            let file = entry.map_err(|e| io_error(path, e))?.path();
+            if file.extension().and_then(|ext| ext.to_str()) == Some("ron") {
+                files.push(file);
+            }
+        }
+        // Sort so that load order doesn't depend on the file system.
+        files.sort();
+        files
+    } else {
+        vec![path.to_path_buf()]
+    };
+
+    let mut loaded: BTreeMap<EntitySpawn, Arc<dyn Spec>> = BTreeMap::new();
+    let mut factions = BTreeMap::new();
+    // Spawn triggers and loot can refer to specs anywhere in the loaded files, check them at the
+    // end.
+    let mut references = Vec::new();
+    for file in &files {
+        let text = fs::read_to_string(file).map_err(|e| io_error(file, e))?;
+        for def in parse_spec_file(file, &text)? {
+            let spec: Arc<dyn Spec> = match def {
+                SpecDef::Mob(s) => {
+                    for trigger in &s.on_death {
+                        if let DeathTrigger::Spawn { name, .. } = trigger {
+                            let name = name.clone();
+                            references.push((file.clone(), s.name.clone(), "on_death", name));
+                        }
+                    }
+                    for name in s.loot.item_names() {
+                        references.push((file.clone(), s.name.clone(), "loot", name.to_string()));
+                    }
+                    Arc::new(s)
+                }
+                SpecDef::Item(s) => Arc::new(s),
+                SpecDef::Faction(s) => {
+                    if factions.contains_key(&s.alignment) {
+                        return Err(SpecError {
+                            file: file.clone(),
+                            position: None,
+                            field: Some((format!("{:?}", s.alignment), "alignment")),
+                            msg: "faction with this alignment is already defined".into(),
+                        });
+                    }
+                    factions.insert(s.alignment, s);
+                    continue;
+                }
+            };
+            let spawn = EntitySpawn(spec.name().to_string());
+            if loaded.contains_key(&spawn) {
+                return Err(SpecError {
+                    file: file.clone(),
+                    position: None,
+                    field: Some((spec.name().to_string(), "name")),
+                    msg: "spec with this name is already defined".into(),
+                });
+            }
+            loaded.insert(spawn, spec);
+        }
+    }
+
+    for (file, spec, field, spawn) in references {
+        let spawn = EntitySpawn(spawn);
+        if !loaded.contains_key(&spawn) && !SPECS.read().unwrap().contains_key(&spawn) {
+            return Err(SpecError {
+                file,
+                position: None,
+                field: Some((spec, field)),
+                msg: format!("unknown spawn '{}'", spawn),
+            });
+        }
+    }
+
+    let ret = loaded.len() + factions.len();
+    SPECS.write().unwrap().append(&mut loaded);
+    FACTIONS.write().unwrap().append(&mut factions);
+    Ok(ret)
+}
+
+fn parse_spec_file(file: &Path, text: &str) -> Result<Vec<SpecDef>, SpecError> {
+    let error = |position, msg| SpecError {
+        file: file.to_path_buf(),
+        position,
+        field: None,
+        msg,
+    };
+
+    let mut de = ron::de::Deserializer::from_str(text).map_err(|e| error(None, e.to_string()))?;
+    let defs: Vec<SpecDef> = match serde::Deserialize::deserialize(&mut de).and_then(|defs| {
+        de.end()?;
+        Ok(defs)
+    }) {
+        Ok(defs) => defs,
+        Err(ron::de::Error::Parser(code, pos)) => {
+            let msg = ron::de::Error::Parser(code, pos).to_string();
+            // Drop the position prefix from the message, it's shown separately.
+            let msg = msg.trim_start_matches(&format!("{}: ", pos)).to_string();
+            return Err(error(Some((pos.line, pos.col)), msg));
+        }
+        Err(e) => {
+            // Errors from the data types don't carry a position, use how far the parser got.
+            let offset = text.len() - de.remainder().len();
+            let line = text[..offset].matches('\n').count() + 1;
+            let col = offset - text[..offset].rfind('\n').map_or(0, |i| i + 1) + 1;
+            return Err(error(Some((line, col)), e.to_string()));
+        }
+    };
+
+    for def in &defs {
+        if let Err((field, msg)) = def.validate() {
+            return Err(SpecError {
+                file: file.to_path_buf(),
+                position: None,
+                field: Some((def.name(), field)),
+                msg,
+            });
+        }
+    }
+
+    Ok(defs)
+}
+
+/// Deserialize a list of biomes into a habitat bit mask.
+fn deserialize_habitat<'a, D: serde::Deserializer<'a>>(d: D) -> Result<u64, D::Error> {
+    let biomes: Vec<Biome> = serde::Deserialize::deserialize(d)?;
+    Ok(biomes.into_iter().fold(0, |acc, b| acc | (1 << b as u64)))
+}
+
 // Helpers for data conciseness.
 
 fn d<T: Default>() -> T { Default::default() }
 
+fn loot(item: &str, weight: u32, depth: i32) -> LootEntry {
+    LootEntry {
+        item: item.into(),
+        weight,
+        depth,
+    }
+}
+
 #[cfg(test)]
 mod test {
     #[test]
@@ -588,4 +1214,78 @@ mod test {
         // Names not in database don't.
         assert!(ron::de::from_str::<EntitySpawn>(&"\"tyop txet\"".to_string()).is_err());
     }
+
+    #[test]
+    fn test_parse_spec_file() {
+        use super::{parse_spec_file, Spec, SpecDef, DUNGEON, TEMPERATE};
+        use crate::ai::{Alignment, Relation};
+        use std::path::Path;
+
+        let file = Path::new("test.ron");
+        let defs = parse_spec_file(
+            file,
+            r#"[
+                Mob((name: "goblin", icon: Dreg, power: 3, habitat: [Dungeon, Grassland, Forest])),
+                Item((name: "dagger", icon: Sword, attack: 3)),
+            ]"#,
+        )
+        .unwrap();
+        assert_eq!(defs.len(), 2);
+        match &defs[0] {
+            SpecDef::Mob(spec) => {
+                assert_eq!(spec.name(), "goblin");
+                assert_eq!(spec.habitat(), DUNGEON | TEMPERATE);
+                assert_eq!(spec.rarity(), 1.0);
+            }
+            _ => panic!("Expected mob spec"),
+        }
+
+        // Typos in field names are caught.
+        let err = parse_spec_file(file, "[\n    Mob((name: \"goblin\", pwer: 3)),\n]").unwrap_err();
+        assert_eq!(err.position.map(|(line, _)| line), Some(2));
+        assert!(err.msg.contains("pwer"));
+
+        // Syntax errors.
+        let err = parse_spec_file(file, "[Mob((name: \"goblin\"))").unwrap_err();
+        assert!(err.position.is_some());
+
+        // Invalid values.
+        let err = parse_spec_file(file, "[Mob((name: \"goblin\", rarity: -1.0))]").unwrap_err();
+        assert_eq!(err.field, Some(("goblin".to_string(), "rarity")));
+
+        // Loot tables.
+        let defs = parse_spec_file(
+            file,
+            r#"[Mob((name: "goblin", loot: (chance: 50, entries: [(item: "sword")])))]"#,
+        )
+        .unwrap();
+        match &defs[0] {
+            SpecDef::Mob(spec) => assert_eq!(spec.loot.entries[0].weight, 1),
+            _ => panic!("Expected mob spec"),
+        }
+        let err = parse_spec_file(
+            file,
+            r#"[Mob((name: "goblin", loot: (entries: [(item: "sword", weight: 0)])))]"#,
+        )
+        .unwrap_err();
+        assert_eq!(err.field, Some(("goblin".to_string(), "loot")));
+
+        // Factions.
+        let defs = parse_spec_file(
+            file,
+            r#"[Faction((alignment: Animal, relations: {Player: Friendly, Enemy: Indifferent}))]"#,
+        )
+        .unwrap();
+        match &defs[0] {
+            SpecDef::Faction(spec) => {
+                assert_eq!(spec.alignment, Alignment::Animal);
+                assert_eq!(spec.relations.get(&Alignment::Player), Some(&Relation::Friendly));
+            }
+            _ => panic!("Expected faction spec"),
+        }
+        // Factions are always friendly to themselves.
+        let text = "[Faction((alignment: Animal, relations: {Animal: Hostile}))]";
+        let err = parse_spec_file(file, text).unwrap_err();
+        assert_eq!(err.field, Some(("Animal".to_string(), "relations")));
+    }
 }
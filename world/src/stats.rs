@@ -1,18 +1,15 @@
 use crate::{
-    attack_damage, effect::Damage, msg, roll, Ability, ActionOutcome, AnimState, ItemType, Slot,
-    World,
+    ai::Action, attack_damage, effect::Damage, msg, noise, roll, Ability, ActionOutcome, AnimState,
+    ItemType, Slot, World,
 };
-use calx::Dir6;
+use calx::{Dir6, RngExt};
 use calx_ecs::Entity;
 use serde_derive::{Deserialize, Serialize};
-use std::collections::BTreeMap;
 use std::default::Default;
-use std::ops::Add;
 
-/// Stats specifies static bonuses for an entity. Stats values can be added
-/// together to build composites. The Default value for Stats must be an
-/// algebraic zero element, adding it to any Stats value must leave that value
-/// unchanged.
+/// Stats specifies static bonuses for an entity. The stats of items a creature has equipped turn
+/// into modifiers on the creature's stats, see the `modifier` module. The Default value for Stats
+/// must leave the stats it's applied to unchanged.
 #[derive(Copy, Clone, Eq, PartialEq, Debug, Default, Serialize, Deserialize)]
 pub struct Stats {
     /// Generic power level
@@ -29,6 +26,12 @@ pub struct Stats {
     pub ranged_range: u32,
     /// Ranged attack power
     pub ranged_power: i32,
+    /// Bonus to action speed, normal speed is zero.
+    #[serde(default)]
+    pub speed: i32,
+    /// Radius of the light the entity gives off, zero for none.
+    #[serde(default)]
+    pub light: i32,
 
     /// Character level
     pub level: i32,
@@ -37,6 +40,12 @@ pub struct Stats {
 
     /// Bit flags for intrinsics
     pub intrinsics: u32,
+    /// Bit flags for damage types that do half damage
+    #[serde(default)]
+    pub resistances: u32,
+    /// Bit flags for damage types that do double damage
+    #[serde(default)]
+    pub vulnerabilities: u32,
 }
 
 impl Stats {
@@ -70,6 +79,7 @@ impl Stats {
             ..self
         }
     }
+    pub fn light(self, light: i32) -> Stats { Stats { light, ..self } }
     pub fn ranged_power(self, ranged_power: i32) -> Stats {
         Stats {
             ranged_power,
@@ -77,37 +87,26 @@ impl Stats {
         }
     }
 
-    pub fn add_intrinsic(&mut self, intrinsic: Intrinsic) {
-        self.intrinsics |= 1 << intrinsic as u32;
+    pub fn resistances(self, damage: &[Damage]) -> Stats {
+        Stats {
+            resistances: damage_flags(damage),
+            ..self
+        }
     }
-}
-
-impl Add<Stats> for Stats {
-    type Output = Stats;
-    #[allow(clippy::suspicious_arithmetic_impl)]
-    fn add(self, other: Stats) -> Stats {
+    pub fn vulnerabilities(self, damage: &[Damage]) -> Stats {
         Stats {
-            base_power: self.base_power + other.base_power,
-            base_attack: self.base_attack + other.base_attack,
-            base_defense: self.base_defense + other.base_defense,
-            armor: self.armor + other.armor,
-            mana: self.mana + other.mana,
-            // XXX: Must be careful to have exactly one "ranged weapon" item
-            // in the mix. A mob with a natural ranged attack equipping a
-            // ranged weapon should *not* have the ranges added together.
-            // On the other hand a "sniper scope" trinket could be a +2 range
-            // type dealie.
-            ranged_range: self.ranged_range + other.ranged_range,
-            ranged_power: self.ranged_power + other.ranged_power,
-
-            level: self.level + other.level,
-            xp: self.xp + other.xp,
-
-            intrinsics: self.intrinsics | other.intrinsics,
+            vulnerabilities: damage_flags(damage),
+            ..self
         }
     }
+
+    pub fn add_intrinsic(&mut self, intrinsic: Intrinsic) {
+        self.intrinsics |= 1 << intrinsic as u32;
+    }
 }
 
+fn damage_flags(damage: &[Damage]) -> u32 { damage.iter().fold(0, |acc, &d| acc | (1 << d as u32)) }
+
 /// Damage state component. The default state is undamaged and unarmored.
 #[derive(Copy, Clone, Eq, PartialEq, Debug, Default, Serialize, Deserialize)]
 pub struct Health {
@@ -117,31 +116,15 @@ pub struct Health {
     pub wounds: i32,
     /// Armor points get eaten away before you start getting wounds.
     pub armor: i32,
+    /// Mana spent on casting spells, recovers over time.
+    #[serde(default)]
+    pub mana_used: i32,
 }
 
 impl Health {
     pub fn new() -> Health { Default::default() }
 }
 
-#[derive(Copy, Clone, Eq, PartialEq, Ord, PartialOrd, Debug, Serialize, Deserialize)]
-/// Temporary creature properties
-pub enum Status {
-    /// Creature is acting erratically
-    Confused,
-    /// Is dead (not undead-dead, no-longer-subject-to-animate-things-logic-dead)
-    Dead,
-    /// Moves 1/3 slower than usual, stacks with Slow intrinsic.
-    Slowed,
-    /// Moves 1/3 faster than usual, stacks with Quick intrinsic.
-    Hasted,
-    /// Creature is delayed.
-    ///
-    /// This gets jumped up every time after the creature acted.
-    Delayed,
-}
-
-pub type Statuses = BTreeMap<Status, u32>;
-
 /// Stats component in the ECS that supports caching applied modifiers for efficiency.
 #[derive(Copy, Clone, Eq, PartialEq, Debug, Default, Serialize, Deserialize)]
 pub struct StatsComponent {
@@ -170,14 +153,31 @@ pub enum Intrinsic {
     Deathsplosion,
     /// Always roaming, can't go to sleep state
     Hyperactive,
+    /// Can't be poisoned.
+    PoisonImmunity,
+    /// Can't be set on fire.
+    FireImmunity,
 }
 
 impl World {
-    pub fn power(&self, e: Entity) -> i32 { self.stats(e).base_power + self.stats(e).level * 2 }
+    pub fn power(&self, e: Entity) -> i32 { self.stats(e).base_power }
 
-    pub fn attack(&self, e: Entity) -> i32 { self.stats(e).base_attack + self.stats(e).level * 2 }
+    pub fn attack(&self, e: Entity) -> i32 { self.stats(e).base_attack }
 
-    pub fn defense(&self, e: Entity) -> i32 { self.stats(e).base_defense + self.stats(e).level * 2 }
+    pub fn defense(&self, e: Entity) -> i32 { self.stats(e).base_defense }
+
+    /// Return the range of the entity's ranged attack, zero if it has none.
+    pub fn ranged_range(&self, e: Entity) -> u32 { self.stats(e).ranged_range }
+
+    /// Return whether the entity is able to shoot right now.
+    ///
+    /// Innate ranged attacks can always be used, ranged weapons need ammunition.
+    pub fn can_fire(&self, e: Entity) -> bool {
+        if self.ranged_range(e) == 0 {
+            return false;
+        }
+        self.entity_equipped(e, Slot::Ranged).is_none() || self.ammo(e).is_some()
+    }
 
     /// Return maximum health of an entity.
     pub fn max_hp(&self, e: Entity) -> i32 { self.power(e) }
@@ -220,12 +220,28 @@ impl World {
         self.stats(e).intrinsics & (1 << intrinsic as u32) != 0
     }
 
-    /// Return whether the entity has a specific temporary status
-    pub fn has_status(&self, e: Entity, status: Status) -> bool {
-        self.ecs()
-            .status
-            .get(e)
-            .map_or(false, |s| s.contains_key(&status))
+    /// Return whether the entity takes half damage from a damage type.
+    pub fn resists(&self, e: Entity, damage: Damage) -> bool {
+        self.stats(e).resistances & (1 << damage as u32) != 0
+    }
+
+    /// Return whether the entity takes double damage from a damage type.
+    pub fn is_vulnerable_to(&self, e: Entity, damage: Damage) -> bool {
+        self.stats(e).vulnerabilities & (1 << damage as u32) != 0
+    }
+
+    /// Return how much of an amount of damage of a given type gets through to the entity.
+    ///
+    /// Resistance and vulnerability to the same damage type cancel each other out.
+    pub fn damage_taken(&self, e: Entity, amount: i32, damage: Damage) -> i32 {
+        if damage.immunity().map(|i| self.has_intrinsic(e, i)) == Some(true) {
+            return 0;
+        }
+        match (self.resists(e, damage), self.is_vulnerable_to(e, damage)) {
+            (true, false) => amount / 2,
+            (false, true) => amount * 2,
+            _ => amount,
+        }
     }
 
     pub fn has_ability(&self, e: Entity, ability: Ability) -> bool {
@@ -249,8 +265,8 @@ impl World {
             }
         }
 
-        // Entity has no abilites.
-        Vec::new()
+        // Innate abilities.
+        self.ecs().abilities.get(e).cloned().unwrap_or_default()
     }
 
     pub(crate) fn damage(
@@ -265,10 +281,18 @@ impl World {
         }
 
         let max_hp = self.max_hp(e);
+        let mut amount = self.damage_taken(e, amount, damage_type);
 
         let mut hurt = false;
         let mut kill = false;
         if let Some(health) = self.ecs_mut().health.get_mut(e) {
+            // Armor points only protect against physical damage.
+            if damage_type == Damage::Physical {
+                let soak = amount.min(health.armor).max(0);
+                health.armor -= soak;
+                amount -= soak;
+            }
+
             if amount > 0 {
                 hurt = true;
                 health.wounds += amount;
@@ -302,98 +326,121 @@ impl World {
                             Damage::Physical => "die[s]",
                             Damage::Fire => "burn[s] to ash",
                             Damage::Electricity => "[is] electrocuted",
+                            Damage::Poison => "succumb[s] to poison",
+                            Damage::Cold => "freeze[s] solid",
+                            Damage::Holy => "[is] smitten",
                         };
                         self.subject(e)
                     );
                 }
                 self.spawn_fx(loc, AnimState::Gib);
             }
-            self.kill_entity(e);
+            self.die(e);
         }
     }
 
-    /// Do a single step of natural regeneration for a creature.
+    /// Heal wounds of a creature.
     ///
     /// Return amount of health gained, or None if at full health.
-    pub(crate) fn tick_regeneration(&mut self, e: Entity) -> Option<i32> {
-        let max_hp = self.max_hp(e);
-        let increase = (max_hp / 30).max(1);
-
+    pub(crate) fn heal(&mut self, e: Entity, amount: i32) -> Option<i32> {
         let health = self.ecs_mut().health.get_mut(e)?;
         if health.wounds > 0 {
-            let increase = increase.min(health.wounds);
-            health.wounds -= increase;
-            Some(increase)
+            let amount = amount.min(health.wounds);
+            health.wounds -= amount;
+            Some(amount)
         } else {
             None
         }
     }
 
-    pub(crate) fn gain_status(&mut self, e: Entity, status: Status, duration: u32) {
-        if duration == 0 {
-            return;
-        }
+    /// Return the size of the mana pool of an entity.
+    pub fn max_mana(&self, e: Entity) -> i32 { self.stats(e).mana.max(0) }
 
-        if let Some(statuses) = self.ecs_mut().status.get_mut(e) {
-            if let Some(current_duration) = statuses.get(&status).cloned() {
-                if duration > current_duration {
-                    // Pump up the duration.
-                    statuses.insert(status, duration);
-                }
-            } else {
-                // TODO: Special stuff when status first goes into effect goes here
-                statuses.insert(status, duration);
+    /// Return how much mana an entity has left to cast spells with.
+    pub fn mana(&self, e: Entity) -> i32 {
+        let used = self.ecs().health.get(e).map_or(0, |h| h.mana_used);
+        (self.max_mana(e) - used).max(0)
+    }
+
+    /// Spend mana from the pool of an entity.
+    ///
+    /// Return false and spend nothing if the entity doesn't have enough mana.
+    pub(crate) fn spend_mana(&mut self, e: Entity, amount: i32) -> bool {
+        if amount <= 0 {
+            return true;
+        }
+        if self.mana(e) < amount {
+            return false;
+        }
+        match self.ecs_mut().health.get_mut(e) {
+            Some(health) => {
+                health.mana_used += amount;
+                true
             }
+            None => false,
         }
     }
 
-    pub(crate) fn tick_statuses(&mut self, e: Entity) {
-        if let Some(statuses) = self.ecs_mut().status.get_mut(e) {
-            let mut remove = Vec::new();
+    /// Recover spent mana of an entity.
+    pub(crate) fn recover_mana(&mut self, e: Entity, amount: i32) {
+        if let Some(health) = self.ecs_mut().health.get_mut(e) {
+            health.mana_used = (health.mana_used - amount).max(0);
+        }
+    }
 
-            for (k, d) in statuses.iter_mut() {
-                *d -= 1;
-                if *d == 0 {
-                    remove.push(*k);
-                }
-            }
+    /// Recover mana over time.
+    ///
+    /// An empty mana pool fills up in the same time regardless of its size.
+    pub(crate) fn tick_mana_recovery(&mut self, e: Entity) {
+        /// How many frames it takes to fill up an empty mana pool.
+        const MANA_RECOVERY_TIME: u64 = 240;
 
-            // TODO: Special stuff when status goes out of effect for dropped statuses.
-            for k in remove.into_iter() {
-                statuses.remove(&k);
-            }
+        let max_mana = self.max_mana(e) as u64;
+        if max_mana == 0 {
+            return;
+        }
+        let interval = (MANA_RECOVERY_TIME / max_mana).max(1);
+        if self.get_tick().is_multiple_of(interval) {
+            self.recover_mana(e, 1);
         }
     }
 
+    /// Do a single step of natural regeneration for a creature.
+    ///
+    /// Return amount of health gained, or None if at full health.
+    pub(crate) fn tick_regeneration(&mut self, e: Entity) -> Option<i32> {
+        let increase = (self.max_hp(e) / 30).max(1);
+        self.heal(e, increase)
+    }
+
     /// Rebuild cached derived stats of an entity.
     ///
     /// Must be explicitly called any time either the entity's base stats or anything relating to
-    /// attached stat-affecting entities like equipped items is changed.
+    /// attached stat-affecting entities like equipped items or statuses is changed.
     pub(crate) fn rebuild_stats(&mut self, e: Entity) {
         if !self.ecs().stats.contains(e) {
             return;
         }
 
-        // Start with the entity's base stats.
-        let mut stats = self.base_stats(e);
-
-        // Add in stat modifiers from equipped items.
-        for &slot in Slot::equipment_iter() {
-            if let Some(item) = self.entity_equipped(e, slot) {
-                stats = stats + self.stats(item);
-            }
+        let stats = self.derive_stats(e);
+        if stats.light != self.ecs().stats[e].actual.light {
+            // Equipment or statuses changed how much light the entity gives off.
+            self.forget_light();
         }
-
-        // Set the derived stats.
         self.ecs_mut().stats[e].actual = stats;
     }
 
-    /// Consume one unit of nutrition
+    /// Rebuild cached derived stats of every entity.
     ///
-    /// Return false if the entity has an empty stomach.
-    pub(crate) fn consume_nutrition(&mut self, _: Entity) -> bool {
-        // TODO nutrition system
-        true
+    /// Call this after loading a save, the cached stats might have been derived with different
+    /// rules in the game version that wrote it.
+    pub fn rebuild_all_stats(&mut self) {
+        // Items first, creatures derive their stats from the items they have equipped.
+        let (items, others): (Vec<Entity>, Vec<Entity>) =
+            self.ecs().stats.ent_iter().cloned().partition(|&e| self.is_item(e));
+        for e in items.into_iter().chain(others) {
+            self.rebuild_stats(e);
+        }
     }
 
     pub(crate) fn really_melee(&mut self, e: Entity, dir: Dir6) -> ActionOutcome {
@@ -402,8 +449,11 @@ impl World {
 
         // XXX: Using power stat for damage, should this be different?
         // Do +5 since dmg 1 is really, really useless.
-        let advantage = self.attack(e) - self.defense(target) + 2 * self.stats(target).armor;
-        let damage = attack_damage(roll(self.rng()), advantage, 5 + self.power(e));
+        let damage_type = Damage::Physical;
+        let advantage = self.attack(e) - self.defense(target);
+        let armor = self.stats(target).armor;
+        let damage =
+            attack_damage(roll(self.rng()), advantage, 5 + self.power(e), armor, damage_type);
 
         if damage == 0 {
             msg!("[One] miss[es] [another].";
@@ -412,8 +462,56 @@ impl World {
             msg!("[One] hit[s] [another] for {}.", damage;
                 self.subject(e), self.object(target));
         }
-        self.damage(target, damage, Damage::Physical, Some(e));
-        self.end_turn(e);
+        self.mob_noise(e, noise::MELEE_NOISE);
+        self.damage(target, damage, damage_type, Some(e));
+        self.end_turn(e, Action::Attack);
+        Some(true)
+    }
+
+    pub(crate) fn really_fire(&mut self, e: Entity, dir: Dir6) -> ActionOutcome {
+        /// Chance for ammunition to break when it hits something.
+        const BREAK_CHANCE_ONE_IN: u32 = 3;
+
+        if !self.can_fire(e) {
+            return None;
+        }
+        let origin = self.location(e)?;
+        let ammo = if self.entity_equipped(e, Slot::Ranged).is_some() {
+            self.ammo(e)
+        } else {
+            None
+        };
+
+        let (landing, target) = self.trace_projectile(origin, dir, self.ranged_range(e));
+        self.spawn_missile_fx(origin, landing, AnimState::Missile);
+        self.mob_noise(e, noise::FIRE_NOISE);
+
+        if let Some(target) = target {
+            let damage_type = Damage::Physical;
+            let advantage = self.attack(e) - self.defense(target);
+            let (power, armor) = (5 + self.stats(e).ranged_power, self.stats(target).armor);
+            let damage = attack_damage(roll(self.rng()), advantage, power, armor, damage_type);
+
+            if damage == 0 {
+                msg!("[One] miss[es] [another].";
+                    self.subject(e), self.object(target));
+            } else {
+                msg!("[One] shoot[s] [another] for {}.", damage;
+                    self.subject(e), self.object(target));
+            }
+            self.damage(target, damage, damage_type, Some(e));
+        }
+
+        if let Some(ammo) = ammo {
+            let shot = self.split_one(ammo);
+            if target.is_some() && self.rng().one_chance_in(BREAK_CHANCE_ONE_IN) {
+                self.kill_entity(shot);
+            } else {
+                self.place_entity(shot, landing);
+            }
+        }
+
+        self.end_turn(e, Action::Shoot);
         Some(true)
     }
 
@@ -475,3 +573,69 @@ impl World {
         }
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::Intrinsic;
+    use crate::{
+        attack_damage, effect::Damage, status::Status, Ability, ExternalEntity, World, WorldSeed,
+    };
+
+    #[test]
+    fn test_damage_types() {
+        let mut world = World::new(&WorldSeed::test(1));
+        let player = world.player().unwrap();
+        {
+            let base = &mut world.ecs_mut().stats[player].base;
+            *base = base
+                .resistances(&[Damage::Cold, Damage::Holy])
+                .vulnerabilities(&[Damage::Electricity, Damage::Holy]);
+            base.add_intrinsic(Intrinsic::FireImmunity);
+        }
+        world.rebuild_stats(player);
+
+        assert_eq!(world.damage_taken(player, 10, Damage::Physical), 10);
+        assert_eq!(world.damage_taken(player, 10, Damage::Cold), 5);
+        assert_eq!(world.damage_taken(player, 10, Damage::Electricity), 20);
+        assert_eq!(world.damage_taken(player, 10, Damage::Holy), 10);
+        assert_eq!(world.damage_taken(player, 10, Damage::Fire), 0);
+
+        // Armor points soak physical damage only.
+        world.ecs_mut().health[player].armor = 3;
+        world.damage(player, 2, Damage::Fire, None);
+        assert_eq!(world.ecs().health[player].wounds, 0);
+        world.damage(player, 2, Damage::Cold, None);
+        assert_eq!(world.ecs().health[player].wounds, 1);
+        world.damage(player, 5, Damage::Physical, None);
+        assert_eq!(world.ecs().health[player].armor, 0);
+        assert_eq!(world.ecs().health[player].wounds, 3);
+    }
+
+    #[test]
+    fn test_armor_deflects_physical_damage() {
+        let hit = |armor, damage| attack_damage(0.0, 20, 10, armor, damage);
+        assert!(hit(5, Damage::Physical) < hit(0, Damage::Physical));
+        assert_eq!(hit(5, Damage::Fire), hit(0, Damage::Physical));
+        assert_eq!(hit(5, Damage::Cold), hit(0, Damage::Cold));
+    }
+
+    #[test]
+    fn test_cold_and_holy_sources() {
+        let mut world = World::new(&WorldSeed::test(1));
+        let player = world.player().unwrap();
+        let loc = world.location(player).unwrap();
+        let wraith = world.spawn(&ExternalEntity::from_name("wraith").unwrap(), loc);
+        let zap = |world: &mut World, ability: Ability| {
+            world.ecs_mut().health[wraith].wounds = 0;
+            for effect in ability.data().effects {
+                world.apply_effect_to_entity(effect, wraith, Some(player));
+            }
+            world.ecs().health[wraith].wounds
+        };
+
+        // Wraiths shrug off frost but wilt under holy light.
+        assert_eq!(zap(&mut world, Ability::FrostBolt), 4);
+        assert!(world.has_status(wraith, Status::Slowed));
+        assert_eq!(zap(&mut world, Ability::Smite), 20);
+    }
+}
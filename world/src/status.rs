@@ -0,0 +1,360 @@
+//! Temporary creature properties
+
+use crate::{
+    effect::{Damage, Effect},
+    modifier::{Modifier, ModifierOp, Stat},
+    msg,
+    stats::Intrinsic,
+    World,
+};
+use calx_ecs::Entity;
+use serde_derive::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+#[derive(Copy, Clone, Eq, PartialEq, Ord, PartialOrd, Debug, Serialize, Deserialize)]
+/// Temporary creature properties
+pub enum Status {
+    /// Creature is acting erratically
+    Confused,
+    /// Is dead (not undead-dead, no-longer-subject-to-animate-things-logic-dead)
+    Dead,
+    /// Moves 1/3 slower than usual, stacks with Slow intrinsic.
+    Slowed,
+    /// Moves 1/3 faster than usual, stacks with Quick intrinsic.
+    Hasted,
+    /// Takes poison damage over time, more doses make it worse.
+    Poisoned,
+    /// Takes fire damage over time.
+    Burning,
+    /// Can't act at all.
+    Stunned,
+    /// Can't be seen by others except from right next to it.
+    Invisible,
+    /// Heals wounds over time.
+    Regenerating,
+}
+
+/// Remaining durations of each active instance of every status on an entity.
+///
+/// Statuses are removed from the map when their last instance runs out.
+pub type Statuses = BTreeMap<Status, Vec<u32>>;
+
+/// What happens when a status is gained while it's already in effect.
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+pub enum StatusStacking {
+    /// Keep a single instance, the longer duration wins.
+    Refresh,
+    /// Add a new instance and make all instances last as long as the longest one.
+    ///
+    /// The tick effect gets applied once for each instance, so the status gets stronger the more
+    /// times it's gained.
+    Intensify,
+    /// Add a new instance that runs out on its own schedule.
+    Independent,
+}
+
+/// Static description of how a status works.
+#[derive(Copy, Clone, Debug)]
+pub struct StatusData {
+    /// Name shown in the status listing.
+    pub name: &'static str,
+    /// Message shown when the status first goes into effect.
+    pub apply_msg: Option<&'static str>,
+    /// Message shown when the last instance of the status runs out.
+    pub expire_msg: Option<&'static str>,
+    pub stacking: StatusStacking,
+    /// Intrinsic that keeps the status from being gained.
+    pub immunity: Option<Intrinsic>,
+    /// Effect applied to the entity for each instance every `period` frames.
+    pub tick_effect: Option<Effect>,
+    pub period: u32,
+    /// Stat modifiers that apply while the status is in effect.
+    pub modifiers: &'static [Modifier],
+}
+
+impl Default for StatusData {
+    fn default() -> Self {
+        StatusData {
+            name: "",
+            apply_msg: None,
+            expire_msg: None,
+            stacking: StatusStacking::Refresh,
+            immunity: None,
+            tick_effect: None,
+            period: 12,
+            modifiers: &[],
+        }
+    }
+}
+
+impl Status {
+    pub fn name(self) -> &'static str { self.data().name }
+
+    pub fn data(self) -> StatusData {
+        use Status::*;
+        match self {
+            Confused => StatusData {
+                name: "confused",
+                apply_msg: Some("[One] [is] confused."),
+                expire_msg: Some("[One] [is] no longer confused."),
+                ..Default::default()
+            },
+            Dead => StatusData {
+                name: "dead",
+                ..Default::default()
+            },
+            Slowed => StatusData {
+                name: "slowed",
+                apply_msg: Some("[One] slow[s] down."),
+                expire_msg: Some("[One] [is] no longer slowed."),
+                modifiers: &[Modifier {
+                    stat: Stat::Speed,
+                    op: ModifierOp::Add(-1),
+                }],
+                ..Default::default()
+            },
+            Hasted => StatusData {
+                name: "hasted",
+                apply_msg: Some("[One] speed[s] up."),
+                expire_msg: Some("[One] [is] no longer hasted."),
+                modifiers: &[Modifier {
+                    stat: Stat::Speed,
+                    op: ModifierOp::Add(1),
+                }],
+                ..Default::default()
+            },
+            Poisoned => StatusData {
+                name: "poisoned",
+                apply_msg: Some("[One] [is] poisoned."),
+                expire_msg: Some("[One] [is] no longer poisoned."),
+                stacking: StatusStacking::Intensify,
+                immunity: Some(Intrinsic::PoisonImmunity),
+                tick_effect: Some(Effect::Hit {
+                    amount: 1,
+                    damage: Damage::Poison,
+                }),
+                ..Default::default()
+            },
+            Burning => StatusData {
+                name: "burning",
+                apply_msg: Some("[One] catch[es] fire."),
+                expire_msg: Some("[One] stop[s] burning."),
+                stacking: StatusStacking::Independent,
+                immunity: Some(Intrinsic::FireImmunity),
+                tick_effect: Some(Effect::Hit {
+                    amount: 2,
+                    damage: Damage::Fire,
+                }),
+                ..Default::default()
+            },
+            Stunned => StatusData {
+                name: "stunned",
+                apply_msg: Some("[One] [is] stunned."),
+                expire_msg: Some("[One] [is] no longer stunned."),
+                // Stunned creatures can't dodge.
+                modifiers: &[Modifier {
+                    stat: Stat::Defense,
+                    op: ModifierOp::Percent(50),
+                }],
+                ..Default::default()
+            },
+            Invisible => StatusData {
+                name: "invisible",
+                apply_msg: Some("[One] vanish[es]."),
+                expire_msg: Some("[One] reappear[s]."),
+                ..Default::default()
+            },
+            Regenerating => StatusData {
+                name: "regenerating",
+                apply_msg: Some("[One] start[s] regenerating."),
+                expire_msg: Some("[One] stop[s] regenerating."),
+                tick_effect: Some(Effect::Heal(2)),
+                ..Default::default()
+            },
+        }
+    }
+}
+
+impl World {
+    /// Return whether the entity has a specific temporary status
+    pub fn has_status(&self, e: Entity, status: Status) -> bool {
+        self.ecs()
+            .status
+            .get(e)
+            .map(|s| s.contains_key(&status))
+            == Some(true)
+    }
+
+    /// List the statuses in effect on an entity with the longest remaining duration of each.
+    pub fn active_statuses(&self, e: Entity) -> Vec<(Status, u32)> {
+        self.ecs()
+            .status
+            .get(e)
+            .map(|s| {
+                s.iter()
+                    .map(|(&status, instances)| {
+                        (status, instances.iter().cloned().max().unwrap_or(0))
+                    })
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
+
+    pub(crate) fn gain_status(&mut self, e: Entity, status: Status, duration: u32) {
+        if duration == 0 {
+            return;
+        }
+        let data = status.data();
+        if let Some(immunity) = data.immunity {
+            if self.has_intrinsic(e, immunity) {
+                return;
+            }
+        }
+
+        let statuses = match self.ecs_mut().status.get_mut(e) {
+            Some(statuses) => statuses,
+            None => return,
+        };
+        let instances = statuses.entry(status).or_insert_with(Vec::new);
+        let is_new = instances.is_empty();
+        match data.stacking {
+            StatusStacking::Refresh => {
+                if is_new {
+                    instances.push(duration);
+                } else if duration > instances[0] {
+                    // Pump up the duration.
+                    instances[0] = duration;
+                }
+            }
+            StatusStacking::Intensify => {
+                let longest = instances.iter().cloned().fold(duration, u32::max);
+                instances.push(longest);
+                for d in instances.iter_mut() {
+                    *d = longest;
+                }
+            }
+            StatusStacking::Independent => instances.push(duration),
+        }
+
+        if is_new {
+            self.rebuild_stats(e);
+            self.status_msg(e, data.apply_msg);
+        }
+    }
+
+    /// Remove all instances of a status.
+    pub(crate) fn cure_status(&mut self, e: Entity, status: Status) {
+        let removed = match self.ecs_mut().status.get_mut(e) {
+            Some(statuses) => statuses.remove(&status).is_some(),
+            None => false,
+        };
+        if removed {
+            self.rebuild_stats(e);
+            self.status_msg(e, status.data().expire_msg);
+        }
+    }
+
+    pub(crate) fn tick_statuses(&mut self, e: Entity) {
+        let active: Vec<Status> = match self.ecs().status.get(e) {
+            Some(statuses) => statuses.keys().cloned().collect(),
+            None => return,
+        };
+
+        for status in active {
+            let data = status.data();
+            let mut ticks = 0;
+            let mut expired = false;
+            if let Some(instances) = self
+                .ecs_mut()
+                .status
+                .get_mut(e)
+                .and_then(|s| s.get_mut(&status))
+            {
+                for d in instances.iter_mut() {
+                    *d -= 1;
+                    if *d % data.period == 0 {
+                        ticks += 1;
+                    }
+                }
+                instances.retain(|&d| d > 0);
+                expired = instances.is_empty();
+            }
+
+            if let Some(effect) = data.tick_effect {
+                for _ in 0..ticks {
+                    self.apply_effect_to_entity(&effect, e, None);
+                }
+            }
+            if !self.is_alive(e) {
+                return;
+            }
+
+            if expired {
+                if let Some(statuses) = self.ecs_mut().status.get_mut(e) {
+                    statuses.remove(&status);
+                }
+                self.rebuild_stats(e);
+                self.status_msg(e, data.expire_msg);
+            }
+        }
+    }
+
+    /// Show a message about a change in the status of an entity the player can see.
+    fn status_msg(&self, e: Entity, template: Option<&str>) {
+        if let (Some(template), Some(loc)) = (template, self.location(e)) {
+            if self.player_sees(loc) {
+                msg!(template; self.subject(e));
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::Status;
+    use crate::{stats::Intrinsic, World, WorldSeed};
+
+    #[test]
+    fn test_status_stacking() {
+        let mut world = World::new(&WorldSeed::test(1));
+        let player = world.player().unwrap();
+
+        world.gain_status(player, Status::Confused, 10);
+        world.gain_status(player, Status::Confused, 5);
+        assert_eq!(world.ecs().status[player][&Status::Confused], vec![10]);
+
+        world.gain_status(player, Status::Poisoned, 10);
+        world.gain_status(player, Status::Poisoned, 5);
+        assert_eq!(world.ecs().status[player][&Status::Poisoned], vec![10, 10]);
+
+        world.gain_status(player, Status::Burning, 10);
+        world.gain_status(player, Status::Burning, 5);
+        assert_eq!(world.ecs().status[player][&Status::Burning], vec![10, 5]);
+
+        for _ in 0..5 {
+            world.tick_statuses(player);
+        }
+        assert_eq!(world.ecs().status[player][&Status::Burning], vec![5]);
+        assert_eq!(world.active_statuses(player)[0], (Status::Confused, 5));
+
+        for _ in 0..5 {
+            world.tick_statuses(player);
+        }
+        assert_eq!(world.active_statuses(player), vec![]);
+        // Two poison doses did 1 damage each and two fires did 2 each as they ran out.
+        assert_eq!(world.ecs().health[player].wounds, 6);
+    }
+
+    #[test]
+    fn test_status_immunity() {
+        let mut world = World::new(&WorldSeed::test(1));
+        let player = world.player().unwrap();
+        world.ecs_mut().stats[player].base.add_intrinsic(Intrinsic::PoisonImmunity);
+        world.rebuild_stats(player);
+
+        world.gain_status(player, Status::Poisoned, 10);
+        assert!(!world.has_status(player, Status::Poisoned));
+        world.gain_status(player, Status::Burning, 10);
+        assert!(world.has_status(player, Status::Burning));
+    }
+}
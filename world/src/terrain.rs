@@ -12,7 +12,7 @@ impl World {
 
     /// Return terrain at location.
     pub fn terrain(&self, loc: Location) -> Terrain {
-        let mut t = self.world_cache.get_terrain(loc);
+        let mut t = self.unoccupied_terrain(loc);
 
         if t == Terrain::Door && self.has_mobs(loc) {
             // Standing in the doorway opens the door.
@@ -22,6 +22,14 @@ impl World {
         t
     }
 
+    /// Return terrain at location without the effects of things standing on it.
+    pub(crate) fn unoccupied_terrain(&self, loc: Location) -> Terrain {
+        match self.terrain_overrides.get(&loc) {
+            Some(&t) => t,
+            None => self.world_cache.get_terrain(loc),
+        }
+    }
+
     /// If location contains a portal, return the destination of the portal.
     pub fn portal(&self, loc: Location) -> Option<Location> { self.world_cache.get_portal(loc) }
 
@@ -197,6 +205,12 @@ impl Terrain {
 
     pub fn is_door(self) -> bool { self.kind() == Kind::Door }
 
+    /// Terrain can be dug through to make a passage.
+    pub fn is_diggable(self) -> bool { self == Terrain::Rock || self == Terrain::Wall }
+
+    /// Terrain is deep or shallow water.
+    pub fn is_water(self) -> bool { self == Terrain::Shallows || self.kind() == Kind::Water }
+
     pub fn is_luminous(self) -> bool { self.kind() == Kind::Magma }
 
     pub fn is_wall(self) -> bool { self.form() == Form::Wall }
@@ -1,30 +1,53 @@
 use crate::{
-    ai, animations, components, desc, flags::Flags, item, spatial::Spatial, spec::EntitySpawn,
-    stats, world_cache::WorldCache, Distribution, ExternalEntity, Location, Rng, WorldSkeleton,
+    ai, animations, components, desc, effect, flags::Flags, flow_field::FlowFieldCache, hunger,
+    identify::Identification, item, light::LightMap, spatial::Spatial, spec::EntitySpawn, stats,
+    status, world_cache::WorldCache, Distribution, ExternalEntity, Location, Rng, Terrain,
+    WorldSkeleton,
 };
 use calx::seeded_rng;
 use serde_derive::{Deserialize, Serialize};
-use std::collections::HashSet;
+use std::cell::RefCell;
+use std::collections::{BTreeMap, HashSet};
 
-pub const GAME_VERSION: &str = "0.1.0";
+pub const GAME_VERSION: &str = "0.1.4";
 
 calx_ecs::build_ecs! {
+    abilities: effect::Abilities,
     anim: animations::Anim,
     brain: ai::Brain,
     desc: desc::Desc,
     health: stats::Health,
+    nutrition: hunger::Nutrition,
     item: item::Item,
     map_memory: components::MapMemory,
+    on_death: effect::DeathTriggers,
     stacking: item::Stacking,
     stats: stats::StatsComponent,
-    status: stats::Statuses,
+    status: status::Statuses,
 }
 
-#[derive(Serialize, Deserialize)]
+#[derive(Clone, Serialize, Deserialize)]
 pub struct WorldSeed {
     pub rng_seed: u32,
     pub world_skeleton: WorldSkeleton,
     pub player_character: ExternalEntity,
+    /// Whether the player needs to eat.
+    #[serde(default)]
+    pub hunger: bool,
+}
+
+impl WorldSeed {
+    /// Return a seed for an overworld with the default player and no hunger.
+    ///
+    /// Meant for tests, new games use a seed from the command line or a random one.
+    pub fn test(rng_seed: u32) -> WorldSeed {
+        WorldSeed {
+            rng_seed,
+            world_skeleton: WorldSkeleton::overworld_sprawl(),
+            player_character: ExternalEntity::from_name("player").unwrap(),
+            hunger: false,
+        }
+    }
 }
 
 /// Toplevel game state object.
@@ -38,13 +61,27 @@ pub struct World {
     /// Static startup game world
     pub(crate) world_cache: WorldCache,
     /// Spawns from worldgen that have been generated in world.
-    generated_spawns: HashSet<(Location, EntitySpawn)>,
+    pub(crate) generated_spawns: HashSet<(Location, EntitySpawn)>,
     /// Spatial index for game entities.
     pub(crate) spatial: Spatial,
+    /// Terrain changed during play, overrides the generated terrain.
+    #[serde(default)]
+    pub(crate) terrain_overrides: BTreeMap<Location, Terrain>,
+    /// Burning terrain cells and how many ticks they have left to burn.
+    #[serde(default)]
+    pub(crate) fires: BTreeMap<Location, u32>,
     /// Global gamestate flags.
     pub(crate) flags: Flags,
     /// Persistent random number generator.
     pub(crate) rng: Rng,
+    /// What the player knows about the items in the world.
+    pub(crate) identification: Identification,
+    /// Cached pathfinding data, not part of the game state.
+    #[serde(skip)]
+    pub(crate) flow_fields: RefCell<FlowFieldCache>,
+    /// Cached lighting around the player, not part of the game state.
+    #[serde(skip)]
+    pub(crate) light_map: RefCell<Option<LightMap>>,
 }
 
 impl World {
@@ -55,14 +92,24 @@ impl World {
             world_cache: WorldCache::new(world_seed.rng_seed, world_seed.world_skeleton.clone()),
             generated_spawns: Default::default(),
             spatial: Default::default(),
+            terrain_overrides: Default::default(),
+            fires: Default::default(),
             flags: Default::default(),
             rng: seeded_rng(&world_seed.rng_seed),
+            identification: Identification::new(world_seed.rng_seed),
+            flow_fields: Default::default(),
+            light_map: Default::default(),
         };
 
         ret.spawn_player(
             ret.world_cache.player_entrance(),
             &world_seed.player_character,
         );
+        if world_seed.hunger {
+            if let Some(player) = ret.player() {
+                ret.ecs.nutrition.insert(player, Default::default());
+            }
+        }
         ret.generate_world_spawns();
 
         ret
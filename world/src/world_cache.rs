@@ -1,6 +1,6 @@
 use crate::location::{Location, Portal};
 use crate::map::MapCell;
-use crate::sector::{self, Sector, WorldSkeleton};
+use crate::sector::{self, Biome, Sector, WorldSkeleton};
 use crate::spec::EntitySpawn;
 use crate::terrain::Terrain;
 use euclid::{vec2, vec3};
@@ -65,6 +65,16 @@ impl WorldCache {
 
     pub fn sector_exists(&self, sector: Sector) -> bool { self.skeleton.contains_key(&sector) }
 
+    /// Return the generation depth of a sector, or None if the sector doesn't exist.
+    pub fn sector_depth(&self, sector: Sector) -> Option<i32> {
+        self.skeleton.get(&sector).map(|spec| spec.depth)
+    }
+
+    /// Return the biome of a sector, or None if the sector doesn't exist.
+    pub fn sector_biome(&self, sector: Sector) -> Option<Biome> {
+        self.skeleton.get(&sector).map(|spec| spec.biome)
+    }
+
     /// Return latest list of spawns.
     ///
     /// `WorldCache` will return spawns from regions that have been loaded into cache. Caller will